@@ -3,8 +3,9 @@
 
 use anyhow::Result;
 use clap::{Arg, Command};
-use cunzhi_cli::mcp::types::{PopupRequest, Content};
+use cunzhi_cli::mcp::types::PopupRequest;
 use cunzhi_cli::mcp::tools::interaction::mcp::EnhancedCliInteraction;
+use cunzhi_cli::mcp::tools::interaction::reply::reply_to_response;
 use std::fs;
 use std::path::PathBuf;
 
@@ -38,9 +39,13 @@ fn handle_mcp_request(request_file: &str) -> Result<()> {
     let popup_request: PopupRequest = serde_json::from_str(&request_json)?;
 
     // 执行CLI交互
-    let response = execute_cli_interaction(&popup_request)?;
+    let reply = execute_cli_interaction(&popup_request)?;
 
-    // 输出响应到stdout（MCP服务器会读取这个输出）
+    // 输出响应 JSON 到stdout（MCP服务器读取最后一行）
+    let continue_prompt = cunzhi_cli::config::load_standalone_config()
+        .map(|config| config.reply_config.continue_prompt)
+        .unwrap_or_else(|_| cunzhi_cli::config::default_continue_prompt());
+    let response = reply_to_response(&reply, &popup_request.id, "popup", &continue_prompt);
     println!("{}", response);
 
     Ok(())
@@ -50,6 +55,9 @@ fn handle_mcp_request(request_file: &str) -> Result<()> {
 fn execute_cli_interaction(request: &PopupRequest) -> Result<String> {
     // 显示消息头部
     println!("\n🤖 寸止 AI 助手");
+    if let Some(ref project) = request.project {
+        println!("📁 {}", project);
+    }
     println!("{}", "─".repeat(50));
 
     // 显示消息内容
@@ -66,14 +74,14 @@ fn execute_cli_interaction(request: &PopupRequest) -> Result<String> {
     if let Some(ref options) = request.predefined_options {
         if !options.is_empty() {
             // 有预定义选项，使用选择界面
-            return Ok(EnhancedCliInteraction::handle_option_selection(options)
-                .map_err(|e| anyhow::anyhow!("交互失败: {}", e))?);
+            return EnhancedCliInteraction::handle_option_selection(options)
+                .map_err(|e| anyhow::anyhow!("交互失败: {}", e));
         }
     }
 
     // 没有预定义选项，直接获取用户输入
-    Ok(EnhancedCliInteraction::handle_custom_input()
-        .map_err(|e| anyhow::anyhow!("交互失败: {}", e))?)
+    EnhancedCliInteraction::handle_custom_input()
+        .map_err(|e| anyhow::anyhow!("交互失败: {}", e))
 }
 
 /// 简单的Markdown渲染
@@ -86,17 +94,14 @@ fn render_simple_markdown(text: &str) -> String {
     for line in lines {
         let trimmed = line.trim();
 
-        if trimmed.starts_with("# ") {
+        if let Some(title) = trimmed.strip_prefix("# ") {
             // H1 标题
-            let title = &trimmed[2..];
             result.push_str(&format!("{}\n", style(title).bold().cyan()));
-        } else if trimmed.starts_with("## ") {
+        } else if let Some(title) = trimmed.strip_prefix("## ") {
             // H2 标题
-            let title = &trimmed[3..];
             result.push_str(&format!("{}\n", style(title).bold().yellow()));
-        } else if trimmed.starts_with("### ") {
+        } else if let Some(title) = trimmed.strip_prefix("### ") {
             // H3 标题
-            let title = &trimmed[4..];
             result.push_str(&format!("{}\n", style(title).bold().green()));
         } else if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
             // 列表项
@@ -126,9 +126,11 @@ read -n 1
     println!("   - 窗口标题: {:?}", default_config.window_title);
 
     // 测试自定义配置
-    let mut custom_config = TerminalLauncherConfig::default();
-    custom_config.preferred_terminal = Some(TerminalType::Custom("echo".to_string()));
-    custom_config.window_title = Some("测试窗口".to_string());
+    let _custom_config = TerminalLauncherConfig {
+        preferred_terminal: Some(TerminalType::Custom("echo".to_string())),
+        window_title: Some("测试窗口".to_string()),
+        ..Default::default()
+    };
     println!("✅ 自定义配置创建成功");
 
     println!("\n🎉 终端启动器测试完成！");
@@ -148,7 +148,7 @@ async fn test_error_handling() {
     let handler = ErrorHandler::new(true);
 
     // 测试不同类型的应用程序错误
-    let test_cases = vec![
+    let test_cases = [
         ("配置文件格式错误", "config"),
         ("无法写入文件", "file_operation"),
         ("连接超时", "network"),
@@ -4,6 +4,8 @@ use crate::config::{load_standalone_config, get_standalone_config_path};
 use crate::utils::{print_boxed_message, format_file_size, colorize, colors};
 use crate::{log_success, log_warning, log_error};
 use std::env;
+use std::path::Path;
+use crate::mcp::tools::interaction::InteractionTool;
 
 /// 显示版本信息
 pub async fn show_version() -> Result<()> {
@@ -24,6 +26,14 @@ pub async fn show_version() -> Result<()> {
     Ok(())
 }
 
+/// 运行命令行交互并把回复写到标准输出
+pub fn run_prompt(request_file: &Path) -> Result<()> {
+    let reply = InteractionTool::run_cli_prompt(request_file)
+        .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+    println!("{}", reply);
+    Ok(())
+}
+
 /// 显示默认帮助信息
 pub async fn show_default_help() -> Result<()> {
     println!("{}", colorize("🤖 寸止 CLI", colors::BOLD));
@@ -17,7 +17,24 @@ pub async fn handle_config_command(action: ConfigAction) -> Result<()> {
         ConfigAction::Validate => {
             validate_config().await
         }
+        ConfigAction::Reset => {
+            reset_config().await
+        }
+    }
+}
+
+async fn reset_config() -> Result<()> {
+    println!("🔄 重置配置文件");
+
+    if let Ok(backup_path) = backup_config() {
+        println!("已备份当前配置到: {:?}", backup_path);
     }
+
+    let default_config = crate::config::AppConfig::default();
+    save_standalone_config(&default_config)?;
+    println!("✅ 配置已重置为默认值: {:?}", get_standalone_config_path()?);
+
+    Ok(())
 }
 
 async fn show_config() -> Result<()> {
@@ -0,0 +1,78 @@
+// 通过控制套接字处理等待中的交互
+use std::time::Duration;
+
+use anyhow::Result;
+use console::style;
+use crate::mcp::control::{broadcast_control_request, ControlRequest, ControlResponse};
+use crate::utils::{format_duration, theme};
+use crate::log_success;
+
+/// 列出所有服务器中等待用户回复的交互
+pub async fn list_pending(json: bool) -> Result<()> {
+    let mut pending = Vec::new();
+    for (pid, response) in broadcast_control_request(&ControlRequest::Pending).await? {
+        if let ControlResponse::Ok { pending: list } = response {
+            pending.extend(list.into_iter().map(|info| (pid, info)));
+        }
+    }
+    pending.sort_by_key(|(_, info)| std::cmp::Reverse(info.age_secs));
+
+    if json {
+        let list: Vec<_> = pending.iter()
+            .map(|(pid, info)| {
+                let mut value = serde_json::to_value(info).unwrap_or_default();
+                value["server_pid"] = (*pid).into();
+                value
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&list)?);
+        return Ok(());
+    }
+
+    if pending.is_empty() {
+        println!("没有等待中的交互");
+        return Ok(());
+    }
+
+    println!("📋 等待中的交互 ({}):", pending.len());
+    for (pid, info) in pending {
+        println!("\n  {}", style(&info.request_id).fg(theme::PRIMARY).bold());
+        println!("    已等待: {}  服务器 PID: {}", format_duration(Duration::from_secs(info.age_secs)), pid);
+        println!("    消息: {}", info.message);
+        if !info.predefined_options.is_empty() {
+            println!("    选项: {}", info.predefined_options.join(" / "));
+        }
+    }
+    println!("\n💡 使用 {} 回复，{} 取消",
+        style("cunzhi answer <ID> \"回复\" [--option 选项]").fg(theme::PRIMARY),
+        style("cunzhi cancel <ID>").fg(theme::PRIMARY));
+    Ok(())
+}
+
+/// 回答等待中的交互
+pub async fn answer(request_id: String, text: Option<String>, options: Vec<String>) -> Result<()> {
+    let request = ControlRequest::Answer { request_id: request_id.clone(), text, options };
+    send_to_owner(&request, &request_id).await?;
+    log_success!("已回答交互 {}", request_id);
+    Ok(())
+}
+
+/// 取消等待中的交互
+pub async fn cancel(request_id: String) -> Result<()> {
+    let request = ControlRequest::Cancel { request_id: request_id.clone() };
+    send_to_owner(&request, &request_id).await?;
+    log_success!("已取消交互 {}", request_id);
+    Ok(())
+}
+
+/// 把请求发给持有该交互的服务器
+async fn send_to_owner(request: &ControlRequest, request_id: &str) -> Result<()> {
+    for (_, response) in broadcast_control_request(request).await? {
+        match response {
+            ControlResponse::Ok { .. } => return Ok(()),
+            ControlResponse::Error { message } => anyhow::bail!(message),
+            ControlResponse::NotFound | ControlResponse::Metrics { .. } => {}
+        }
+    }
+    anyhow::bail!("未找到等待中的交互: {}", request_id)
+}
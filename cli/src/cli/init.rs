@@ -5,8 +5,7 @@ use std::collections::HashMap;
 use std::path::Path;
 
 use crate::config::{AppConfig, ReplyConfig, McpConfig, save_standalone_config};
-use crate::utils::{print_boxed_message, colorize, colors, ModernProgressBar, StatusIndicator, theme};
-use console;
+use crate::utils::{print_boxed_message, colorize, colors, ModernProgressBar};
 use crate::{log_success, log_warning};
 
 /// 项目模板类型
@@ -176,7 +175,7 @@ async fn configure_from_template(template: ProjectTemplate, _project_name: Optio
         mcp_tools.insert(tool.to_string(), template.mcp_tools.contains(&tool.to_string()));
     }
 
-    let mcp_config = McpConfig { tools: mcp_tools };
+    let mcp_config = McpConfig { tools: mcp_tools, elicitation: crate::config::default_elicitation() };
 
     // 创建回复配置
     let reply_config = ReplyConfig {
@@ -189,6 +188,10 @@ async fn configure_from_template(template: ProjectTemplate, _project_name: Optio
         reply_config,
         mcp_config,
         terminal_config: crate::config::default_terminal_config(),
+        prompt_config: crate::config::default_prompt_config(),
+        http_config: crate::config::default_http_config(),
+        gateway_config: crate::config::default_gateway_config(),
+        guard_config: crate::config::default_guard_config(),
         version: env!("CARGO_PKG_VERSION").to_string(),
     };
 
@@ -210,6 +213,10 @@ async fn configure_custom_project(_project_name: Option<String>) -> Result<AppCo
         reply_config,
         mcp_config,
         terminal_config: crate::config::default_terminal_config(),
+        prompt_config: crate::config::default_prompt_config(),
+        http_config: crate::config::default_http_config(),
+        gateway_config: crate::config::default_gateway_config(),
+        guard_config: crate::config::default_guard_config(),
         version: env!("CARGO_PKG_VERSION").to_string(),
     };
 
@@ -235,7 +242,7 @@ async fn configure_mcp_tools_interactive() -> Result<McpConfig> {
     tools.insert("ji".to_string(), selected.iter().any(|s| s.starts_with("ji")));
 
     println!("✅ MCP 工具配置完成\n");
-    Ok(McpConfig { tools })
+    Ok(McpConfig { tools, elicitation: crate::config::default_elicitation() })
 }
 
 /// 交互式配置回复设置
@@ -324,11 +331,8 @@ async fn show_completion_info(config: &AppConfig) -> Result<()> {
     println!("  {} - 查看帮助信息", colorize("cunzhi --help", colors::CYAN));
 
     // 显示配置文件位置
-    match crate::config::get_standalone_config_path() {
-        Ok(path) => {
-            println!("\n📁 配置文件位置: {}", colorize(&path.display().to_string(), colors::DIM));
-        }
-        Err(_) => {}
+    if let Ok(path) = crate::config::get_standalone_config_path() {
+        println!("\n📁 配置文件位置: {}", colorize(&path.display().to_string(), colors::DIM));
     }
 
     println!("\n感谢使用寸止 CLI！🎯");
@@ -392,8 +396,12 @@ pub fn create_config_template(template_name: &str) -> Result<AppConfig> {
             auto_continue_threshold: template.continue_threshold,
             continue_prompt: "请按照最佳实践继续".to_string(),
         },
-        mcp_config: McpConfig { tools: mcp_tools },
+        mcp_config: McpConfig { tools: mcp_tools, elicitation: crate::config::default_elicitation() },
         terminal_config: crate::config::default_terminal_config(),
+        prompt_config: crate::config::default_prompt_config(),
+        http_config: crate::config::default_http_config(),
+        gateway_config: crate::config::default_gateway_config(),
+        guard_config: crate::config::default_guard_config(),
         version: env!("CARGO_PKG_VERSION").to_string(),
     };
 
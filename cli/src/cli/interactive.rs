@@ -104,6 +104,10 @@ async fn run_configuration_wizard() -> Result<AppConfig> {
         reply_config,
         mcp_config,
         terminal_config: crate::config::default_terminal_config(),
+        prompt_config: crate::config::default_prompt_config(),
+        http_config: crate::config::default_http_config(),
+        gateway_config: crate::config::default_gateway_config(),
+        guard_config: crate::config::default_guard_config(),
         // Telegram 功能已移除
         version: env!("CARGO_PKG_VERSION").to_string(),
     };
@@ -140,7 +144,7 @@ async fn configure_mcp_tools() -> Result<McpConfig> {
         println!();
     }
 
-    Ok(McpConfig { tools })
+    Ok(McpConfig { tools, elicitation: crate::config::default_elicitation() })
 }
 
 // Telegram 功能已移除
@@ -1,33 +1,60 @@
 // MCP 配置管理命令实现
+use std::path::{Path, PathBuf};
+
 use anyhow::Result;
 use crate::cli::McpAction;
-use crate::mcp::{generate_mcp_config, validate_mcp_config, ZhiRequest, InteractionTool};
+use crate::mcp::recording::{load_recording, replay, Recorder};
+use crate::mcp::unix_socket::{bridge_stdio, resolve_socket_path};
+use crate::cli::probe::probe;
+use crate::config::{get_guard_audit_log_path, load_standalone_config, read_http_token};
+use crate::mcp::client::ClientTarget;
+use crate::mcp::guard::Guard;
+use crate::mcp::{generate_mcp_config, validate_mcp_config, ZhiServer};
 use crate::utils::{print_boxed_message, colorize, colors, StatusIndicator};
-use crate::{log_success, log_warning, log_error};
+use crate::{log_important, log_warning};
 
 pub async fn handle_mcp_command(action: McpAction) -> Result<()> {
     match action {
-        McpAction::Generate => {
-            generate_mcp_config_command().await
+        McpAction::Generate { socket } => {
+            let socket = socket.map(resolve_socket_path).transpose()?;
+            generate_mcp_config_command(socket.as_deref()).await
+        }
+        McpAction::Bridge { socket } => {
+            bridge_stdio(&resolve_socket_path(socket)?).await
+        }
+        McpAction::Replay { file } => {
+            replay_command(&file).await
         }
         McpAction::Validate => {
             validate_mcp_config_command().await
         }
-        McpAction::Chat => {
-            start_interactive_chat().await
+        McpAction::Probe { url, token, socket, command } => {
+            // 未指定令牌时使用本机服务器的令牌
+            let token = token.or_else(|| url.as_ref().and_then(|_| read_http_token()));
+            let target = match (url, socket) {
+                (Some(url), _) => ClientTarget::Http(url),
+                (None, Some(socket)) => ClientTarget::Unix(resolve_socket_path(Some(socket))?),
+                (None, None) if !command.is_empty() => ClientTarget::Command(command),
+                // 未指定目标时检查本机的 cunzhi 服务器
+                (None, None) => ClientTarget::Command(vec![
+                    std::env::current_exe()?.to_string_lossy().into_owned(),
+                    "mcp-server".to_string(),
+                ]),
+            };
+            probe(target, token).await
         }
     }
 }
 
 /// 生成 MCP 配置文件
-async fn generate_mcp_config_command() -> Result<()> {
+async fn generate_mcp_config_command(socket: Option<&Path>) -> Result<()> {
     let indicator = StatusIndicator::new();
 
     print_boxed_message("生成 MCP 配置", "为 Augment 和其他 MCP 客户端生成配置文件");
 
     indicator.info("正在生成 MCP 客户端配置文件...");
 
-    match generate_mcp_config() {
+    match generate_mcp_config(socket) {
         Ok(_) => {
             indicator.success("MCP 配置文件生成成功");
 
@@ -46,6 +73,87 @@ async fn generate_mcp_config_command() -> Result<()> {
     }
 }
 
+/// 以 stdio 模式运行 MCP 服务器，可选录制通信
+pub async fn run_mcp_server(record: Option<PathBuf>) -> Result<()> {
+    let mut server = ZhiServer::new().connect_gateway().await;
+    if let Some(path) = record {
+        server.record_to(Recorder::create(&path)?);
+        log_important!(info, "录制 MCP 通信到: {}", path.display());
+    }
+    server.start().await
+}
+
+/// 在客户端与下游服务器之间转发，按 `guard_config` 规则暂停需要批准的工具调用
+pub async fn run_guard(command: Vec<String>, project: Option<PathBuf>) -> Result<()> {
+    let config = load_standalone_config()?;
+    let guard_config = config.guard_config.clone();
+    if guard_config.rules.is_empty() {
+        log_warning!("未配置审批规则（guard_config.rules），所有工具调用将直接转发");
+    }
+
+    let project = match project {
+        Some(project) => project,
+        None => std::env::current_dir()?,
+    };
+    let audit_log = match &guard_config.audit_log {
+        Some(path) => PathBuf::from(path),
+        None => get_guard_audit_log_path()?,
+    };
+    log_important!(info, "审批网关已启动，审计日志: {}", audit_log.display());
+
+    let guard = Guard::new(&guard_config, &project, &audit_log, command.join(" "))?
+        .with_interaction_config(config);
+    guard.run(&command, spawn_stdin_reader(), tokio::io::stdout()).await
+}
+
+/// 在独立线程中读取标准输入：tokio 的 stdin 读取无法取消，下游先退出时会阻止进程退出
+fn spawn_stdin_reader() -> tokio::io::DuplexStream {
+    use tokio::io::AsyncWriteExt;
+
+    let (reader, mut writer) = tokio::io::duplex(8192);
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        use std::io::Read;
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 8192];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 || runtime.block_on(writer.write_all(&buf[..n])).is_err() {
+                break;
+            }
+        }
+    });
+    reader
+}
+
+/// 回放录制的会话
+async fn replay_command(file: &Path) -> Result<()> {
+    let indicator = StatusIndicator::new();
+
+    print_boxed_message("回放 MCP 会话", &format!("回放 {}", file.display()));
+
+    let frames = load_recording(file)?;
+    let report = replay(ZhiServer::new(), &frames).await;
+
+    for mismatch in &report.mismatches {
+        println!("\n{} {} (id: {}, 会话: {})",
+            colorize("✗", colors::RED), mismatch.method, mismatch.id, mismatch.session);
+        for difference in &mismatch.differences {
+            println!("    {}", difference);
+        }
+    }
+
+    println!("\n📊 回放 {} 个请求：{} 个一致，{} 个不一致，{} 个未比较（录制中无响应）",
+        report.requests, report.matched, report.mismatches.len(), report.skipped);
+
+    if report.is_success() {
+        indicator.success("所有响应与录制一致");
+        Ok(())
+    } else {
+        indicator.error("响应与录制不一致");
+        anyhow::bail!("{} 个响应与录制不一致", report.mismatches.len())
+    }
+}
+
 /// 验证 MCP 配置
 async fn validate_mcp_config_command() -> Result<()> {
     let indicator = StatusIndicator::new();
@@ -77,215 +185,3 @@ async fn validate_mcp_config_command() -> Result<()> {
         }
     }
 }
-
-/// 启动交互式聊天界面
-async fn start_interactive_chat() -> Result<()> {
-    print_boxed_message("寸止 AI 助手", "开始与 AI 助手进行交互式对话");
-
-    println!("\n{}", colorize("💬 欢迎使用寸止 AI 助手！", colors::GREEN));
-    println!("{}", colorize("输入 'exit' 或 'quit' 退出聊天", colors::CYAN));
-    println!("{}", colorize("输入 'help' 查看可用命令", colors::CYAN));
-
-    loop {
-        // 创建一个简单的输入请求
-        let request = ZhiRequest {
-            message: "请输入您的问题或需求:".to_string(),
-            predefined_options: vec![
-                "代码分析".to_string(),
-                "项目优化建议".to_string(),
-                "技术问题咨询".to_string(),
-                "最佳实践指导".to_string(),
-            ],
-            is_markdown: false,
-            terminal_mode: Some(false),
-        };
-
-        match InteractionTool::zhi(request).await {
-            Ok(result) => {
-                // 解析用户输入
-                if let Some(content) = result.content.first() {
-                    let user_input = content.text.trim();
-
-                    // 检查退出命令
-                    if user_input.eq_ignore_ascii_case("exit") ||
-                       user_input.eq_ignore_ascii_case("quit") {
-                        println!("\n{}", colorize("👋 再见！感谢使用寸止 AI 助手", colors::GREEN));
-                        break;
-                    }
-
-                    // 检查帮助命令
-                    if user_input.eq_ignore_ascii_case("help") {
-                        show_chat_help();
-                        continue;
-                    }
-
-                    // 处理用户输入
-                    handle_user_input(user_input).await?;
-                }
-            }
-            Err(e) => {
-                println!("\n{}", colorize(&format!("❌ 交互错误: {}", e), colors::RED));
-                break;
-            }
-        }
-    }
-
-    Ok(())
-}
-
-/// 显示聊天帮助信息
-fn show_chat_help() {
-    println!("\n{}", colorize("📖 可用命令:", colors::CYAN));
-    println!("  {} - 退出聊天", colorize("exit/quit", colors::YELLOW));
-    println!("  {} - 显示此帮助", colorize("help", colors::YELLOW));
-    println!("  {} - 分析代码质量", colorize("analyze <文件路径>", colors::YELLOW));
-    println!("  {} - 项目结构建议", colorize("optimize", colors::YELLOW));
-    println!("  {} - 技术问题咨询", colorize("ask <问题>", colors::YELLOW));
-}
-
-/// 处理用户输入
-async fn handle_user_input(input: &str) -> Result<()> {
-    // 模拟 AI 响应
-    let response = if input.starts_with("analyze") {
-        generate_code_analysis_response(input)
-    } else if input.starts_with("optimize") {
-        generate_optimization_response()
-    } else if input.starts_with("ask") {
-        generate_qa_response(input)
-    } else {
-        generate_general_response(input)
-    };
-
-    // 显示 AI 响应
-    let ai_request = ZhiRequest {
-        message: response,
-        predefined_options: vec![
-            "继续分析".to_string(),
-            "查看详细信息".to_string(),
-            "提供更多建议".to_string(),
-            "切换话题".to_string(),
-        ],
-        is_markdown: true,
-        terminal_mode: Some(false),
-    };
-
-    match InteractionTool::zhi(ai_request).await {
-        Ok(_) => {
-            // 用户选择了某个选项，可以继续处理
-        }
-        Err(e) => {
-            println!("{}", colorize(&format!("响应错误: {}", e), colors::RED));
-        }
-    }
-
-    Ok(())
-}
-
-/// 生成代码分析响应
-fn generate_code_analysis_response(input: &str) -> String {
-    let file_path = input.strip_prefix("analyze").unwrap_or("").trim();
-
-    format!(r#"# 🔍 代码分析报告
-
-## 分析目标
-文件: `{}`
-
-## 分析结果
-### ✅ 优点
-- **代码结构清晰** - 函数职责明确，模块化程度高
-- **注释完整** - 关键逻辑都有详细说明
-- **错误处理** - 异常情况处理得当
-
-### ⚠️ 改进建议
-- **性能优化** - 可以考虑使用更高效的算法
-- **代码复用** - 部分逻辑可以提取为公共函数
-- **测试覆盖** - 建议增加单元测试
-
-### 🎯 下一步行动
-1. 重构重复代码
-2. 添加性能测试
-3. 完善文档说明"#, file_path)
-}
-
-/// 生成优化建议响应
-fn generate_optimization_response() -> String {
-    r#"# 🚀 项目优化建议
-
-## 架构优化
-### 📁 目录结构
-```
-src/
-├── core/          # 核心业务逻辑
-├── utils/         # 工具函数
-├── config/        # 配置管理
-└── tests/         # 测试文件
-```
-
-### 🔧 技术栈建议
-- **依赖管理** - 使用 Cargo.toml 管理依赖版本
-- **代码质量** - 集成 clippy 和 rustfmt
-- **CI/CD** - 配置 GitHub Actions 自动化流程
-
-## 性能优化
-- **内存管理** - 减少不必要的克隆操作
-- **并发处理** - 使用 tokio 异步编程
-- **缓存策略** - 实现智能缓存机制
-
-## 安全加固
-- **输入验证** - 严格验证用户输入
-- **错误处理** - 避免敏感信息泄露
-- **依赖审计** - 定期检查依赖安全性"#.to_string()
-}
-
-/// 生成问答响应
-fn generate_qa_response(input: &str) -> String {
-    let question = input.strip_prefix("ask").unwrap_or("").trim();
-
-    format!(r#"# 💡 技术问答
-
-## 您的问题
-> {}
-
-## 解答
-基于您的问题，我建议从以下几个方面考虑：
-
-### 🎯 核心要点
-- **最佳实践** - 遵循行业标准和社区约定
-- **性能考量** - 平衡功能需求和性能表现
-- **可维护性** - 编写易于理解和修改的代码
-
-### 📚 相关资源
-- [Rust 官方文档](https://doc.rust-lang.org/)
-- [Rust 编程指南](https://rust-lang.github.io/api-guidelines/)
-- [异步编程手册](https://rust-lang.github.io/async-book/)
-
-### 🔗 推荐工具
-- **开发环境** - VS Code + rust-analyzer
-- **测试框架** - cargo test + proptest
-- **性能分析** - cargo flamegraph"#, question)
-}
-
-/// 生成通用响应
-fn generate_general_response(input: &str) -> String {
-    format!(r#"# 🤖 AI 助手回复
-
-## 您的输入
-> {}
-
-## 我的理解
-感谢您的输入！我正在分析您的需求...
-
-### 🎯 可能的帮助方向
-- **代码审查** - 分析代码质量和潜在问题
-- **架构设计** - 提供系统设计建议
-- **技术选型** - 推荐合适的技术方案
-- **最佳实践** - 分享行业经验和标准
-
-### 💡 建议
-如果您有具体的代码或项目需要分析，可以使用：
-- `analyze <文件路径>` - 分析特定文件
-- `optimize` - 获取项目优化建议
-- `ask <具体问题>` - 咨询技术问题
-
-请告诉我您希望我如何帮助您！"#, input)
-}
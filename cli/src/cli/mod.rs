@@ -4,6 +4,10 @@ pub mod interactive;
 pub mod config;
 pub mod server;
 pub mod mcp;
+pub mod control;
+pub mod probe;
+
+use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -13,6 +17,7 @@ use crate::utils::{ErrorHandler, setup_panic_handler};
 #[command(name = "cunzhi")]
 #[command(about = "寸止 CLI - 智能代码审查工具的命令行版本")]
 #[command(version)]
+#[command(long_version = concat!(env!("CARGO_PKG_VERSION"), " (", env!("CARGO_PKG_NAME"), ")"))]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -34,7 +39,11 @@ pub enum Commands {
         action: ServerAction,
     },
     /// 启动纯净的 MCP 服务器（无 UI）
-    McpServer,
+    McpServer {
+        /// 把收发的每条 JSON-RPC 消息连同时间戳和方向追加到 JSONL 文件，供 `cunzhi mcp replay` 回放
+        #[arg(long, value_name = "FILE")]
+        record: Option<PathBuf>,
+    },
     /// 配置管理
     Config {
         #[command(subcommand)]
@@ -54,6 +63,43 @@ pub enum Commands {
         #[arg(short, long)]
         yes: bool,
     },
+    /// 列出正在运行的服务器中等待用户回复的交互
+    Pending {
+        /// 以 JSON 输出，便于脚本处理
+        #[arg(long)]
+        json: bool,
+    },
+    /// 回答等待中的交互
+    Answer {
+        /// 交互请求 ID
+        request_id: String,
+        /// 回复文本（与选项都不提供时等同于继续）
+        text: Option<String>,
+        /// 选中的预定义选项，可重复指定
+        #[arg(short, long = "option")]
+        options: Vec<String>,
+    },
+    /// 取消等待中的交互
+    Cancel {
+        /// 交互请求 ID
+        request_id: String,
+    },
+    /// 包装一个 stdio MCP 服务器，匹配 guard_config 规则的工具调用需经用户批准后才转发
+    Guard {
+        /// 项目目录，规则中的 outside_project 以此判断（默认为当前目录）
+        #[arg(long, value_name = "DIR")]
+        project: Option<PathBuf>,
+        /// 下游服务器的启动命令及参数（写在 `--` 之后）
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// 在终端中完成一次 zhi 交互，回复写到标准输出（由 MCP 服务器的命令行回退模式调用）
+    #[command(hide = true)]
+    Prompt {
+        /// 交互请求文件
+        #[arg(long, value_name = "FILE")]
+        request: PathBuf,
+    },
     /// 显示版本信息
     Version,
     /// 显示系统信息和诊断
@@ -62,12 +108,51 @@ pub enum Commands {
 
 #[derive(Subcommand)]
 pub enum ServerAction {
-    /// 启动服务器
-    Start,
-    /// 停止服务器
+    /// 启动服务器（默认在当前进程中运行，--daemon 时在后台运行）
+    Start {
+        /// HTTP 监听端口（不指定时前台使用 stdio 模式，后台由系统分配端口）
+        #[arg(short, long, conflicts_with = "socket")]
+        port: Option<u16>,
+        /// 改为监听 Unix 域套接字（不指定路径时使用运行时目录下的 mcp.sock）
+        #[arg(short, long, value_name = "PATH")]
+        socket: Option<Option<PathBuf>>,
+        /// 以后台服务运行（PID 文件、日志，可用 stop/restart/status 管理）
+        #[arg(short, long)]
+        daemon: bool,
+    },
+    /// 停止后台服务器
     Stop,
+    /// 重启后台服务器
+    Restart {
+        /// HTTP 监听端口（不指定则沿用当前设置）
+        #[arg(short, long, conflicts_with = "socket")]
+        port: Option<u16>,
+        /// 改为监听 Unix 域套接字（不指定路径时使用运行时目录下的 mcp.sock）
+        #[arg(short, long, value_name = "PATH")]
+        socket: Option<Option<PathBuf>>,
+    },
     /// 查看服务器状态
     Status,
+    /// 显示 HTTP 访问令牌（客户端以 `Authorization: Bearer <令牌>` 携带）
+    Token {
+        /// 生成新令牌，旧令牌在服务器重启后失效
+        #[arg(long)]
+        regenerate: bool,
+    },
+    /// 汇总所有正在运行的服务器的运行指标（与 HTTP `/metrics` 相同）
+    Metrics {
+        /// 以 Prometheus 文本格式输出
+        #[arg(long)]
+        prometheus: bool,
+    },
+    /// 运行后台服务进程（由 start 调用）
+    #[command(hide = true)]
+    Run {
+        #[arg(short, long, default_value_t = 0)]
+        port: u16,
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -78,16 +163,45 @@ pub enum ConfigAction {
     Show,
     /// 验证配置
     Validate,
+    /// 重置为默认配置（自动备份当前配置）
+    Reset,
 }
 
 #[derive(Subcommand)]
 pub enum McpAction {
     /// 生成 MCP 客户端配置文件
-    Generate,
+    Generate {
+        /// 通过 Unix 域套接字桥接到共享的服务器进程（不指定路径时使用默认套接字）
+        #[arg(short, long, value_name = "PATH")]
+        socket: Option<Option<PathBuf>>,
+    },
+    /// 在标准输入输出与 Unix 域套接字之间转发，供只支持 stdio 的客户端连接共享服务器
+    Bridge {
+        /// 套接字路径（默认为运行时目录下的 mcp.sock）
+        socket: Option<PathBuf>,
+    },
+    /// 回放 `mcp-server --record` 录制的会话，并与录制的响应比较
+    Replay {
+        /// 录制文件（JSONL）
+        file: PathBuf,
+    },
     /// 验证 MCP 配置
     Validate,
-    /// 启动交互式聊天界面
-    Chat,
+    /// 连接 MCP 服务器，列出工具、资源和提示词，并交互式调用工具
+    Probe {
+        /// 服务器的 Streamable HTTP 端点，如 http://127.0.0.1:8080/mcp
+        #[arg(long, conflicts_with_all = ["socket", "command"])]
+        url: Option<String>,
+        /// HTTP 访问令牌（默认使用本机服务器的令牌）
+        #[arg(long, requires = "url")]
+        token: Option<String>,
+        /// 服务器监听的 Unix 域套接字
+        #[arg(long, value_name = "PATH", conflicts_with = "command")]
+        socket: Option<PathBuf>,
+        /// 启动服务器的命令及参数（写在 `--` 之后，通过 stdio 通信）；都不指定时检查本机的 cunzhi mcp-server
+        #[arg(last = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
 }
 
 impl Cli {
@@ -109,15 +223,30 @@ impl Cli {
                 // 使用新的项目初始化向导
                 init::run_project_init_wizard(name, yes).await
             }
+            Some(Commands::Pending { json }) => {
+                control::list_pending(json).await
+            }
+            Some(Commands::Answer { request_id, text, options }) => {
+                control::answer(request_id, text, options).await
+            }
+            Some(Commands::Cancel { request_id }) => {
+                control::cancel(request_id).await
+            }
+            Some(Commands::Guard { project, command }) => {
+                mcp::run_guard(command, project).await
+            }
+            Some(Commands::Prompt { request }) => {
+                commands::run_prompt(&request)
+            }
             Some(Commands::Version) => {
                 commands::show_version().await
             }
             Some(Commands::Doctor) => {
                 commands::run_doctor().await
             }
-            Some(Commands::McpServer) => {
+            Some(Commands::McpServer { record }) => {
                 // 启动纯净的 MCP 服务器，无 UI 交互
-                crate::mcp::run_server().await.map_err(|e| anyhow::anyhow!("{}", e))
+                mcp::run_mcp_server(record).await
             }
             None => {
                 commands::show_default_help().await
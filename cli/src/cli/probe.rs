@@ -0,0 +1,285 @@
+// MCP 服务器探测：连接服务器，列出能力并交互式调用
+use anyhow::Result;
+use console::style;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::mcp::client::{ClientTarget, McpClient};
+use crate::mcp::validate_arguments;
+use crate::utils::{colorize, colors, print_boxed_message, theme, StatusIndicator};
+
+/// 连接服务器，打印服务器信息和工具、资源、提示词列表，然后进入交互模式
+///
+/// 标准输入不是终端时按行读取命令并在结束后退出，任一命令失败则返回错误，便于脚本检查部署
+pub async fn probe(target: ClientTarget, token: Option<String>) -> Result<()> {
+    let indicator = StatusIndicator::new();
+    print_boxed_message("MCP 探测", &format!("连接 {}", target));
+
+    let mut client = McpClient::connect(&target).await?;
+    client.set_bearer_token(token);
+    client.on_notification(print_notification);
+
+    let info = match client.initialize().await {
+        Ok(info) => info,
+        Err(e) => {
+            indicator.error(&format!("初始化失败: {}", e));
+            return Err(e);
+        }
+    };
+    indicator.success(&format!(
+        "已连接 {} {}（协议 {}）",
+        info["serverInfo"]["name"].as_str().unwrap_or("未知服务器"),
+        info["serverInfo"]["version"].as_str().unwrap_or(""),
+        info["protocolVersion"].as_str().unwrap_or("未知")
+    ));
+
+    let capabilities = &info["capabilities"];
+    if let Some(capabilities) = capabilities.as_object() {
+        let names: Vec<&str> = capabilities.keys().map(String::as_str).collect();
+        println!("  能力: {}", names.join(", "));
+    }
+    if let Some(instructions) = info["instructions"].as_str() {
+        println!("  说明: {}", instructions);
+    }
+
+    let mut session = ProbeSession { client, tools: Vec::new(), prompts: Vec::new() };
+    let listings = [("tools", "tools"), ("resources", "resources"), ("prompts", "prompts")];
+    for (capability, command) in listings {
+        if capabilities.get(capability).is_some() {
+            if let Err(e) = session.execute(command, "").await {
+                println!("{}", colorize(&format!("❌ {}", e), colors::RED));
+            }
+        }
+    }
+
+    let result = session.repl().await;
+    session.client.close().await?;
+    result
+}
+
+struct ProbeSession {
+    client: McpClient,
+    /// 最近一次列出的工具，调用前用其 inputSchema 校验参数
+    tools: Vec<Value>,
+    prompts: Vec<Value>,
+}
+
+impl ProbeSession {
+    async fn repl(&mut self) -> Result<()> {
+        let interactive = atty::is(atty::Stream::Stdin);
+        if interactive {
+            println!("\n💡 输入 {} 查看命令，{} 退出", style("help").fg(theme::PRIMARY), style("exit").fg(theme::PRIMARY));
+        }
+
+        let mut failures = 0;
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        loop {
+            if interactive {
+                print!("\nmcp> ");
+                use std::io::Write;
+                std::io::stdout().flush()?;
+            }
+            let Some(line) = lines.next_line().await? else { break };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (command, rest) = split_word(line);
+            if matches!(command, "exit" | "quit") {
+                break;
+            }
+            if !interactive {
+                println!("\n> {}", line);
+            }
+            if let Err(e) = self.execute(command, rest).await {
+                failures += 1;
+                println!("{}", colorize(&format!("❌ {}", e), colors::RED));
+            }
+        }
+
+        if !interactive && failures > 0 {
+            anyhow::bail!("{} 条命令执行失败", failures);
+        }
+        Ok(())
+    }
+
+    async fn execute(&mut self, command: &str, rest: &str) -> Result<()> {
+        match command {
+            "help" => {
+                print_help();
+                Ok(())
+            }
+            "tools" => {
+                self.tools = self.client.list_all("tools/list", "tools").await?;
+                println!("\n🛠️  工具 ({}):", self.tools.len());
+                for tool in &self.tools {
+                    println!("  {} - {}", style(tool["name"].as_str().unwrap_or("?")).fg(theme::PRIMARY), first_line(&tool["description"]));
+                    if let Some(properties) = tool["inputSchema"]["properties"].as_object() {
+                        let required = tool["inputSchema"]["required"].as_array().cloned().unwrap_or_default();
+                        let params: Vec<String> = properties.keys()
+                            .map(|name| if required.contains(&json!(name)) { name.clone() } else { format!("{}?", name) })
+                            .collect();
+                        println!("      参数: {}", params.join(", "));
+                    }
+                }
+                Ok(())
+            }
+            "resources" => {
+                let resources = self.client.list_all("resources/list", "resources").await?;
+                println!("\n📚 资源 ({}):", resources.len());
+                for resource in &resources {
+                    println!("  {} - {}", style(resource["uri"].as_str().unwrap_or("?")).fg(theme::PRIMARY), resource["name"].as_str().unwrap_or(""));
+                }
+                let templates = self.client.list_all("resources/templates/list", "resourceTemplates").await.unwrap_or_default();
+                for template in &templates {
+                    println!("  {} (模板) - {}", style(template["uriTemplate"].as_str().unwrap_or("?")).fg(theme::PRIMARY), template["name"].as_str().unwrap_or(""));
+                }
+                Ok(())
+            }
+            "prompts" => {
+                self.prompts = self.client.list_all("prompts/list", "prompts").await?;
+                println!("\n📝 提示词 ({}):", self.prompts.len());
+                for prompt in &self.prompts {
+                    println!("  {} - {}", style(prompt["name"].as_str().unwrap_or("?")).fg(theme::PRIMARY), first_line(&prompt["description"]));
+                }
+                Ok(())
+            }
+            "call" => {
+                let (name, arguments) = split_word(rest);
+                let arguments = parse_json_object(arguments)?;
+                if self.tools.is_empty() {
+                    self.tools = self.client.list_all("tools/list", "tools").await?;
+                }
+                let tool = self.tools.iter().find(|tool| tool["name"] == name)
+                    .ok_or_else(|| anyhow::anyhow!("未知的工具: {}（使用 tools 查看可用工具）", name))?;
+                validate_arguments(&tool["inputSchema"], &arguments)?;
+
+                let result = self.client.request("tools/call", json!({ "name": name, "arguments": arguments })).await?;
+                print_tool_result(&result);
+                if result["isError"] == true {
+                    anyhow::bail!("工具 {} 返回错误", name);
+                }
+                Ok(())
+            }
+            "read" => {
+                if rest.is_empty() {
+                    anyhow::bail!("用法: read <uri>");
+                }
+                let result = self.client.request("resources/read", json!({ "uri": rest })).await?;
+                for content in result["contents"].as_array().into_iter().flatten() {
+                    match content["text"].as_str() {
+                        Some(text) => println!("{}", text),
+                        None => println!("[二进制内容 {}]", content["mimeType"].as_str().unwrap_or("")),
+                    }
+                }
+                Ok(())
+            }
+            "prompt" => {
+                let (name, arguments) = split_word(rest);
+                let arguments = parse_json_object(arguments)?;
+                if let Some(prompt) = self.prompts.iter().find(|prompt| prompt["name"] == name) {
+                    for argument in prompt["arguments"].as_array().into_iter().flatten() {
+                        let field = argument["name"].as_str().unwrap_or_default();
+                        if argument["required"] == true && arguments.get(field).is_none() {
+                            anyhow::bail!("缺少必需参数 {}", field);
+                        }
+                    }
+                }
+
+                let result = self.client.request("prompts/get", json!({ "name": name, "arguments": arguments })).await?;
+                for message in result["messages"].as_array().into_iter().flatten() {
+                    println!("[{}] {}", message["role"].as_str().unwrap_or("?"), message["content"]["text"].as_str().unwrap_or(""));
+                }
+                Ok(())
+            }
+            "raw" => {
+                let (method, params) = split_word(rest);
+                if method.is_empty() {
+                    anyhow::bail!("用法: raw <method> [JSON 参数]");
+                }
+                let result = self.client.request(method, parse_json_object(params)?).await?;
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                Ok(())
+            }
+            _ => anyhow::bail!("未知命令: {}（输入 help 查看命令）", command),
+        }
+    }
+}
+
+fn print_help() {
+    println!("\n📖 可用命令:");
+    println!("  {}                       列出工具及参数", style("tools").fg(theme::PRIMARY));
+    println!("  {} <工具> [JSON 参数]      调用工具，参数先按工具的 inputSchema 校验", style("call").fg(theme::PRIMARY));
+    println!("  {}                   列出资源", style("resources").fg(theme::PRIMARY));
+    println!("  {} <uri>                  读取资源", style("read").fg(theme::PRIMARY));
+    println!("  {}                     列出提示词", style("prompts").fg(theme::PRIMARY));
+    println!("  {} <名称> [JSON 参数]    获取提示词", style("prompt").fg(theme::PRIMARY));
+    println!("  {} <方法> [JSON 参数]       发送任意请求并显示原始结果", style("raw").fg(theme::PRIMARY));
+    println!("  {}                        退出", style("exit").fg(theme::PRIMARY));
+}
+
+fn print_tool_result(result: &Value) {
+    if result["isError"] == true {
+        println!("{}", colorize("⚠️  工具返回错误", colors::YELLOW));
+    }
+    for content in result["content"].as_array().into_iter().flatten() {
+        match content["type"].as_str() {
+            Some("text") => println!("{}", content["text"].as_str().unwrap_or("")),
+            Some("image") => println!(
+                "[图片 {}，{} 字节 Base64]",
+                content["mimeType"].as_str().unwrap_or("?"),
+                content["data"].as_str().map(str::len).unwrap_or(0)
+            ),
+            _ => println!("{}", content),
+        }
+    }
+    if let Some(structured) = result.get("structuredContent") {
+        println!("\n结构化结果:\n{}", serde_json::to_string_pretty(structured).unwrap_or_default());
+    }
+}
+
+/// 打印服务器推送的日志、进度和其它通知
+fn print_notification(message: &Value) {
+    let params = &message["params"];
+    let line = match message["method"].as_str().unwrap_or("") {
+        "notifications/message" => format!(
+            "📣 [{}] {}: {}",
+            params["level"].as_str().unwrap_or("info"),
+            params["logger"].as_str().unwrap_or("server"),
+            params["data"].as_str().map(str::to_string).unwrap_or_else(|| params["data"].to_string())
+        ),
+        "notifications/progress" => format!(
+            "⏳ {} {}",
+            params["progress"],
+            params["message"].as_str().unwrap_or("")
+        ),
+        method => format!("📣 {}", method),
+    };
+    eprintln!("{}", colorize(&line, colors::CYAN));
+}
+
+/// 拆出第一个单词和剩余部分
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+/// 解析 JSON 对象参数，空字符串视为 `{}`
+fn parse_json_object(text: &str) -> Result<Value> {
+    if text.is_empty() {
+        return Ok(json!({}));
+    }
+    let value: Value = serde_json::from_str(text).map_err(|e| anyhow::anyhow!("参数不是有效的 JSON: {}", e))?;
+    if !value.is_object() {
+        anyhow::bail!("参数必须是 JSON 对象");
+    }
+    Ok(value)
+}
+
+fn first_line(description: &Value) -> &str {
+    description.as_str().and_then(|text| text.lines().next()).unwrap_or("")
+}
@@ -1,62 +1,100 @@
 // MCP 服务器管理命令实现
+use std::path::PathBuf;
+use std::time::Duration;
+
 use anyhow::Result;
 use crate::cli::ServerAction;
-use crate::mcp::ZhiServer;
-use crate::utils::{print_boxed_message, colorize, colors, ModernProgressBar, StatusIndicator, theme};
+use crate::mcp::control::{broadcast_control_request, control_socket_path, send_control_request, ControlRequest, ControlResponse};
+use crate::mcp::daemon::{fetch_health, run_daemon, running_daemon, spawn_daemon, stop_daemon};
+use crate::mcp::metrics::{Histogram, InteractionBackend, MetricsSnapshot, ToolOutcome, WAIT_BUCKETS};
+use crate::config::{load_or_create_http_token, regenerate_http_token};
+use crate::mcp::unix_socket::resolve_socket_path;
+use crate::mcp::{DaemonPaths, DaemonState, ServerMode, ZhiServer};
+use crate::utils::{print_boxed_message, colorize, colors, format_duration, StatusIndicator, theme};
 use console;
-use crate::{log_success, log_warning, log_error};
+use crate::{log_success, log_warning};
 
 pub async fn handle_server_command(action: ServerAction) -> Result<()> {
     match action {
-        ServerAction::Start => {
-            start_server().await
+        ServerAction::Start { port, socket, daemon } => {
+            let socket = socket.map(resolve_socket_path).transpose()?;
+            if daemon {
+                start_daemon(daemon_mode(port, socket)).await
+            } else {
+                start_foreground(port, socket).await
+            }
         }
         ServerAction::Stop => {
             stop_server().await
         }
+        ServerAction::Restart { port, socket } => {
+            let socket = socket.map(resolve_socket_path).transpose()?;
+            restart_server(port, socket).await
+        }
         ServerAction::Status => {
             show_server_status().await
         }
+        ServerAction::Token { regenerate } => {
+            show_http_token(regenerate)
+        }
+        ServerAction::Metrics { prometheus } => {
+            show_metrics(prometheus).await
+        }
+        ServerAction::Run { port, socket } => {
+            run_daemon(&ZhiServer::new().connect_gateway().await, daemon_mode(Some(port), socket)).await
+        }
     }
 }
 
-/// 启动 MCP 服务器
-async fn start_server() -> Result<()> {
+/// 后台服务的运行模式：指定套接字时使用 Unix 域套接字，否则使用 HTTP（未指定端口时由系统分配）
+fn daemon_mode(port: Option<u16>, socket: Option<PathBuf>) -> ServerMode {
+    match socket {
+        Some(path) => ServerMode::Unix { path },
+        None => ServerMode::Http { port: port.unwrap_or(0) },
+    }
+}
+
+/// 在当前进程中启动 MCP 服务器，直到连接关闭或收到关闭信号
+async fn start_foreground(port: Option<u16>, socket: Option<PathBuf>) -> Result<()> {
+    let mode = match (port, socket) {
+        (_, Some(path)) => {
+            println!("🔌 MCP 套接字: {}", console::style(path.display()).fg(theme::PRIMARY));
+            println!("💡 按 Ctrl+C 停止服务器");
+            ServerMode::Unix { path }
+        }
+        (Some(port), None) => {
+            let host = crate::config::load_standalone_config()
+                .map(|config| config.http_config.bind_address)
+                .unwrap_or_else(|_| crate::config::default_http_bind_address());
+            println!("🌐 MCP 端点: {}", console::style(format!("http://{}:{}/mcp", host, port)).fg(theme::PRIMARY));
+            println!("💡 按 Ctrl+C 停止服务器");
+            ServerMode::Http { port }
+        }
+        (None, None) => ServerMode::Stdio,
+    };
+
+    ZhiServer::new().connect_gateway().await.start_with_mode(mode).await
+}
+
+/// 启动后台 MCP 服务器
+async fn start_daemon(mode: ServerMode) -> Result<()> {
     let indicator = StatusIndicator::new();
 
-    print_boxed_message("启动 MCP 服务器", "正在初始化智能代码审查服务");
-
-    // 显示启动进度
-    let pb = ModernProgressBar::new_spinner();
-    pb.set_message("正在初始化服务器...");
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
-
-    let server = ZhiServer::new();
-
-    // 模拟启动过程
-    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-    pb.set_message("正在加载配置...");
-
-    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
-    pb.set_message("正在启动工具...");
-
-    match server.start().await {
-        Ok(_) => {
-            pb.finish_with_message("服务器启动成功！");
-            indicator.success("MCP 服务器已成功启动");
-
-            // 显示服务器信息
-            let tools = server.list_tools();
-            if !tools.is_empty() {
-                println!("\n📋 可用工具:");
-                for tool in tools {
-                    let status_color = if tool.enabled { theme::SUCCESS } else { theme::MUTED };
-                    println!("  {} - {}",
-                        console::style(&tool.name).fg(status_color).bold(),
-                        console::style(&tool.description).dim()
-                    );
-                }
-            }
+    print_boxed_message("启动 MCP 服务器", "正在启动后台服务");
+
+    let paths = DaemonPaths::new()?;
+    if let Some(running) = running_daemon(&paths)? {
+        indicator.warning(&format!("MCP 服务器已在运行 (PID {})", running.pid));
+        if let Some(state) = &running.state {
+            print_daemon_state(state);
+        }
+        return Ok(());
+    }
+
+    match spawn_daemon(&mode).await {
+        Ok(state) => {
+            indicator.success("MCP 服务器启动成功");
+            print_daemon_state(&state);
 
             println!("\n💡 下一步操作:");
             println!("  {} - 查看服务器状态", console::style("cunzhi server status").fg(theme::PRIMARY));
@@ -65,72 +103,227 @@ async fn start_server() -> Result<()> {
             Ok(())
         }
         Err(e) => {
-            pb.finish_with_message("启动失败");
             indicator.error(&format!("MCP 服务器启动失败: {}", e));
             Err(e)
         }
     }
 }
 
-/// 停止 MCP 服务器
+/// 停止后台 MCP 服务器
 async fn stop_server() -> Result<()> {
     print_boxed_message("停止 MCP 服务器", "正在关闭服务");
 
-    let server = ZhiServer::new();
-
-    match server.stop().await {
-        Ok(_) => {
-            log_success!("MCP 服务器已停止");
-            Ok(())
-        }
-        Err(e) => {
-            log_warning!("停止服务器时出现问题: {}", e);
-            // 即使出错也返回成功，因为可能服务器本来就没运行
-            Ok(())
-        }
+    match stop_daemon().await? {
+        Some(stopped) => log_success!("MCP 服务器已停止 (PID {})", stopped.pid),
+        None => log_warning!("MCP 服务器未运行"),
     }
+    Ok(())
+}
+
+/// 重启后台 MCP 服务器，未指定端口或套接字时沿用原来的监听方式
+async fn restart_server(port: Option<u16>, socket: Option<PathBuf>) -> Result<()> {
+    let previous_mode = stop_daemon().await?
+        .and_then(|stopped| stopped.state)
+        .map(|state| state.mode());
+
+    let mode = match (port, socket, previous_mode) {
+        (None, None, Some(previous)) => previous,
+        (port, socket, _) => daemon_mode(port, socket),
+    };
+    start_daemon(mode).await
 }
 
 /// 显示服务器状态
 async fn show_server_status() -> Result<()> {
     print_boxed_message("MCP 服务器状态", "检查服务器运行状态");
 
-    let server = ZhiServer::new();
-
-    match server.status().await {
-        Ok(status) => {
-            println!("🔍 服务器状态: {}", colorize(&status, colors::GREEN));
-
-            // 显示工具状态
-            let tools = server.list_tools();
-            if !tools.is_empty() {
-                println!("\n🛠️  工具状态:");
-                for tool in tools {
-                    let status_icon = if tool.enabled { "✅" } else { "❌" };
-                    println!("  {} {} - {}", status_icon, tool.name, tool.description);
+    let paths = DaemonPaths::new()?;
+    match running_daemon(&paths)? {
+        Some(running) => {
+            println!("🔍 服务器状态: {}", colorize("运行中", colors::GREEN));
+            match &running.state {
+                Some(state) => {
+                    print_daemon_state(state);
+                    let sessions = match state.http_addr() {
+                        Some(addr) => fetch_health(addr).await
+                            .and_then(|health| health["sessions"].as_u64())
+                            .map(|count| count.to_string())
+                            .unwrap_or_else(|| "未知（服务无响应）".to_string()),
+                        // 套接字传输没有 /health 端点，通过控制套接字查询
+                        None => match send_control_request(&control_socket_path(running.pid), &ControlRequest::Metrics).await {
+                            Ok(ControlResponse::Metrics { metrics }) => metrics.active_sessions.to_string(),
+                            _ => "未知（服务无响应）".to_string(),
+                        },
+                    };
+                    println!("  运行时长: {}", format_uptime(state.uptime()));
+                    println!("  活动会话: {}", sessions);
                 }
-            } else {
-                log_warning!("没有启用的工具");
+                None => println!("  PID: {}（正在启动）", running.pid),
             }
+        }
+        None => {
+            println!("🔍 服务器状态: {}", colorize("未运行", colors::YELLOW));
+        }
+    }
 
-            // 显示配置信息
-            match crate::config::load_standalone_config() {
-                Ok(config) => {
-                    println!("\n⚙️  配置信息:");
-                    println!("  配置版本: {}", config.version);
-                    let enabled_count = config.mcp_config.tools.values().filter(|&&v| v).count();
-                    println!("  启用工具数: {}", enabled_count);
-                }
-                Err(_) => {
-                    log_warning!("无法加载配置信息");
-                }
-            }
+    // 显示工具状态
+    let tools = ZhiServer::new().list_tools();
+    if !tools.is_empty() {
+        println!("\n🛠️  工具状态:");
+        for tool in tools {
+            let status_icon = if tool.enabled { "✅" } else { "❌" };
+            println!("  {} {} - {}", status_icon, tool.name, tool.description);
+        }
+    } else {
+        log_warning!("没有启用的工具");
+    }
 
-            Ok(())
+    // 显示配置信息
+    match crate::config::load_standalone_config() {
+        Ok(config) => {
+            println!("\n⚙️  配置信息:");
+            println!("  配置版本: {}", config.version);
+            let enabled_count = config.mcp_config.tools.values().filter(|&&v| v).count();
+            println!("  启用工具数: {}", enabled_count);
         }
-        Err(e) => {
-            log_error!("获取服务器状态失败: {}", e);
-            Err(e)
+        Err(_) => {
+            log_warning!("无法加载配置信息");
+        }
+    }
+
+    Ok(())
+}
+
+/// 显示（或重新生成）HTTP 访问令牌
+fn show_http_token(regenerate: bool) -> Result<()> {
+    let token = if regenerate {
+        let token = regenerate_http_token()?;
+        log_success!("已生成新的访问令牌，重启服务器后生效");
+        token
+    } else {
+        load_or_create_http_token()?
+    };
+
+    // 令牌单独输出到标准输出，便于脚本读取
+    println!("{}", token);
+    if !crate::config::load_standalone_config().map(|config| config.http_config.require_token).unwrap_or(true) {
+        log_warning!("当前配置未启用令牌认证（http_config.require_token = false）");
+    }
+    Ok(())
+}
+
+/// 汇总并显示所有服务器进程的运行指标
+async fn show_metrics(prometheus: bool) -> Result<()> {
+    let mut total = MetricsSnapshot::default();
+    let mut servers = 0;
+    for (_, response) in broadcast_control_request(&ControlRequest::Metrics).await? {
+        if let ControlResponse::Metrics { metrics } = response {
+            total.merge(&metrics);
+            servers += 1;
+        }
+    }
+
+    if prometheus {
+        print!("{}", total.render_prometheus());
+        return Ok(());
+    }
+    if servers == 0 {
+        log_warning!("没有正在运行的 MCP 服务器");
+        return Ok(());
+    }
+
+    print_boxed_message("MCP 服务器指标", &format!("汇总 {} 个服务器进程", servers));
+
+    println!("🛠️  工具调用:");
+    if total.tool_calls.is_empty() {
+        println!("  暂无");
+    }
+    for (tool, outcomes) in &total.tool_calls {
+        let counts: Vec<String> = outcomes.iter()
+            .map(|(outcome, count)| {
+                let name = ToolOutcome::parse(outcome).map(|outcome| outcome.display_name()).unwrap_or(outcome);
+                format!("{} {}", name, count)
+            })
+            .collect();
+        println!("  {} - 共 {} 次（{}）", console::style(tool).fg(theme::PRIMARY), total.tool_call_total(tool), counts.join("，"));
+    }
+
+    println!("\n⏳ zhi 等待用户回复:");
+    if total.zhi_waits.is_empty() {
+        println!("  暂无");
+    }
+    for (backend, histogram) in &total.zhi_waits {
+        println!("  {} - {}", backend_name(backend), describe_waits(histogram));
+    }
+
+    if !total.zhi_fallbacks.is_empty() {
+        let fallbacks: Vec<String> = total.zhi_fallbacks.iter()
+            .map(|(backend, count)| format!("{} {} 次", backend_name(backend), count))
+            .collect();
+        println!("\n🔁 交互回退: {}", fallbacks.join("，"));
+    }
+
+    let writes: u64 = total.memory_writes.values().sum();
+    let by_category: Vec<String> = total.memory_writes.iter()
+        .map(|(category, count)| format!("{} {}", category, count))
+        .collect();
+    if by_category.is_empty() {
+        println!("\n🧠 记忆写入: 0");
+    } else {
+        println!("\n🧠 记忆写入: {}（{}）", writes, by_category.join("，"));
+    }
+    println!("👥 活动会话: {}", total.active_sessions);
+    Ok(())
+}
+
+fn backend_name(backend: &str) -> &str {
+    InteractionBackend::parse(backend).map(|backend| backend.display_name()).unwrap_or(backend)
+}
+
+/// 「3 次，平均 12.5s，P50 ≤ 15 秒，P90 ≤ 60 秒」
+fn describe_waits(histogram: &Histogram) -> String {
+    let mut parts = vec![format!("{} 次", histogram.count)];
+    if let Some(mean) = histogram.mean() {
+        parts.push(format!("平均 {}", format_duration(Duration::from_secs_f64(mean))));
+    }
+    for (label, quantile) in [("P50", 0.5), ("P90", 0.9)] {
+        match histogram.quantile_upper_bound(quantile) {
+            Some(bound) if bound.is_finite() => parts.push(format!("{} ≤ {} 秒", label, bound)),
+            Some(_) => parts.push(format!("{} > {} 秒", label, WAIT_BUCKETS[WAIT_BUCKETS.len() - 1])),
+            None => {}
         }
     }
+    parts.join("，")
+}
+
+fn print_daemon_state(state: &DaemonState) {
+    println!("  PID: {}", state.pid);
+    println!("  传输方式: {}", state.transport);
+    if let Some(port) = state.port {
+        println!("  端口: {}", port);
+    }
+    if let Some(socket) = &state.socket {
+        println!("  套接字: {}", socket.display());
+    }
+    println!("  MCP 端点: {}", console::style(state.endpoint()).fg(theme::PRIMARY));
+    println!("  日志文件: {}", state.log_file.display());
+}
+
+/// 将运行时长格式化为「x 天 x 小时 x 分 x 秒」
+fn format_uptime(uptime: Duration) -> String {
+    let total = uptime.as_secs();
+    let (days, hours, minutes, seconds) = (total / 86400, total / 3600 % 24, total / 60 % 60, total % 60);
+
+    let mut parts = Vec::new();
+    if days > 0 {
+        parts.push(format!("{} 天", days));
+    }
+    if days > 0 || hours > 0 {
+        parts.push(format!("{} 小时", hours));
+    }
+    if days > 0 || hours > 0 || minutes > 0 {
+        parts.push(format!("{} 分", minutes));
+    }
+    parts.push(format!("{} 秒", seconds));
+    parts.join(" ")
 }
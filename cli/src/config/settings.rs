@@ -10,6 +10,14 @@ pub struct AppConfig {
     pub mcp_config: McpConfig, // MCP工具配置
     #[serde(default = "default_terminal_config")]
     pub terminal_config: TerminalConfig, // 终端启动器配置
+    #[serde(default = "default_prompt_config")]
+    pub prompt_config: PromptConfig, // MCP 提示词模板
+    #[serde(default = "default_http_config")]
+    pub http_config: HttpConfig, // HTTP 传输的监听地址与访问控制
+    #[serde(default = "default_gateway_config")]
+    pub gateway_config: GatewayConfig, // 代理的下游 MCP 服务器
+    #[serde(default = "default_guard_config")]
+    pub guard_config: GuardConfig, // `cunzhi guard` 需要用户批准的工具调用规则
     #[serde(default = "default_version")]
     pub version: String, // 配置版本
 }
@@ -28,6 +36,92 @@ pub struct ReplyConfig {
 pub struct McpConfig {
     #[serde(default = "default_mcp_tools")]
     pub tools: HashMap<String, bool>, // MCP工具启用状态
+    #[serde(default = "default_elicitation")]
+    pub elicitation: bool, // 客户端支持时，zhi 是否优先通过 elicitation 表单询问用户
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpConfig {
+    #[serde(default = "default_http_bind_address")]
+    pub bind_address: String, // 监听地址，默认只接受本机连接
+    #[serde(default = "default_http_require_token")]
+    pub require_token: bool, // 是否要求 `Authorization: Bearer <令牌>`，监听非本机地址时必须启用
+    #[serde(default)]
+    pub allowed_origins: Vec<String>, // 允许的浏览器来源（Origin 头），携带其它 Origin 的请求一律拒绝
+    #[serde(default = "default_http_max_body_bytes")]
+    pub max_body_bytes: usize, // 请求体大小上限（字节）
+    #[serde(default = "default_http_session_idle_timeout")]
+    pub session_idle_timeout_seconds: u64, // 会话空闲多久后过期（秒），0 表示不过期
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GatewayConfig {
+    #[serde(default)]
+    pub servers: Vec<DownstreamServer>, // 下游服务器，其工具、资源和提示词以名称为前缀重新导出
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownstreamServer {
+    pub name: String, // 命名空间前缀，工具 `read_file` 导出为 `<name>__read_file`
+    #[serde(default)]
+    pub command: Vec<String>, // stdio 服务器的启动命令及参数
+    #[serde(default)]
+    pub url: Option<String>, // Streamable HTTP 端点，与 command 二选一
+    #[serde(default)]
+    pub token: Option<String>, // HTTP 端点的 `Authorization: Bearer` 令牌
+    #[serde(default = "default_downstream_enabled")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GuardConfig {
+    #[serde(default)]
+    pub rules: Vec<GuardRule>, // 匹配任一规则的工具调用需要用户批准
+    #[serde(default)]
+    pub audit_log: Option<String>, // 审计日志（JSONL）路径，默认为配置目录下的 guard-audit.jsonl
+    #[serde(default = "default_guard_approval_timeout")]
+    pub approval_timeout_seconds: u64, // 等待用户批准的最长时间（秒），超时视为拒绝
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GuardRule {
+    #[serde(default = "default_guard_rule_tool")]
+    pub tool: String, // 工具名称模式，支持 `*` 和 `?` 通配，如 `*delete*`、`shell.exec`
+    #[serde(default)]
+    pub argument: Option<String>, // 只检查该参数，不指定时检查所有字符串参数
+    #[serde(default)]
+    pub pattern: Option<String>, // 参数值模式，支持 `*` 和 `?` 通配
+    #[serde(default)]
+    pub outside_project: bool, // 参数中的路径位于项目目录之外时匹配
+    #[serde(default)]
+    pub reason: Option<String>, // 显示给用户并写入审计日志的说明
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptConfig {
+    #[serde(default = "default_prompt_templates")]
+    pub templates: Vec<PromptTemplate>, // 通过 MCP prompts 提供给客户端的模板
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptTemplate {
+    pub name: String, // 提示词名称，客户端通常显示为斜杠命令
+    #[serde(default)]
+    pub description: String, // 提示词说明
+    pub template: String, // 模板内容，使用 {{参数名}} 引用参数
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>, // 模板参数
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptArgument {
+    pub name: String, // 参数名
+    #[serde(default)]
+    pub description: String, // 参数说明
+    #[serde(default)]
+    pub required: bool, // 是否必填
+    #[serde(default)]
+    pub default: Option<String>, // 未提供时使用的默认值
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -64,6 +158,10 @@ impl Default for AppConfig {
             reply_config: default_reply_config(),
             mcp_config: default_mcp_config(),
             terminal_config: default_terminal_config(),
+            prompt_config: default_prompt_config(),
+            http_config: default_http_config(),
+            gateway_config: default_gateway_config(),
+            guard_config: default_guard_config(),
             version: default_version(),
         }
     }
@@ -85,6 +183,7 @@ pub fn default_reply_config() -> ReplyConfig {
 pub fn default_mcp_config() -> McpConfig {
     McpConfig {
         tools: default_mcp_tools(),
+        elicitation: default_elicitation(),
     }
 }
 
@@ -108,6 +207,34 @@ pub fn default_terminal_config() -> TerminalConfig {
     }
 }
 
+pub fn default_http_config() -> HttpConfig {
+    HttpConfig {
+        bind_address: default_http_bind_address(),
+        require_token: default_http_require_token(),
+        allowed_origins: Vec::new(),
+        max_body_bytes: default_http_max_body_bytes(),
+        session_idle_timeout_seconds: default_http_session_idle_timeout(),
+    }
+}
+
+pub fn default_gateway_config() -> GatewayConfig {
+    GatewayConfig::default()
+}
+
+pub fn default_guard_config() -> GuardConfig {
+    GuardConfig {
+        rules: Vec::new(),
+        audit_log: None,
+        approval_timeout_seconds: default_guard_approval_timeout(),
+    }
+}
+
+pub fn default_prompt_config() -> PromptConfig {
+    PromptConfig {
+        templates: default_prompt_templates(),
+    }
+}
+
 // MCP 相关默认值
 pub fn default_enable_continue_reply() -> bool {
     true
@@ -128,6 +255,70 @@ pub fn default_mcp_tools() -> HashMap<String, bool> {
     tools
 }
 
+pub fn default_elicitation() -> bool {
+    true
+}
+
+pub fn default_prompt_templates() -> Vec<PromptTemplate> {
+    let project_path = PromptArgument {
+        name: "project_path".to_string(),
+        description: "项目路径".to_string(),
+        required: false,
+        default: Some(".".to_string()),
+    };
+    let language = PromptArgument {
+        name: "language".to_string(),
+        description: "回复使用的语言".to_string(),
+        required: false,
+        default: Some("中文".to_string()),
+    };
+
+    vec![
+        PromptTemplate {
+            name: "best-practice".to_string(),
+            description: "按照项目规范和最佳实践继续工作".to_string(),
+            template: "请在项目 {{project_path}} 中按照最佳实践继续，先用 ji 工具回忆项目规范，遇到不确定的地方用 zhi 工具询问我。请使用{{language}}回复。".to_string(),
+            arguments: vec![project_path.clone(), language.clone()],
+        },
+        PromptTemplate {
+            name: "review".to_string(),
+            description: "审查最近的改动".to_string(),
+            template: "请审查项目 {{project_path}} 中最近的改动，指出潜在问题并给出改进建议，完成后用 zhi 工具向我确认。请使用{{language}}回复。".to_string(),
+            arguments: vec![project_path, language],
+        },
+    ]
+}
+
+// HTTP 传输相关默认值
+pub fn default_http_bind_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+pub fn default_http_require_token() -> bool {
+    true
+}
+
+pub fn default_http_max_body_bytes() -> usize {
+    4 * 1024 * 1024 // 4 MiB，足够容纳带图片的工具结果
+}
+
+pub fn default_http_session_idle_timeout() -> u64 {
+    30 * 60 // 30 分钟
+}
+
+pub fn default_guard_approval_timeout() -> u64 {
+    5 * 60 // 5 分钟
+}
+
+// 网关相关默认值
+pub fn default_downstream_enabled() -> bool {
+    true
+}
+
+pub fn default_guard_rule_tool() -> String {
+    "*".to_string()
+}
+
 // Telegram 相关默认值
 pub fn default_telegram_enabled() -> bool {
     false
@@ -179,6 +370,42 @@ impl AppConfig {
             return Err(anyhow::anyhow!("自动继续阈值不能为 0"));
         }
 
+        // 验证提示词模板
+        let mut names = std::collections::HashSet::new();
+        for template in &self.prompt_config.templates {
+            if template.name.trim().is_empty() {
+                return Err(anyhow::anyhow!("提示词模板名称不能为空"));
+            }
+            if !names.insert(template.name.as_str()) {
+                return Err(anyhow::anyhow!("提示词模板名称重复: {}", template.name));
+            }
+        }
+
+        // 验证 HTTP 配置
+        self.http_config.bind_ip()?;
+        if self.http_config.max_body_bytes == 0 {
+            return Err(anyhow::anyhow!("HTTP 请求体大小上限不能为 0"));
+        }
+
+        // 验证下游服务器
+        let mut names = std::collections::HashSet::new();
+        for server in &self.gateway_config.servers {
+            server.validate()?;
+            if !names.insert(server.name.as_str()) {
+                return Err(anyhow::anyhow!("下游服务器名称重复: {}", server.name));
+            }
+        }
+
+        // 验证审批规则
+        if self.guard_config.approval_timeout_seconds == 0 {
+            return Err(anyhow::anyhow!("审批超时时间必须大于 0"));
+        }
+        for rule in &self.guard_config.rules {
+            if rule.tool.trim().is_empty() {
+                return Err(anyhow::anyhow!("审批规则的工具名称模式不能为空"));
+            }
+        }
+
         Ok(())
     }
 
@@ -225,6 +452,30 @@ impl TelegramConfig {
     }
 }
 
+impl DownstreamServer {
+    /// 名称只能包含字母、数字、`-` 和 `_`，并且只能指定启动命令和 URL 中的一个
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let valid_name = !self.name.is_empty()
+            && self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(anyhow::anyhow!("下游服务器名称只能包含字母、数字、- 和 _: {:?}", self.name));
+        }
+        match (self.command.is_empty(), &self.url) {
+            (false, None) | (true, Some(_)) => Ok(()),
+            (false, Some(_)) => Err(anyhow::anyhow!("下游服务器 {} 不能同时指定 command 和 url", self.name)),
+            (true, None) => Err(anyhow::anyhow!("下游服务器 {} 需要指定 command 或 url", self.name)),
+        }
+    }
+}
+
+impl HttpConfig {
+    /// 解析监听地址
+    pub fn bind_ip(&self) -> anyhow::Result<std::net::IpAddr> {
+        self.bind_address.trim().parse()
+            .map_err(|_| anyhow::anyhow!("无效的 HTTP 监听地址: {}", self.bind_address))
+    }
+}
+
 impl McpConfig {
     /// 检查工具是否启用
     pub fn is_tool_enabled(&self, tool_name: &str) -> bool {
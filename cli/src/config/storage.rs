@@ -45,11 +45,16 @@ pub fn save_standalone_config(config: &AppConfig) -> Result<()> {
 }
 
 /// 获取独立配置文件路径（不依赖GUI框架）
+///
+/// 可通过 `CUNZHI_CONFIG_DIR` 环境变量覆盖配置目录
 pub fn get_standalone_config_path() -> Result<PathBuf> {
-    // 使用标准的配置目录
-    let config_dir = dirs::config_dir()
-        .ok_or_else(|| anyhow::anyhow!("无法获取配置目录"))?
-        .join("cunzhi");
+    let config_dir = match std::env::var_os("CUNZHI_CONFIG_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        // 使用标准的配置目录
+        _ => dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("无法获取配置目录"))?
+            .join("cunzhi"),
+    };
 
     // 确保目录存在
     fs::create_dir_all(&config_dir)?;
@@ -57,6 +62,65 @@ pub fn get_standalone_config_path() -> Result<PathBuf> {
     Ok(config_dir.join("config.json"))
 }
 
+/// 获取运行时目录（控制套接字、默认 MCP 套接字等），同一用户的所有进程共用
+///
+/// 使用 `$XDG_RUNTIME_DIR`，不存在时使用临时目录；目录不会自动创建
+pub fn get_runtime_dir() -> PathBuf {
+    let base = dirs::runtime_dir().unwrap_or_else(std::env::temp_dir);
+    match std::env::var("USER").or_else(|_| std::env::var("USERNAME")) {
+        Ok(user) if !user.is_empty() => base.join(format!("cunzhi-{}", user)),
+        _ => base.join("cunzhi"),
+    }
+}
+
+/// HTTP 访问令牌文件路径，与配置文件位于同一目录
+pub fn get_http_token_path() -> Result<PathBuf> {
+    Ok(get_standalone_config_path()?.with_file_name("http_token"))
+}
+
+/// `cunzhi guard` 的默认审计日志路径，与配置文件位于同一目录
+pub fn get_guard_audit_log_path() -> Result<PathBuf> {
+    Ok(get_standalone_config_path()?.with_file_name("guard-audit.jsonl"))
+}
+
+/// 读取已保存的 HTTP 访问令牌
+pub fn read_http_token() -> Option<String> {
+    let token = fs::read_to_string(get_http_token_path().ok()?).ok()?;
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+/// 读取 HTTP 访问令牌，不存在时生成一个
+pub fn load_or_create_http_token() -> Result<String> {
+    match read_http_token() {
+        Some(token) => Ok(token),
+        None => regenerate_http_token(),
+    }
+}
+
+/// 生成新的 HTTP 访问令牌并保存（仅所有者可读写），旧令牌在服务器重启后失效
+pub fn regenerate_http_token() -> Result<String> {
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    let path = get_http_token_path()?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // 已存在的文件不受 mode 影响，单独收紧权限
+        if path.exists() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    use std::io::Write;
+    let mut file = options.open(&path)?;
+    writeln!(file, "{}", token)?;
+    Ok(token)
+}
+
 /// 检查配置文件是否存在
 pub fn config_exists() -> bool {
     get_standalone_config_path()
@@ -0,0 +1,402 @@
+// MCP 客户端
+//
+// 连接任意 MCP 服务器：启动子进程走 stdio、连接 Unix 域套接字，或通过 Streamable HTTP 访问。
+// 只实现探测和网关所需的部分：请求/响应、通知回调，以及对服务器请求的最小应答
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::Result;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+
+use super::server::{MCP_SESSION_HEADER, SUPPORTED_PROTOCOL_VERSIONS};
+use crate::log_debug;
+
+/// 要连接的 MCP 服务器
+#[derive(Debug, Clone)]
+pub enum ClientTarget {
+    /// 启动命令，通过子进程的标准输入输出通信
+    Command(Vec<String>),
+    /// Streamable HTTP 端点，如 `http://127.0.0.1:8080/mcp`
+    Http(String),
+    /// Unix 域套接字
+    Unix(PathBuf),
+}
+
+impl std::fmt::Display for ClientTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Command(command) => write!(f, "{}", command.join(" ")),
+            Self::Http(url) => write!(f, "{}", url),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// 服务器返回的 JSON-RPC 错误
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message} (错误码 {code})")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+type BoxedReader = Box<dyn AsyncRead + Unpin + Send>;
+type BoxedWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+enum Transport {
+    /// 按行分隔的 JSON-RPC（子进程 stdio 或 Unix 域套接字）
+    Lines {
+        writer: BoxedWriter,
+        lines: Lines<BufReader<BoxedReader>>,
+        child: Option<tokio::process::Child>,
+    },
+    Http {
+        url: hyper::Uri,
+        session: Option<String>,
+        /// `Authorization: Bearer` 令牌
+        token: Option<String>,
+    },
+}
+
+/// MCP 客户端
+pub struct McpClient {
+    transport: Transport,
+    next_id: u64,
+    /// 服务器发来的通知
+    on_notification: Box<dyn Fn(&Value) + Send>,
+}
+
+impl McpClient {
+    /// 连接服务器，尚未初始化
+    pub async fn connect(target: &ClientTarget) -> Result<Self> {
+        let transport = match target {
+            ClientTarget::Command(command) => {
+                let (program, args) = command.split_first()
+                    .ok_or_else(|| anyhow::anyhow!("服务器启动命令不能为空"))?;
+                let mut child = tokio::process::Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::inherit())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| anyhow::anyhow!("无法启动 {}: {}", program, e))?;
+                let writer: BoxedWriter = Box::new(child.stdin.take().expect("stdin is piped"));
+                let reader: BoxedReader = Box::new(child.stdout.take().expect("stdout is piped"));
+                Transport::Lines { writer, lines: BufReader::new(reader).lines(), child: Some(child) }
+            }
+            ClientTarget::Unix(path) => connect_unix(path).await?,
+            ClientTarget::Http(url) => {
+                let url: hyper::Uri = url.parse().map_err(|e| anyhow::anyhow!("无效的 URL {}: {}", url, e))?;
+                if url.scheme_str() != Some("http") {
+                    anyhow::bail!("只支持 http:// 端点");
+                }
+                Transport::Http { url, session: None, token: None }
+            }
+        };
+
+        Ok(Self { transport, next_id: 1, on_notification: Box::new(|_| {}) })
+    }
+
+    /// 设置 HTTP 请求携带的访问令牌，其它传输忽略
+    pub fn set_bearer_token(&mut self, bearer: Option<String>) {
+        if let Transport::Http { token, .. } = &mut self.transport {
+            *token = bearer;
+        }
+    }
+
+    /// 设置收到服务器通知时的回调
+    pub fn on_notification(&mut self, handler: impl Fn(&Value) + Send + 'static) {
+        self.on_notification = Box::new(handler);
+    }
+
+    /// 完成初始化握手，返回服务器的 `initialize` 结果
+    pub async fn initialize(&mut self) -> Result<Value> {
+        self.initialize_as("cunzhi-probe").await
+    }
+
+    /// 以指定的客户端名称完成初始化握手
+    pub async fn initialize_as(&mut self, client_name: &str) -> Result<Value> {
+        let result = self.request("initialize", json!({
+            "protocolVersion": SUPPORTED_PROTOCOL_VERSIONS[0],
+            "capabilities": {},
+            "clientInfo": {
+                "name": client_name,
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        })).await?;
+        self.notify("notifications/initialized", json!({})).await?;
+        Ok(result)
+    }
+
+    /// 列出 `tools/list`、`resources/list` 等分页结果中的全部条目
+    pub async fn list_all(&mut self, method: &str, key: &str) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut cursor = Value::Null;
+        loop {
+            let params = if cursor.is_null() { json!({}) } else { json!({ "cursor": cursor }) };
+            let mut result = self.request(method, params).await?;
+            if let Some(Value::Array(page)) = result.get_mut(key).map(Value::take) {
+                items.extend(page);
+            }
+            cursor = result["nextCursor"].take();
+            if cursor.is_null() {
+                return Ok(items);
+            }
+        }
+    }
+
+    /// 发送请求并等待响应；JSON-RPC 错误以 `RpcError` 返回
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let id = Value::from(id);
+
+        match &mut self.transport {
+            Transport::Lines { writer, lines, .. } => {
+                write_line(writer, &message).await?;
+                loop {
+                    let line = lines.next_line().await?
+                        .ok_or_else(|| anyhow::anyhow!("服务器关闭了连接"))?;
+                    let Ok(incoming) = serde_json::from_str::<Value>(&line) else {
+                        log_debug!("忽略无法解析的输出: {}", line);
+                        continue;
+                    };
+                    if let Some(result) = take_response(&incoming, &id) {
+                        return result;
+                    }
+                    if let Some(reply) = handle_incoming(&incoming, &self.on_notification) {
+                        write_line(writer, &reply).await?;
+                    }
+                }
+            }
+            Transport::Http { url, session, token } => {
+                let response = post(url, session.as_deref(), token.as_deref(), &message).await?;
+                if let Some(new_session) = header(&response, MCP_SESSION_HEADER) {
+                    *session = Some(new_session);
+                }
+                let mut messages = HttpMessages::new(response).await?;
+                while let Some(incoming) = messages.next().await? {
+                    if let Some(result) = take_response(&incoming, &id) {
+                        return result;
+                    }
+                    if let Some(reply) = handle_incoming(&incoming, &self.on_notification) {
+                        // 服务器在等待应答后才会结束当前流，应答需要另开请求发送
+                        let (url, session, token) = (url.clone(), session.clone(), token.clone());
+                        tokio::spawn(async move {
+                            if let Err(e) = post(&url, session.as_deref(), token.as_deref(), &reply).await {
+                                log_debug!("发送应答失败: {}", e);
+                            }
+                        });
+                    }
+                }
+                anyhow::bail!("服务器没有返回 {} 的响应", method)
+            }
+        }
+    }
+
+    /// 发送通知
+    pub async fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        match &mut self.transport {
+            Transport::Lines { writer, .. } => write_line(writer, &message).await,
+            Transport::Http { url, session, token } => {
+                let response = post(url, session.as_deref(), token.as_deref(), &message).await?;
+                if !response.status().is_success() {
+                    anyhow::bail!("服务器拒绝了通知 {}: HTTP {}", method, response.status());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// 断开连接：关闭子进程输入并等待退出，HTTP 会话发送 DELETE 结束
+    pub async fn close(self) -> Result<()> {
+        match self.transport {
+            Transport::Lines { writer, child, .. } => {
+                drop(writer);
+                if let Some(mut child) = child {
+                    let exited = tokio::time::timeout(std::time::Duration::from_secs(5), child.wait()).await;
+                    if exited.is_err() {
+                        child.kill().await?;
+                    }
+                }
+            }
+            Transport::Http { url, session: Some(session), token } => {
+                let mut builder = hyper::Request::delete(&url).header(MCP_SESSION_HEADER, session);
+                if let Some(token) = token {
+                    builder = builder.header("Authorization", format!("Bearer {}", token));
+                }
+                let request = builder.body(Full::new(Bytes::new()))?;
+                let _ = send(&url, request).await;
+            }
+            Transport::Http { session: None, .. } => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &std::path::Path) -> Result<Transport> {
+    let stream = tokio::net::UnixStream::connect(path).await
+        .map_err(|e| anyhow::anyhow!("无法连接套接字 {}: {}", path.display(), e))?;
+    let (reader, writer) = stream.into_split();
+    let reader: BoxedReader = Box::new(reader);
+    Ok(Transport::Lines { writer: Box::new(writer), lines: BufReader::new(reader).lines(), child: None })
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &std::path::Path) -> Result<Transport> {
+    anyhow::bail!("当前平台不支持 Unix 域套接字")
+}
+
+/// 与 `id` 对应的响应，其它消息返回 None
+fn take_response(message: &Value, id: &Value) -> Option<Result<Value>> {
+    if message.get("method").is_some() || message.get("id") != Some(id) {
+        return None;
+    }
+    Some(match message.get("error") {
+        Some(error) => Err(RpcError {
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or("未知错误").to_string(),
+        }.into()),
+        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+    })
+}
+
+/// 处理服务器发来的通知和请求，返回需要发回的应答
+///
+/// 客户端没有声明任何能力，除 `ping` 外的服务器请求都以「方法不存在」拒绝
+fn handle_incoming(message: &Value, on_notification: &(dyn Fn(&Value) + Send)) -> Option<Value> {
+    let method = message["method"].as_str()?;
+    let Some(id) = message.get("id") else {
+        on_notification(message);
+        return None;
+    };
+
+    let reply = match method {
+        "ping" => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
+        _ => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32601, "message": format!("客户端不支持 {}", method) }
+        }),
+    };
+    Some(reply)
+}
+
+async fn write_line(writer: &mut BoxedWriter, message: &Value) -> Result<()> {
+    writer.write_all(format!("{}\n", message).as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn header<B>(response: &hyper::Response<B>, name: &str) -> Option<String> {
+    response.headers().get(name)?.to_str().ok().map(str::to_string)
+}
+
+/// 以 POST 发送一条 JSON-RPC 消息
+async fn post(url: &hyper::Uri, session: Option<&str>, token: Option<&str>, message: &Value) -> Result<hyper::Response<hyper::body::Incoming>> {
+    let mut builder = hyper::Request::post(url)
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json, text/event-stream");
+    if let Some(session) = session {
+        builder = builder.header(MCP_SESSION_HEADER, session);
+    }
+    if let Some(token) = token {
+        builder = builder.header("Authorization", format!("Bearer {}", token));
+    }
+    let request = builder.body(Full::new(Bytes::from(message.to_string())))?;
+    send(url, request).await
+}
+
+/// 每个请求使用独立连接，SSE 响应不会阻塞后续请求
+async fn send(url: &hyper::Uri, mut request: hyper::Request<Full<Bytes>>) -> Result<hyper::Response<hyper::body::Incoming>> {
+    let host = url.host().ok_or_else(|| anyhow::anyhow!("URL 缺少主机名"))?;
+    let port = url.port_u16().unwrap_or(80);
+    let stream = tokio::net::TcpStream::connect((host, port)).await
+        .map_err(|e| anyhow::anyhow!("无法连接 {}:{}: {}", host, port, e))?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let authority = url.authority().map(|authority| authority.to_string()).unwrap_or_default();
+    request.headers_mut().insert(hyper::header::HOST, authority.parse()?);
+    Ok(sender.send_request(request).await?)
+}
+
+/// HTTP 响应中的 JSON-RPC 消息：JSON 响应体，或逐个读取的 SSE 事件
+enum HttpMessages {
+    Json(Vec<Value>),
+    Events { body: hyper::body::Incoming, buffer: Vec<u8> },
+}
+
+impl HttpMessages {
+    async fn new(response: hyper::Response<hyper::body::Incoming>) -> Result<Self> {
+        let status = response.status();
+        let content_type = header(&response, "Content-Type").unwrap_or_default();
+
+        if !status.is_success() {
+            let body = response.into_body().collect().await?.to_bytes();
+            let body = String::from_utf8_lossy(&body);
+            // JSON-RPC 错误同样以 JSON 返回
+            if let Ok(message) = serde_json::from_str::<Value>(&body) {
+                return Ok(Self::Json(vec![message]));
+            }
+            anyhow::bail!("HTTP {}: {}", status, body.trim());
+        }
+
+        if content_type.starts_with("text/event-stream") {
+            return Ok(Self::Events { body: response.into_body(), buffer: Vec::new() });
+        }
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let messages = match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(batch)) => batch,
+            Ok(message) => vec![message],
+            Err(_) if body.is_empty() => Vec::new(),
+            Err(e) => anyhow::bail!("无法解析响应: {}", e),
+        };
+        Ok(Self::Json(messages))
+    }
+
+    async fn next(&mut self) -> Result<Option<Value>> {
+        match self {
+            Self::Json(messages) => Ok((!messages.is_empty()).then(|| messages.remove(0))),
+            Self::Events { body, buffer } => loop {
+                // 事件以空行分隔，只关心其中的 data 行
+                if let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+                    let event: Vec<u8> = buffer.drain(..end + 2).collect();
+                    let event = String::from_utf8_lossy(&event);
+                    let data: Vec<&str> = event.lines()
+                        .filter_map(|line| line.strip_prefix("data:"))
+                        .map(str::trim)
+                        .collect();
+                    if data.is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str(&data.join("\n")) {
+                        Ok(message) => return Ok(Some(message)),
+                        Err(e) => {
+                            log_debug!("忽略无法解析的事件: {}", e);
+                            continue;
+                        }
+                    }
+                }
+
+                match body.frame().await {
+                    Some(frame) => {
+                        if let Ok(data) = frame?.into_data() {
+                            buffer.extend(data.iter().filter(|byte| **byte != b'\r'));
+                        }
+                    }
+                    None => return Ok(None),
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// MCP 参数补全（`completion/complete`）
+//
+// - `ji` 的 `project_path`：客户端的根目录、已有记忆（`.cunzhi-memory`）的项目和当前目录树中的目录
+// - 其它工具参数：按 inputSchema 中的 enum 和布尔类型补全（如 `ji` 的 `action`、`category`）
+// - 记忆资源模板：`{project}`（百分号编码的项目路径）、`{category}` 和 `{id}`
+//
+// 候选值按输入前缀过滤，结果最多返回 `MAX_COMPLETIONS` 项
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use super::tools::memory::resources::{percent_decode, percent_encode};
+use super::tools::memory::{MemoryCategory, MemoryManager};
+
+/// 单次补全返回的最大数量（MCP 规范的上限）
+pub const MAX_COMPLETIONS: usize = 100;
+
+/// 在当前目录下查找记忆存储的最大深度
+const SCAN_DEPTH: usize = 3;
+
+/// 查找记忆存储时跳过的目录
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target"];
+
+const MEMORY_DIR: &str = ".cunzhi-memory";
+
+/// `completion/complete` 的结果
+pub fn completion_result(values: Vec<String>) -> Value {
+    let total = values.len();
+    let values: Vec<String> = values.into_iter().take(MAX_COMPLETIONS).collect();
+    json!({
+        "completion": {
+            "values": values,
+            "total": total,
+            "hasMore": total > MAX_COMPLETIONS
+        }
+    })
+}
+
+/// 按参数 Schema 中的 enum 或布尔类型补全，没有固定取值时返回空列表
+pub fn complete_from_schema(input_schema: &Value, argument: &str, value: &str) -> Vec<String> {
+    let property = &input_schema["properties"][argument];
+    let candidates: Vec<String> = match property["enum"].as_array() {
+        Some(values) => values.iter()
+            .map(|value| value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string()))
+            .collect(),
+        None if schema_types(property).contains(&"boolean") => vec!["true".to_string(), "false".to_string()],
+        None => Vec::new(),
+    };
+    candidates.into_iter().filter(|candidate| candidate.starts_with(value)).collect()
+}
+
+/// `type` 可以是字符串或数组（如 `["boolean", "null"]`）
+fn schema_types(property: &Value) -> Vec<&str> {
+    match &property["type"] {
+        Value::String(kind) => vec![kind.as_str()],
+        Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 补全项目路径：客户端的根目录、当前目录和已有记忆的项目在前，然后是与输入同级的目录
+///
+/// 相对路径基于服务器的当前目录，与 `ji` 检查路径的方式一致
+pub fn complete_project_path(value: &str, roots: &[String], projects: &[String]) -> Vec<String> {
+    let cwd = std::env::current_dir().ok();
+    let mut candidates: Vec<String> = roots.to_vec();
+    candidates.extend(cwd.iter().map(|dir| dir.to_string_lossy().to_string()));
+    candidates.extend(memory_projects(projects));
+    candidates.retain(|candidate| candidate.starts_with(value));
+    candidates.extend(child_directories(value, cwd.as_deref()));
+
+    let mut seen = std::collections::HashSet::new();
+    candidates.retain(|candidate| seen.insert(candidate.clone()));
+    candidates
+}
+
+/// 补全记忆资源模板中的变量，`context` 为客户端已填写的其它变量
+pub fn complete_memory_uri(argument: &str, value: &str, context: &Value, projects: &[String]) -> Vec<String> {
+    match argument {
+        "project" => memory_projects(projects).into_iter()
+            .filter(|project| project.starts_with(value) || percent_encode(project).starts_with(value))
+            .map(|project| percent_encode(&project))
+            .collect(),
+        "category" => MemoryCategory::ALL.iter()
+            .map(|category| category.as_str().to_string())
+            .filter(|category| category.starts_with(value))
+            .collect(),
+        "id" => {
+            let project = context["project"].as_str().and_then(percent_decode);
+            let category = context["category"].as_str().and_then(MemoryCategory::parse);
+            let (Some(project), Some(category)) = (project, category) else { return Vec::new() };
+            MemoryManager::open_existing(&project)
+                .and_then(|manager| manager.get_memories_by_category(category))
+                .map(|memories| memories.into_iter().map(|memory| memory.id).filter(|id| id.starts_with(value)).collect())
+                .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// 已有记忆存储的项目：给定项目中存在记忆目录的，加上当前目录树中找到的
+fn memory_projects(projects: &[String]) -> Vec<String> {
+    let mut found: Vec<String> = projects.iter()
+        .filter(|project| Path::new(project).join(MEMORY_DIR).is_dir())
+        .cloned()
+        .collect();
+    if let Ok(cwd) = std::env::current_dir() {
+        scan_memory_projects(&cwd, SCAN_DEPTH, &mut found);
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    found.retain(|project| seen.insert(project.clone()));
+    found
+}
+
+fn scan_memory_projects(dir: &Path, depth: usize, found: &mut Vec<String>) {
+    if dir.join(MEMORY_DIR).is_dir() {
+        found.push(dir.to_string_lossy().to_string());
+    }
+    if depth == 0 {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut children: Vec<PathBuf> = entries.filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str())
+        })
+        .map(|entry| entry.path())
+        .collect();
+    children.sort();
+    for child in children {
+        scan_memory_projects(&child, depth - 1, found);
+    }
+}
+
+/// 与输入同级、名称以输入最后一段开头的目录，保留输入的写法（相对、绝对或 `~/`）
+fn child_directories(value: &str, cwd: Option<&Path>) -> Vec<String> {
+    let (parent_text, partial) = match value.rfind('/') {
+        Some(index) => value.split_at(index + 1),
+        None => ("", value),
+    };
+    let parent = match parent_text.strip_prefix("~/") {
+        Some(rest) => match dirs::home_dir() {
+            Some(home) => home.join(rest),
+            None => return Vec::new(),
+        },
+        None if parent_text.is_empty() => match cwd {
+            Some(cwd) => cwd.to_path_buf(),
+            None => return Vec::new(),
+        },
+        None => match cwd {
+            Some(cwd) => cwd.join(parent_text),
+            None => PathBuf::from(parent_text),
+        },
+    };
+
+    let Ok(entries) = std::fs::read_dir(&parent) else { return Vec::new() };
+    let mut names: Vec<String> = entries.filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        // 隐藏目录只在输入以 `.` 开头时列出
+        .filter(|name| name.starts_with(partial) && (!name.starts_with('.') || partial.starts_with('.')))
+        .collect();
+    names.sort();
+    names.into_iter().map(|name| format!("{}{}", parent_text, name)).collect()
+}
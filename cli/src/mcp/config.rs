@@ -1,10 +1,12 @@
 // MCP 客户端配置生成器
 use anyhow::Result;
 use serde_json::Value;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::{log_important, log_debug};
 
 /// MCP 客户端配置
+// 字段名与客户端配置文件中的键一致
+#[allow(non_snake_case)]
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct McpClientConfig {
     pub mcpServers: std::collections::HashMap<String, McpServerConfig>,
@@ -21,6 +23,19 @@ pub struct McpServerConfig {
 impl McpClientConfig {
     /// 创建默认的 cunzhi-cli MCP 配置
     pub fn new_cunzhi_config() -> Self {
+        Self::with_args(vec!["mcp-server".to_string()])
+    }
+
+    /// 创建通过 stdio 桥接到 Unix 域套接字上共享服务器的配置
+    pub fn new_bridge_config(socket: &Path) -> Self {
+        Self::with_args(vec![
+            "mcp".to_string(),
+            "bridge".to_string(),
+            socket.to_string_lossy().to_string(),
+        ])
+    }
+
+    fn with_args(args: Vec<String>) -> Self {
         let mut servers = std::collections::HashMap::new();
 
         // 获取当前可执行文件路径
@@ -31,7 +46,7 @@ impl McpClientConfig {
 
         servers.insert("cunzhi-cli".to_string(), McpServerConfig {
             command: exe_path,
-            args: vec!["mcp-server".to_string()],
+            args,
             env: Some({
                 let mut env = std::collections::HashMap::new();
                 env.insert("RUST_LOG".to_string(), "info".to_string());
@@ -59,8 +74,13 @@ impl McpClientConfig {
 }
 
 /// 生成 MCP 客户端配置文件
-pub fn generate_mcp_config() -> Result<()> {
-    let config = McpClientConfig::new_cunzhi_config();
+///
+/// 指定套接字时，客户端通过 `cunzhi mcp bridge` 连接该套接字上的共享服务器
+pub fn generate_mcp_config(socket: Option<&Path>) -> Result<()> {
+    let config = match socket {
+        Some(socket) => McpClientConfig::new_bridge_config(socket),
+        None => McpClientConfig::new_cunzhi_config(),
+    };
 
     // 常见的 MCP 配置文件位置
     let config_paths = vec![
@@ -74,32 +94,34 @@ pub fn generate_mcp_config() -> Result<()> {
         Some(PathBuf::from("mcp_config.json")),
     ];
 
-    for path_opt in config_paths {
-        if let Some(path) = path_opt {
-            if let Some(parent) = path.parent() {
-                if !parent.exists() {
-                    std::fs::create_dir_all(parent)?;
-                }
+    for path in config_paths.into_iter().flatten() {
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                std::fs::create_dir_all(parent)?;
             }
+        }
 
-            // 如果文件已存在，尝试合并配置
-            if path.exists() {
-                match merge_config(&config, &path) {
-                    Ok(_) => log_important!(info, "已更新现有配置: {}", path.display()),
-                    Err(e) => {
-                        log_debug!("合并配置失败，创建新配置: {}", e);
-                        config.save_to_file(&path)?;
-                    }
+        // 如果文件已存在，尝试合并配置
+        if path.exists() {
+            match merge_config(&config, &path) {
+                Ok(_) => log_important!(info, "已更新现有配置: {}", path.display()),
+                Err(e) => {
+                    log_debug!("合并配置失败，创建新配置: {}", e);
+                    config.save_to_file(&path)?;
                 }
-            } else {
-                config.save_to_file(&path)?;
             }
+        } else {
+            config.save_to_file(&path)?;
         }
     }
 
     println!("\n📋 MCP 配置文件已生成，包含以下工具:");
     println!("  🤖 zhi_cunzhi - 智能代码审查工具");
     println!("  🧠 ji_cunzhi - 记忆管理工具");
+    if let Some(socket) = socket {
+        println!("\n🔌 客户端将通过套接字 {} 连接共享服务器，请先运行:", socket.display());
+        println!("  cunzhi server start --daemon --socket {}", socket.display());
+    }
     println!("\n💡 使用方法:");
     println!("  1. 重启您的 MCP 客户端 (如 Claude Desktop, Augment)");
     println!("  2. 工具应该会自动出现在可用工具列表中");
@@ -0,0 +1,247 @@
+// 本地控制套接字
+//
+// 服务器运行期间在运行时目录下监听 `control/<pid>.sock`（Unix 域套接字），
+// 供 `cunzhi pending / answer / cancel` 列出、回答或取消等待中的 `zhi` 交互，
+// 以及 `cunzhi server metrics` 读取运行指标。
+// 协议为一行请求 JSON、一行响应 JSON
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::metrics::{metrics, MetricsSnapshot};
+use super::tools::interaction::pending::{
+    answer_pending, cancel_pending, list_pending, ControlError, PendingInfo,
+};
+use crate::config::get_runtime_dir;
+use crate::{log_debug, log_important};
+
+/// 控制请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ControlRequest {
+    /// 列出等待中的交互
+    Pending,
+    /// 回答交互
+    Answer {
+        request_id: String,
+        #[serde(default)]
+        text: Option<String>,
+        #[serde(default)]
+        options: Vec<String>,
+    },
+    /// 取消交互
+    Cancel { request_id: String },
+    /// 读取运行指标
+    Metrics,
+}
+
+/// 控制响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ControlResponse {
+    Ok {
+        #[serde(default)]
+        pending: Vec<PendingInfo>,
+    },
+    /// 运行指标
+    Metrics { metrics: MetricsSnapshot },
+    /// 本服务器没有该 ID 的交互
+    NotFound,
+    Error { message: String },
+}
+
+/// 控制套接字所在目录，同一用户的所有服务器进程共用
+pub fn control_dir() -> PathBuf {
+    get_runtime_dir().join("control")
+}
+
+/// 指定服务器进程的控制套接字
+pub fn control_socket_path(pid: u32) -> PathBuf {
+    control_dir().join(format!("{}.sock", pid))
+}
+
+/// 处理一条控制请求
+pub fn handle_control_request(request: ControlRequest) -> ControlResponse {
+    let result = match request {
+        ControlRequest::Pending => return ControlResponse::Ok { pending: list_pending() },
+        ControlRequest::Metrics => return ControlResponse::Metrics { metrics: metrics().snapshot() },
+        ControlRequest::Answer { request_id, text, options } => answer_pending(&request_id, text, options),
+        ControlRequest::Cancel { request_id } => cancel_pending(&request_id),
+    };
+    match result {
+        Ok(()) => ControlResponse::Ok { pending: vec![] },
+        Err(ControlError::NotFound) => ControlResponse::NotFound,
+        Err(ControlError::Invalid(message)) => ControlResponse::Error { message },
+    }
+}
+
+/// 正在监听的控制套接字，最后一个持有者释放时停止监听并删除套接字文件
+pub struct ControlSocket {
+    path: PathBuf,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ControlSocket {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn current_socket() -> &'static Mutex<Weak<ControlSocket>> {
+    static SOCKET: Mutex<Weak<ControlSocket>> = Mutex::new(Weak::new());
+    &SOCKET
+}
+
+/// 本进程是否正在监听控制套接字
+pub fn control_socket_running() -> bool {
+    current_socket().lock().unwrap().strong_count() > 0
+}
+
+/// 启动（或复用）本进程的控制套接字，失败时返回 None
+///
+/// 同一进程内的多个传输共用一个套接字，因为等待中的交互是进程级的
+pub fn start_control_socket() -> Option<Arc<ControlSocket>> {
+    let mut current = current_socket().lock().unwrap();
+    if let Some(socket) = current.upgrade() {
+        return Some(socket);
+    }
+
+    match bind_control_socket() {
+        Ok(socket) => {
+            log_debug!("控制套接字已启动: {}", socket.path.display());
+            let socket = Arc::new(socket);
+            *current = Arc::downgrade(&socket);
+            Some(socket)
+        }
+        Err(e) => {
+            log_important!(warn, "无法启动控制套接字: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bind_control_socket() -> Result<ControlSocket> {
+    use std::os::unix::fs::DirBuilderExt;
+    use tokio::net::UnixListener;
+
+    let dir = control_dir();
+    // 套接字可以回答交互，只允许当前用户访问
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    let path = control_socket_path(std::process::id());
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)
+        .map_err(|e| anyhow::anyhow!("无法监听 {}: {}", path.display(), e))?;
+
+    let task = tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_control_connection(stream).await {
+                            log_debug!("控制连接异常结束: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    log_important!(warn, "接受控制连接失败: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(ControlSocket { path, task })
+}
+
+#[cfg(not(unix))]
+fn bind_control_socket() -> Result<ControlSocket> {
+    anyhow::bail!("当前平台不支持 Unix 域套接字")
+}
+
+#[cfg(unix)]
+async fn serve_control_connection(stream: tokio::net::UnixStream) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+
+    let response = match serde_json::from_str::<ControlRequest>(&line) {
+        Ok(request) => handle_control_request(request),
+        Err(e) => ControlResponse::Error { message: format!("无效的控制请求: {}", e) },
+    };
+    let mut response = serde_json::to_string(&response)?;
+    response.push('\n');
+    writer.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// 向指定的控制套接字发送请求
+#[cfg(unix)]
+pub async fn send_control_request(path: &Path, request: &ControlRequest) -> Result<ControlResponse> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path).await?;
+    let mut request = serde_json::to_string(request)?;
+    request.push('\n');
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    serde_json::from_str(&line).map_err(|e| anyhow::anyhow!("无效的控制响应: {}", e))
+}
+
+#[cfg(not(unix))]
+pub async fn send_control_request(_path: &Path, _request: &ControlRequest) -> Result<ControlResponse> {
+    anyhow::bail!("当前平台不支持 Unix 域套接字")
+}
+
+/// 向所有正在运行的服务器发送请求，返回各服务器的 PID 与响应
+///
+/// 无法连接的套接字属于已退出的进程，会被清理
+pub async fn broadcast_control_request(request: &ControlRequest) -> Result<Vec<(u32, ControlResponse)>> {
+    let entries = match std::fs::read_dir(control_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut responses = Vec::new();
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let Some(pid) = path.file_stem()
+            .filter(|_| path.extension().is_some_and(|ext| ext == "sock"))
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok())
+        else {
+            continue;
+        };
+
+        match send_control_request(&path, request).await {
+            Ok(response) => responses.push((pid, response)),
+            Err(e) if is_stale(&e) => {
+                log_debug!("控制套接字 {} 无法连接，已清理: {}", path.display(), e);
+                let _ = std::fs::remove_file(&path);
+            }
+            Err(e) => log_important!(warn, "控制套接字 {} 请求失败: {}", path.display(), e),
+        }
+    }
+    Ok(responses)
+}
+
+/// 连接被拒绝说明监听的进程已经退出
+fn is_stale(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<std::io::Error>().is_some_and(|e| {
+        matches!(e.kind(), std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotFound)
+    })
+}
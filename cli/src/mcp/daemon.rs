@@ -0,0 +1,370 @@
+// 后台服务进程管理
+//
+// `cunzhi server start --daemon` 以后台进程运行 HTTP 或 Unix 域套接字模式的服务器，运行时文件放在配置目录下：
+// - `server.pid`：进程 ID，服务进程在整个生命周期内持有该文件的排他锁
+// - `server.json`：启动时间、传输方式、端口或套接字路径等运行信息
+// - `server.log`：服务进程的标准输出与标准错误
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::control::{start_control_socket, ControlSocket};
+use super::server::{bind_http, shutdown_signal, ServerMode, ZhiServer};
+use super::unix_socket::bind_unix_socket;
+use crate::config::get_standalone_config_path;
+use crate::log_important;
+
+/// 等待后台进程就绪的最长时间
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 发送 SIGTERM 后等待进程退出的最长时间，略长于服务器的优雅关闭等待
+pub const STOP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 查询 /health 的超时时间
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 轮询进程状态的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 后台进程的运行时文件位置
+#[derive(Debug, Clone)]
+pub struct DaemonPaths {
+    pub pid_file: PathBuf,
+    pub state_file: PathBuf,
+    pub log_file: PathBuf,
+}
+
+impl DaemonPaths {
+    /// 与配置文件位于同一目录
+    pub fn new() -> Result<Self> {
+        let config_path = get_standalone_config_path()?;
+        let dir = config_path.parent()
+            .ok_or_else(|| anyhow::anyhow!("无法确定配置目录"))?;
+        Ok(Self {
+            pid_file: dir.join("server.pid"),
+            state_file: dir.join("server.json"),
+            log_file: dir.join("server.log"),
+        })
+    }
+}
+
+/// 后台进程启动后写入的运行信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonState {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub transport: String,
+    /// HTTP 模式的监听地址
+    #[serde(default)]
+    pub host: Option<String>,
+    /// HTTP 模式的监听端口
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Unix 域套接字模式的套接字路径
+    #[serde(default)]
+    pub socket: Option<PathBuf>,
+    pub log_file: PathBuf,
+}
+
+impl DaemonState {
+    /// 已运行时长
+    pub fn uptime(&self) -> Duration {
+        (Utc::now() - self.started_at).to_std().unwrap_or_default()
+    }
+
+    /// MCP 端点地址
+    pub fn endpoint(&self) -> String {
+        match (&self.socket, self.http_addr()) {
+            (Some(socket), _) => format!("unix:{}", socket.display()),
+            (None, Some(addr)) => format!("http://{}/mcp", addr),
+            (None, None) => "未知".to_string(),
+        }
+    }
+
+    /// 连接 HTTP 服务使用的地址，监听所有地址时使用本机地址
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        let ip = self.host.as_deref()
+            .and_then(|host| host.parse::<IpAddr>().ok())
+            .filter(|ip| !ip.is_unspecified())
+            .unwrap_or(IpAddr::from([127, 0, 0, 1]));
+        Some(SocketAddr::new(ip, self.port?))
+    }
+
+    /// 以相同方式重新启动时使用的模式
+    pub fn mode(&self) -> ServerMode {
+        match (&self.socket, self.port) {
+            (Some(path), _) => ServerMode::Unix { path: path.clone() },
+            (None, port) => ServerMode::Http { port: port.unwrap_or_default() },
+        }
+    }
+}
+
+/// 正在运行的后台进程
+#[derive(Debug, Clone)]
+pub struct RunningDaemon {
+    pub pid: u32,
+    /// 进程仍在启动时为 None
+    pub state: Option<DaemonState>,
+}
+
+/// 服务进程持有的 PID 文件锁，释放时清理运行时文件
+pub struct DaemonLock {
+    file: File,
+    paths: DaemonPaths,
+}
+
+impl DaemonLock {
+    /// 锁定 PID 文件并写入当前进程 ID，已有服务进程运行时返回错误
+    pub fn acquire(paths: &DaemonPaths) -> Result<Self> {
+        let mut file = open_pid_file(paths)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = std::fs::read_to_string(&paths.pid_file).unwrap_or_default();
+                anyhow::bail!("服务器已在运行 (PID {})", pid.trim());
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(anyhow::anyhow!("无法锁定 PID 文件 {}: {}", paths.pid_file.display(), e));
+            }
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Self { file, paths: paths.clone() })
+    }
+
+    /// 写入运行信息
+    pub fn write_state(&self, state: &DaemonState) -> Result<()> {
+        std::fs::write(&self.paths.state_file, serde_json::to_string_pretty(state)?)?;
+        Ok(())
+    }
+}
+
+impl Drop for DaemonLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.paths.state_file);
+        let _ = std::fs::remove_file(&self.paths.pid_file);
+        let _ = self.file.unlock();
+    }
+}
+
+fn open_pid_file(paths: &DaemonPaths) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&paths.pid_file)
+        .map_err(|e| anyhow::anyhow!("无法打开 PID 文件 {}: {}", paths.pid_file.display(), e))
+}
+
+/// 查找正在运行的后台进程
+///
+/// 以 PID 文件是否被锁定判断进程是否存活，进程异常退出后残留的文件会被清理
+pub fn running_daemon(paths: &DaemonPaths) -> Result<Option<RunningDaemon>> {
+    if !paths.pid_file.exists() {
+        return Ok(None);
+    }
+
+    let file = open_pid_file(paths)?;
+    match file.try_lock() {
+        Ok(()) => {
+            // 没有进程持有锁，说明上次的服务进程已经退出
+            let _ = std::fs::remove_file(&paths.state_file);
+            let _ = std::fs::remove_file(&paths.pid_file);
+            let _ = file.unlock();
+            Ok(None)
+        }
+        Err(TryLockError::WouldBlock) => {
+            let state = read_state(paths);
+            let pid = match &state {
+                Some(state) => Some(state.pid),
+                None => std::fs::read_to_string(&paths.pid_file).ok()
+                    .and_then(|pid| pid.trim().parse().ok()),
+            };
+            match pid {
+                Some(pid) => Ok(Some(RunningDaemon { pid, state })),
+                None => anyhow::bail!("服务器正在运行，但无法读取 PID 文件 {}", paths.pid_file.display()),
+            }
+        }
+        Err(TryLockError::Error(e)) => {
+            Err(anyhow::anyhow!("无法检查 PID 文件 {}: {}", paths.pid_file.display(), e))
+        }
+    }
+}
+
+fn read_state(paths: &DaemonPaths) -> Option<DaemonState> {
+    let content = std::fs::read_to_string(&paths.state_file).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// 在当前进程中运行后台服务（由 `server run` 调用）
+pub async fn run_daemon(server: &ZhiServer, mode: ServerMode) -> Result<()> {
+    let paths = DaemonPaths::new()?;
+    let lock = DaemonLock::acquire(&paths)?;
+
+    if server.get_enabled_tools().is_empty() {
+        anyhow::bail!("没有启用的 MCP 工具");
+    }
+
+    let mut state = DaemonState {
+        pid: std::process::id(),
+        started_at: Utc::now(),
+        transport: String::new(),
+        host: None,
+        port: None,
+        socket: None,
+        log_file: paths.log_file.clone(),
+    };
+
+    let result = match mode {
+        ServerMode::Http { port } => {
+            let (listener, token) = bind_http(&server.config().get().http_config, port).await?;
+            let addr = listener.local_addr()?;
+            state.transport = "http".to_string();
+            state.host = Some(addr.ip().to_string());
+            state.port = Some(addr.port());
+            let _control = announce(&lock, &state)?;
+            server.clone().with_http_token(token).serve_http(listener, shutdown_signal()).await
+        }
+        ServerMode::Unix { path } => {
+            let listener = bind_unix_socket(&path)?;
+            state.transport = "unix".to_string();
+            state.socket = Some(path);
+            let _control = announce(&lock, &state)?;
+            server.serve_unix(listener, shutdown_signal()).await
+        }
+        ServerMode::Stdio => anyhow::bail!("后台服务不支持 stdio 模式"),
+    };
+
+    log_important!(info, "后台服务已退出 (PID {})", state.pid);
+    drop(lock);
+    result
+}
+
+/// 写入运行信息并启动控制套接字，此后 `server start` 视为启动完成
+fn announce(lock: &DaemonLock, state: &DaemonState) -> Result<Option<Arc<ControlSocket>>> {
+    lock.write_state(state)?;
+    log_important!(info, "后台服务已启动 (PID {})，监听地址: {}", state.pid, state.endpoint());
+    Ok(start_control_socket())
+}
+
+/// 启动后台服务进程，等待其写入运行信息
+///
+/// HTTP 模式的端口为 0 时由系统分配空闲端口
+pub async fn spawn_daemon(mode: &ServerMode) -> Result<DaemonState> {
+    let mode_args = match mode {
+        ServerMode::Http { port } => vec!["--port".to_string(), port.to_string()],
+        ServerMode::Unix { path } => vec!["--socket".to_string(), path.to_string_lossy().to_string()],
+        ServerMode::Stdio => anyhow::bail!("后台服务不支持 stdio 模式"),
+    };
+
+    let paths = DaemonPaths::new()?;
+    if let Some(running) = running_daemon(&paths)? {
+        anyhow::bail!("服务器已在运行 (PID {})", running.pid);
+    }
+
+    let log = OpenOptions::new().create(true).append(true).open(&paths.log_file)
+        .map_err(|e| anyhow::anyhow!("无法打开日志文件 {}: {}", paths.log_file.display(), e))?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(["server", "run"])
+        .args(&mode_args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    // 脱离当前终端的进程组，避免随终端一起收到信号
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn()
+        .map_err(|e| anyhow::anyhow!("无法启动后台进程: {}", e))?;
+    let pid = child.id();
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        if let Some(state) = read_state(&paths).filter(|state| state.pid == pid) {
+            return Ok(state);
+        }
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!("后台进程启动失败 ({})，详见日志: {}", status, paths.log_file.display());
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!("后台进程在 {} 秒内未就绪，详见日志: {}", STARTUP_TIMEOUT.as_secs(), paths.log_file.display());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// 向后台进程发送 SIGTERM 并等待其退出，未运行时返回 None
+pub async fn stop_daemon() -> Result<Option<RunningDaemon>> {
+    let paths = DaemonPaths::new()?;
+    let Some(running) = running_daemon(&paths)? else {
+        return Ok(None);
+    };
+
+    terminate(running.pid)?;
+
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while running_daemon(&paths)?.is_some() {
+        if Instant::now() >= deadline {
+            anyhow::bail!("服务器 (PID {}) 在 {} 秒内未退出", running.pid, STOP_TIMEOUT.as_secs());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    Ok(Some(running))
+}
+
+/// 请求进程正常退出
+fn terminate(pid: u32) -> Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("taskkill");
+        command.args(["/PID", &pid.to_string()]);
+        command
+    } else {
+        let mut command = Command::new("kill");
+        command.args(["-TERM", &pid.to_string()]);
+        command
+    };
+
+    let status = command.stdout(Stdio::null()).stderr(Stdio::null()).status()
+        .map_err(|e| anyhow::anyhow!("无法向进程 {} 发送停止信号: {}", pid, e))?;
+    if !status.success() {
+        anyhow::bail!("无法向进程 {} 发送停止信号", pid);
+    }
+    Ok(())
+}
+
+/// 查询后台服务的 /health，服务无响应时返回 None
+pub async fn fetch_health(addr: SocketAddr) -> Option<serde_json::Value> {
+    let request = async {
+        let mut stream = TcpStream::connect(addr).await.ok()?;
+        stream.write_all(format!("GET /health HTTP/1.0\r\nHost: {}\r\n\r\n", addr).as_bytes()).await.ok()?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.ok()?;
+        let response = String::from_utf8_lossy(&response);
+        let (_, body) = response.split_once("\r\n\r\n")?;
+        serde_json::from_str(body).ok()
+    };
+
+    tokio::time::timeout(HEALTH_TIMEOUT, request).await.ok().flatten()
+}
@@ -0,0 +1,272 @@
+// MCP 网关：代理下游 MCP 服务器
+//
+// 按 `gateway_config.servers` 连接下游服务器（stdio 命令或 HTTP 端点），把它们的工具、资源和提示词
+// 与 `zhi`、`ji` 一起导出：工具和提示词以 `<名称>__` 为前缀，资源保留原 URI、名称加前缀。
+// 代理工具注册到服务器的工具注册表，配置中的启用开关、参数校验和运行指标对它们同样生效。
+// 下游的列表在连接时读取一次；发往同一下游的请求依次发送
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use super::client::{ClientTarget, McpClient, RpcError};
+use super::session::RequestContext;
+use super::tools::{McpTool, ToolFuture};
+use super::types::McpError;
+use crate::config::DownstreamServer;
+use crate::{log_debug, log_important};
+
+/// 命名空间前缀与下游名称之间的分隔符
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// 连接并初始化单个下游服务器的最长时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 已连接的下游服务器
+pub struct Downstream {
+    name: String,
+    client: Mutex<McpClient>,
+    tools: Vec<Value>,
+    resources: Vec<Value>,
+    resource_templates: Vec<Value>,
+    prompts: Vec<Value>,
+}
+
+impl Downstream {
+    /// 连接并初始化下游服务器，读取其工具、资源和提示词列表
+    pub async fn connect(config: &DownstreamServer) -> Result<Self> {
+        config.validate()?;
+        let target = match &config.url {
+            Some(url) => ClientTarget::Http(url.clone()),
+            None => ClientTarget::Command(config.command.clone()),
+        };
+
+        let mut client = McpClient::connect(&target).await?;
+        client.set_bearer_token(config.token.clone());
+        let name = config.name.clone();
+        client.on_notification(move |message| {
+            log_debug!("下游 {} 的通知: {}", name, message["method"].as_str().unwrap_or("?"));
+        });
+
+        let info = client.initialize_as("cunzhi-gateway").await?;
+        let capabilities = &info["capabilities"];
+        let mut downstream = Self {
+            name: config.name.clone(),
+            client: Mutex::new(client),
+            tools: Vec::new(),
+            resources: Vec::new(),
+            resource_templates: Vec::new(),
+            prompts: Vec::new(),
+        };
+
+        let client = downstream.client.get_mut();
+        if capabilities.get("tools").is_some() {
+            downstream.tools = client.list_all("tools/list", "tools").await?;
+        }
+        if capabilities.get("resources").is_some() {
+            downstream.resources = client.list_all("resources/list", "resources").await?;
+            // 模板列表是可选的
+            downstream.resource_templates = client.list_all("resources/templates/list", "resourceTemplates").await
+                .unwrap_or_default();
+        }
+        if capabilities.get("prompts").is_some() {
+            downstream.prompts = client.list_all("prompts/list", "prompts").await?;
+        }
+        Ok(downstream)
+    }
+
+    /// 命名空间前缀，即配置中的名称
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 下游的工具列表（原名称）
+    pub fn tools(&self) -> &[Value] {
+        &self.tools
+    }
+
+    fn prefixed(&self, name: &str) -> String {
+        format!("{}{}{}", self.name, NAMESPACE_SEPARATOR, name)
+    }
+
+    /// 去掉本服务器的前缀，不属于本服务器时返回 None
+    fn strip_prefix<'a>(&self, name: &'a str) -> Option<&'a str> {
+        name.strip_prefix(self.name.as_str())?.strip_prefix(NAMESPACE_SEPARATOR)
+    }
+
+    /// 资源 URI 是否在本服务器的资源列表中
+    fn lists_resource(&self, uri: &str) -> bool {
+        self.resources.iter().any(|resource| resource["uri"] == uri)
+    }
+
+    /// 资源 URI 是否匹配本服务器某个模板中第一个参数之前的部分
+    fn matches_resource_template(&self, uri: &str) -> bool {
+        self.resource_templates.iter()
+            .filter_map(|template| template["uriTemplate"].as_str())
+            .filter_map(|template| template.split('{').next())
+            .any(|prefix| !prefix.is_empty() && uri.starts_with(prefix))
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let result = self.client.lock().await.request(method, params).await;
+        result.map_err(|e| match e.downcast::<RpcError>() {
+            Ok(error) => McpError::from_code(error.code, format!("下游服务器 {}: {}", self.name, error.message)),
+            Err(e) => McpError::internal_error(format!("下游服务器 {} 请求失败: {}", self.name, e), None),
+        })
+    }
+}
+
+/// 全部下游服务器
+#[derive(Clone, Default)]
+pub struct Gateway {
+    downstreams: Vec<Arc<Downstream>>,
+}
+
+impl Gateway {
+    /// 并发连接所有启用的下游服务器，连接失败的服务器记录日志后跳过
+    pub async fn connect(servers: &[DownstreamServer]) -> Self {
+        let mut tasks = tokio::task::JoinSet::new();
+        for (index, server) in servers.iter().filter(|server| server.enabled).cloned().enumerate() {
+            tasks.spawn(async move {
+                let result = tokio::time::timeout(CONNECT_TIMEOUT, Downstream::connect(&server)).await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("连接超时")));
+                (index, server.name, result)
+            });
+        }
+
+        let mut connected = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            let Ok((index, name, result)) = joined else { continue };
+            match result {
+                Ok(downstream) => {
+                    log_important!(info, "已连接下游服务器 {}：{} 个工具，{} 个资源，{} 个提示词",
+                        name, downstream.tools.len(), downstream.resources.len(), downstream.prompts.len());
+                    connected.push((index, Arc::new(downstream)));
+                }
+                Err(e) => log_important!(warn, "无法连接下游服务器 {}: {}", name, e),
+            }
+        }
+
+        // 保持配置中的顺序
+        connected.sort_by_key(|(index, _)| *index);
+        Self { downstreams: connected.into_iter().map(|(_, downstream)| downstream).collect() }
+    }
+
+    pub fn downstreams(&self) -> &[Arc<Downstream>] {
+        &self.downstreams
+    }
+
+    /// 所有下游工具的代理，名称带前缀
+    pub fn tools(&self) -> Vec<ProxyTool> {
+        self.downstreams.iter()
+            .flat_map(|downstream| downstream.tools.iter().map(move |tool| ProxyTool::new(downstream.clone(), tool)))
+            .collect()
+    }
+
+    /// 下游资源，名称带前缀
+    pub fn list_resources(&self) -> Vec<Value> {
+        self.prefixed_items(|downstream| &downstream.resources)
+    }
+
+    /// 下游资源模板，名称带前缀
+    pub fn list_resource_templates(&self) -> Vec<Value> {
+        self.prefixed_items(|downstream| &downstream.resource_templates)
+    }
+
+    /// 下游提示词，名称带前缀
+    pub fn list_prompts(&self) -> Vec<Value> {
+        self.prefixed_items(|downstream| &downstream.prompts)
+    }
+
+    fn prefixed_items(&self, items: impl Fn(&Downstream) -> &[Value]) -> Vec<Value> {
+        self.downstreams.iter()
+            .flat_map(|downstream| items(downstream).iter().map(move |item| {
+                let mut item = item.clone();
+                if let Some(name) = item["name"].as_str() {
+                    item["name"] = downstream.prefixed(name).into();
+                }
+                item
+            }))
+            .collect()
+    }
+
+    /// 读取下游资源，没有下游提供该 URI 时返回 None
+    ///
+    /// 先在所有下游的资源列表中查找，都没有列出时才按资源模板的前缀匹配，
+    /// 避免前面下游的宽泛模板截获后面下游明确列出的资源
+    pub async fn read_resource(&self, uri: &str) -> Option<Result<Value, McpError>> {
+        let downstream = self.downstreams.iter().find(|downstream| downstream.lists_resource(uri))
+            .or_else(|| self.downstreams.iter().find(|downstream| downstream.matches_resource_template(uri)))?;
+        Some(downstream.request("resources/read", json!({ "uri": uri })).await)
+    }
+
+    /// 获取带前缀的下游提示词，名称不属于任何下游时返回 None
+    pub async fn get_prompt(&self, name: &str, arguments: &Value) -> Option<Result<Value, McpError>> {
+        let (downstream, prompt) = self.downstreams.iter()
+            .find_map(|downstream| downstream.strip_prefix(name).map(|prompt| (downstream, prompt)))?;
+        let mut params = json!({ "name": prompt });
+        if !arguments.is_null() {
+            params["arguments"] = arguments.clone();
+        }
+        Some(downstream.request("prompts/get", params).await)
+    }
+}
+
+/// 代理到下游服务器的工具
+pub struct ProxyTool {
+    downstream: Arc<Downstream>,
+    /// 带前缀的名称
+    name: String,
+    /// 下游的原名称
+    remote_name: String,
+    description: String,
+    input_schema: Value,
+    output_schema: Option<Value>,
+}
+
+impl ProxyTool {
+    /// 由下游 `tools/list` 中的一项创建代理工具
+    fn new(downstream: Arc<Downstream>, tool: &Value) -> Self {
+        let remote_name = tool["name"].as_str().unwrap_or_default().to_string();
+        let name = downstream.prefixed(&remote_name);
+        let description = tool["description"].as_str().unwrap_or(&remote_name).to_string();
+        let input_schema = match &tool["inputSchema"] {
+            Value::Null => json!({ "type": "object" }),
+            schema => schema.clone(),
+        };
+        let output_schema = tool.get("outputSchema").filter(|schema| !schema.is_null()).cloned();
+
+        Self { downstream, name, remote_name, description, input_schema, output_schema }
+    }
+}
+
+impl McpTool for ProxyTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn input_schema(&self) -> Value {
+        self.input_schema.clone()
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        self.output_schema.clone()
+    }
+
+    fn call<'a>(&'a self, arguments: Value, _ctx: &'a RequestContext) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let params = json!({ "name": self.remote_name, "arguments": arguments });
+            let result = self.downstream.request("tools/call", params).await?;
+            serde_json::from_value(result).map_err(|e| McpError::internal_error(
+                format!("下游服务器 {} 返回的工具结果格式错误: {}", self.downstream.name, e), None,
+            ))
+        })
+    }
+}
+
@@ -0,0 +1,502 @@
+// 工具调用审批网关（`cunzhi guard`）
+//
+// 作为 stdio 包装器位于客户端与一个下游 MCP 服务器之间，原样转发双方的消息。
+// 匹配 `guard_config.rules` 的 `tools/call` 先暂停，通过弹窗、终端窗口或 `cunzhi answer`
+// 让用户批准、拒绝或修改参数，然后转发或直接向客户端返回错误。网关作为客户端的子进程运行，
+// 控制终端属于客户端，因此不使用命令行交互。超过 `approval_timeout_seconds` 未回复视为拒绝。
+// 每次决定追加到审计日志（JSONL）
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+use super::control::start_control_socket;
+use super::session::RequestContext;
+use super::tools::interaction::InteractionTool;
+use super::types::{CallToolResult, ZhiRequest};
+use crate::config::{AppConfig, GuardConfig, GuardRule};
+use crate::{log_debug, log_important};
+
+/// 批准：按原参数执行
+pub const APPROVE_OPTION: &str = "批准";
+/// 拒绝：不执行，向客户端返回错误
+pub const DENY_OPTION: &str = "拒绝";
+
+/// 通配符匹配：`*` 匹配任意字符序列，`?` 匹配单个字符
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let regex = regex::escape(pattern).replace(r"\*", ".*").replace(r"\?", ".");
+    regex::Regex::new(&format!("(?s)^{}$", regex)).is_ok_and(|regex| regex.is_match(text))
+}
+
+/// 用户对一次工具调用的决定
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuardDecision {
+    /// 批准，按给定参数转发；`edited` 表示参数经用户修改
+    Approve { arguments: Value, edited: bool },
+    /// 拒绝，原因作为工具错误返回给客户端
+    Deny { reason: String },
+}
+
+/// 审计日志中的决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditDecision {
+    Approved,
+    Edited,
+    Denied,
+    /// 超时未回复，视为拒绝
+    #[serde(rename = "timed_out")]
+    TimedOut,
+}
+
+/// 审计日志的一条记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// 下游服务器的启动命令
+    pub server: String,
+    pub tool: String,
+    /// 客户端请求的参数
+    pub arguments: Value,
+    /// 匹配的规则
+    pub rule: String,
+    pub decision: AuditDecision,
+    /// 修改后实际转发的参数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarded_arguments: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 用户通过哪种交互方式回复（popup、cli、control 等）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// 以追加方式写入的审计日志
+struct AuditLog {
+    file: Mutex<LineWriter<File>>,
+}
+
+impl AuditLog {
+    fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| anyhow::anyhow!("无法打开审计日志 {}: {}", path.display(), e))?;
+        Ok(Self { file: Mutex::new(LineWriter::new(file)) })
+    }
+
+    fn append(&self, entry: &AuditEntry) {
+        let Ok(line) = serde_json::to_string(entry) else { return };
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(file, "{}", line) {
+            log_important!(warn, "写入审计日志失败: {}", e);
+        }
+    }
+}
+
+/// 审批网关
+pub struct Guard {
+    rules: Vec<GuardRule>,
+    /// 规则中 `outside_project` 的判断依据
+    project_root: PathBuf,
+    /// 下游服务器的启动命令，显示给用户并写入审计日志
+    server: String,
+    /// 等待用户批准的最长时间
+    approval_timeout: Duration,
+    /// 询问用户时使用的配置，未指定时读取配置文件
+    interaction_config: Option<AppConfig>,
+    audit: AuditLog,
+}
+
+impl Guard {
+    /// 按配置创建审批网关，审计日志以追加方式打开
+    pub fn new(config: &GuardConfig, project_root: &Path, audit_log: &Path, server: String) -> Result<Self> {
+        let project_root = project_root.canonicalize()
+            .map_err(|e| anyhow::anyhow!("无效的项目目录 {}: {}", project_root.display(), e))?;
+        Ok(Self {
+            rules: config.rules.clone(),
+            project_root,
+            server,
+            approval_timeout: Duration::from_secs(config.approval_timeout_seconds),
+            interaction_config: None,
+            audit: AuditLog::open(audit_log)?,
+        })
+    }
+
+    /// 询问用户时使用给定的配置
+    ///
+    /// 终端窗口和控制套接字的等待时间放宽到审批超时之后，超时统一由审批超时处理并记为 `timed_out`
+    pub fn with_interaction_config(mut self, mut config: AppConfig) -> Self {
+        let wait = u32::try_from(self.approval_timeout.as_secs().saturating_add(1)).unwrap_or(u32::MAX);
+        config.terminal_config.timeout_seconds = config.terminal_config.timeout_seconds.max(wait);
+        self.interaction_config = Some(config);
+        self
+    }
+
+    /// 第一条匹配该调用的规则，不需要批准时返回 None
+    pub fn matching_rule(&self, tool: &str, arguments: &Value) -> Option<&GuardRule> {
+        self.rules.iter().find(|rule| self.rule_matches(rule, tool, arguments))
+    }
+
+    fn rule_matches(&self, rule: &GuardRule, tool: &str, arguments: &Value) -> bool {
+        if !wildcard_match(&rule.tool, tool) {
+            return false;
+        }
+
+        let mut values = Vec::new();
+        match &rule.argument {
+            Some(name) => match arguments.get(name) {
+                Some(value) => collect_strings(value, &mut values),
+                None => return false,
+            },
+            None => collect_strings(arguments, &mut values),
+        }
+        if rule.pattern.is_none() && !rule.outside_project {
+            return true;
+        }
+        values.iter().any(|value| {
+            rule.pattern.as_ref().is_none_or(|pattern| wildcard_match(pattern, value))
+                && (!rule.outside_project || self.is_outside_project(value))
+        })
+    }
+
+    /// 参数值作为路径（相对路径基于项目目录）是否位于项目目录之外
+    fn is_outside_project(&self, value: &str) -> bool {
+        let value = value.strip_prefix("file://").unwrap_or(value);
+        if value.is_empty() || value.contains('\n') {
+            return false;
+        }
+        let path = match value.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => match dirs::home_dir() {
+                Some(home) => home.join(rest.trim_start_matches('/')),
+                None => return true,
+            },
+            _ => PathBuf::from(value),
+        };
+        !normalize_path(&self.project_root.join(path)).starts_with(&self.project_root)
+    }
+
+    /// 暂停调用并询问用户，决定写入审计日志；无法询问用户或超时未回复时视为拒绝
+    pub async fn review(&self, tool: &str, arguments: &Value, rule: &GuardRule) -> GuardDecision {
+        let request = ZhiRequest {
+            message: self.approval_message(tool, arguments, rule),
+            predefined_options: vec![APPROVE_OPTION.to_string(), DENY_OPTION.to_string()],
+            is_markdown: true,
+            terminal_mode: None,
+        };
+        // 控制终端属于客户端，命令行交互会与客户端争用终端
+        let mut ctx = RequestContext::detached().without_cli_fallback();
+        if let Some(config) = &self.interaction_config {
+            ctx = ctx.with_config(config.clone());
+        }
+        let interaction = tokio::time::timeout(self.approval_timeout, InteractionTool::zhi_with_context(request, &ctx));
+        let (decision, source, timed_out) = match interaction.await {
+            Ok(Ok(result)) => {
+                let response = result.structured_content.unwrap_or_default();
+                let source = response["metadata"]["source"].as_str().map(str::to_string);
+                (decision_from_response(&response, arguments), source, false)
+            }
+            Ok(Err(e)) => (GuardDecision::Deny { reason: format!("无法获取用户批准: {}", e.message()) }, None, false),
+            Err(_) => {
+                let reason = format!("等待用户批准超时（{}秒），调用未执行", self.approval_timeout.as_secs());
+                (GuardDecision::Deny { reason }, None, true)
+            }
+        };
+
+        let (audit_decision, forwarded_arguments, reason) = match &decision {
+            GuardDecision::Approve { edited: false, .. } => (AuditDecision::Approved, None, None),
+            GuardDecision::Approve { arguments, edited: true } => (AuditDecision::Edited, Some(arguments.clone()), None),
+            GuardDecision::Deny { reason } if timed_out => (AuditDecision::TimedOut, None, Some(reason.clone())),
+            GuardDecision::Deny { reason } => (AuditDecision::Denied, None, Some(reason.clone())),
+        };
+        log_important!(info, "工具调用 {} 的审批结果: {:?}", tool, audit_decision);
+        self.audit.append(&AuditEntry {
+            timestamp: Utc::now(),
+            server: self.server.clone(),
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            rule: describe_rule(rule),
+            decision: audit_decision,
+            forwarded_arguments,
+            reason,
+            source,
+        });
+        decision
+    }
+
+    /// 首行包含工具名称，`cunzhi pending` 只显示首行
+    fn approval_message(&self, tool: &str, arguments: &Value, rule: &GuardRule) -> String {
+        format!(
+            "## ⚠️ 工具调用需要批准: {}\n\n\
+             - **服务器**: `{}`\n\
+             - **规则**: {}\n\n\
+             **参数**:\n\n```json\n{}\n```\n\n\
+             选择「{}」按原参数执行，「{}」阻止本次调用；不选择选项、直接输入新的 JSON 参数对象，按新参数执行。",
+            tool,
+            self.server,
+            describe_rule(rule),
+            serde_json::to_string_pretty(arguments).unwrap_or_default(),
+            APPROVE_OPTION,
+            DENY_OPTION
+        )
+    }
+
+    /// 启动下游服务器，在 `input`/`output`（客户端）与下游的 stdio 之间转发，直到任一方关闭
+    pub async fn run<R, W>(self, command: &[String], input: R, output: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (program, args) = command.split_first()
+            .ok_or_else(|| anyhow::anyhow!("缺少下游服务器的启动命令"))?;
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("无法启动下游服务器 {}: {}", program, e))?;
+        let mut child_stdin = child.stdin.take().expect("stdin 已设为管道");
+        let child_stdout = child.stdout.take().expect("stdout 已设为管道");
+
+        // 等待批准的调用可以通过 `cunzhi pending / answer` 在其它终端处理
+        let _control = start_control_socket();
+        let guard = Arc::new(self);
+
+        let (client_tx, mut client_rx) = mpsc::unbounded_channel::<String>();
+        let (server_tx, mut server_rx) = mpsc::unbounded_channel::<String>();
+
+        let client_writer = tokio::spawn(async move {
+            let mut output = output;
+            while let Some(line) = client_rx.recv().await {
+                output.write_all(line.as_bytes()).await?;
+                output.write_all(b"\n").await?;
+                output.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        });
+        let server_writer = tokio::spawn(async move {
+            while let Some(line) = server_rx.recv().await {
+                child_stdin.write_all(line.as_bytes()).await?;
+                child_stdin.write_all(b"\n").await?;
+                child_stdin.flush().await?;
+            }
+            // 所有发送端释放后关闭下游的标准输入
+            Ok::<_, std::io::Error>(())
+        });
+
+        let downstream = {
+            let client_tx = client_tx.clone();
+            async move {
+                let mut lines = BufReader::new(child_stdout).lines();
+                while let Some(line) = lines.next_line().await? {
+                    let _ = client_tx.send(line);
+                }
+                Ok::<_, std::io::Error>(())
+            }
+        };
+        let upstream = {
+            let client_tx = client_tx.clone();
+            async move {
+                let mut reviews = tokio::task::JoinSet::new();
+                let mut lines = BufReader::new(input).lines();
+                while let Some(line) = lines.next_line().await? {
+                    match guard.intercept(&line) {
+                        Intercept::Forward => {
+                            let _ = server_tx.send(line);
+                        }
+                        Intercept::Reply(response) => {
+                            let _ = client_tx.send(response.to_string());
+                        }
+                        Intercept::Drop => {
+                            log::warn!("丢弃需要批准但没有 id 的工具调用: {}", line);
+                        }
+                        Intercept::Review { message, rule } => {
+                            let (guard, client_tx, server_tx) = (guard.clone(), client_tx.clone(), server_tx.clone());
+                            reviews.spawn(async move {
+                                guard.review_and_forward(message, rule, &client_tx, &server_tx).await;
+                            });
+                        }
+                    }
+                }
+                // 客户端已关闭，等待中的审批不再有人接收结果
+                reviews.shutdown().await;
+                Ok::<_, std::io::Error>(())
+            }
+        };
+
+        // 下游退出时立即结束；客户端关闭输入时继续转发下游剩余的输出
+        tokio::pin!(downstream);
+        tokio::select! {
+            result = &mut downstream => result?,
+            result = upstream => {
+                result?;
+                downstream.await?;
+            }
+        }
+
+        server_writer.abort();
+        drop(client_tx);
+        client_writer.await??;
+        let status = child.wait().await?;
+        log_debug!("下游服务器已退出: {}", status);
+        Ok(())
+    }
+
+    /// 检查客户端发来的一行消息
+    pub fn intercept(&self, line: &str) -> Intercept {
+        let Ok(message) = serde_json::from_str::<Value>(line) else { return Intercept::Forward };
+        match &message {
+            Value::Array(batch) => {
+                if !batch.iter().any(|message| self.guarded_call(message).is_some()) {
+                    return Intercept::Forward;
+                }
+                // 批量请求无法部分暂停，整批拒绝
+                let errors: Vec<Value> = batch.iter()
+                    .filter_map(|message| message.get("id"))
+                    .map(|id| json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {"code": -32600, "message": "批量请求中包含需要批准的工具调用，请逐条发送"}
+                    }))
+                    .collect();
+                // 全是通知时无法回复，也不能放行
+                if errors.is_empty() {
+                    return Intercept::Drop;
+                }
+                Intercept::Reply(Value::Array(errors))
+            }
+            _ => match self.guarded_call(&message) {
+                // 没有 id 的调用无法回复审批结果，直接丢弃
+                Some(_) if message.get("id").is_none() => Intercept::Drop,
+                Some(rule) => Intercept::Review { rule: rule.clone(), message },
+                None => Intercept::Forward,
+            },
+        }
+    }
+
+    /// 需要批准的 `tools/call` 请求所匹配的规则
+    fn guarded_call(&self, message: &Value) -> Option<&GuardRule> {
+        if message["method"] != "tools/call" {
+            return None;
+        }
+        let tool = message["params"]["name"].as_str()?;
+        self.matching_rule(tool, &call_arguments(message))
+    }
+
+    async fn review_and_forward(
+        &self,
+        mut message: Value,
+        rule: GuardRule,
+        client_tx: &mpsc::UnboundedSender<String>,
+        server_tx: &mpsc::UnboundedSender<String>,
+    ) {
+        let tool = message["params"]["name"].as_str().unwrap_or_default().to_string();
+        match self.review(&tool, &call_arguments(&message), &rule).await {
+            GuardDecision::Approve { arguments, .. } => {
+                message["params"]["arguments"] = arguments;
+                let _ = server_tx.send(message.to_string());
+            }
+            GuardDecision::Deny { reason } => {
+                let result = serde_json::to_value(CallToolResult::error(reason)).unwrap_or_default();
+                let response = json!({ "jsonrpc": "2.0", "id": message["id"], "result": result });
+                let _ = client_tx.send(response.to_string());
+            }
+        }
+    }
+}
+
+pub enum Intercept {
+    /// 原样转发给下游
+    Forward,
+    /// 直接回复客户端
+    Reply(Value),
+    /// 丢弃，不转发也不回复
+    Drop,
+    /// 等待用户批准
+    Review { message: Value, rule: GuardRule },
+}
+
+/// 根据 `zhi` 的回复得出决定：未选择选项时输入的 JSON 对象作为新参数，未明确批准的一律拒绝
+///
+/// 所有交互路径（弹窗、终端窗口、`cunzhi answer`）都支持不选选项直接输入文本
+pub fn decision_from_response(response: &Value, arguments: &Value) -> GuardDecision {
+    let selected: Vec<&str> = response["selected_options"].as_array().into_iter().flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let input = response["user_input"].as_str().map(str::trim).filter(|input| !input.is_empty());
+
+    if selected.contains(&APPROVE_OPTION) {
+        return GuardDecision::Approve { arguments: arguments.clone(), edited: false };
+    }
+    if let (true, Some(input)) = (selected.is_empty(), input) {
+        return match serde_json::from_str::<Value>(input).ok().filter(Value::is_object) {
+            Some(arguments) => GuardDecision::Approve { arguments, edited: true },
+            None => GuardDecision::Deny { reason: format!("用户输入的不是 JSON 参数对象，调用未执行：{}", input) },
+        };
+    }
+    let reason = match input {
+        Some(note) if selected.contains(&DENY_OPTION) => format!("用户拒绝了该工具调用：{}", note),
+        _ => "用户拒绝了该工具调用".to_string(),
+    };
+    GuardDecision::Deny { reason }
+}
+
+/// 规则的可读说明，优先使用配置的 reason
+pub fn describe_rule(rule: &GuardRule) -> String {
+    if let Some(reason) = &rule.reason {
+        return reason.clone();
+    }
+    let mut parts = vec![format!("工具匹配 `{}`", rule.tool)];
+    let target = rule.argument.as_ref().map(|name| format!("参数 `{}`", name)).unwrap_or_else(|| "参数".to_string());
+    if let Some(pattern) = &rule.pattern {
+        parts.push(format!("{}匹配 `{}`", target, pattern));
+    }
+    if rule.outside_project {
+        parts.push(format!("{}中的路径位于项目目录之外", target));
+    }
+    parts.join("，")
+}
+
+fn call_arguments(message: &Value) -> Value {
+    match &message["params"]["arguments"] {
+        Value::Null => json!({}),
+        arguments => arguments.clone(),
+    }
+}
+
+/// 收集 JSON 值中的所有字符串
+fn collect_strings<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::String(text) => out.push(text),
+        Value::Array(items) => items.iter().for_each(|item| collect_strings(item, out)),
+        Value::Object(map) => map.values().for_each(|item| collect_strings(item, out)),
+        _ => {}
+    }
+}
+
+/// 按字面处理 `.` 和 `..`，不访问文件系统（路径可能尚不存在）
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
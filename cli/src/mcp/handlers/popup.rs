@@ -1,31 +1,31 @@
 use anyhow::Result;
 use std::process::Command;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::mcp::types::PopupRequest;
 
 /// 创建 CLI 交互弹窗
 ///
-/// 优先调用与 MCP 服务器同目录的 UI 命令，找不到时使用全局版本
-pub fn create_cli_popup(request: &PopupRequest) -> Result<String> {
+/// 优先调用与 MCP 服务器同目录的 UI 命令，找不到时使用全局版本。
+/// 返回的 future 被丢弃（如请求被取消）时，UI 子进程会被终止
+pub async fn create_cli_popup(request: &PopupRequest) -> Result<String> {
     // 创建临时请求文件 - 跨平台适配
     let temp_dir = std::env::temp_dir();
-    let temp_file = temp_dir.join(format!("mcp_request_{}.json", request.id));
+    let temp_file = TempRequestFile(temp_dir.join(format!("mcp_request_{}.json", request.id)));
     let request_json = serde_json::to_string_pretty(request)?;
-    fs::write(&temp_file, request_json)?;
+    fs::write(&temp_file.0, request_json)?;
 
     // 尝试找到cunzhi-ui命令的路径
     let command_path = find_ui_command()?;
 
     // 调用cunzhi-ui命令
-    let output = Command::new(&command_path)
+    let output = tokio::process::Command::new(&command_path)
         .arg("--mcp-request")
-        .arg(temp_file.to_string_lossy().to_string())
-        .output()?;
-
-    // 清理临时文件
-    let _ = fs::remove_file(&temp_file);
+        .arg(temp_file.0.to_string_lossy().to_string())
+        .kill_on_drop(true)
+        .output()
+        .await?;
 
     if output.status.success() {
         let response = String::from_utf8(output.stdout)?;
@@ -36,6 +36,15 @@ pub fn create_cli_popup(request: &PopupRequest) -> Result<String> {
     }
 }
 
+/// 临时请求文件，离开作用域时自动清理
+pub struct TempRequestFile(pub PathBuf);
+
+impl Drop for TempRequestFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
 /// 查找cunzhi-ui命令的路径
 ///
 /// 按优先级查找：同目录 -> 全局版本 -> 开发环境
@@ -86,7 +95,7 @@ fn test_cargo_ui_available() -> bool {
         if cargo_toml.exists() {
             // 尝试运行cargo run --bin cunzhi-ui --version
             return Command::new("cargo")
-                .args(&["run", "--bin", "cunzhi-ui", "--", "--version"])
+                .args(["run", "--bin", "cunzhi-ui", "--", "--version"])
                 .output()
                 .map(|output| output.status.success())
                 .unwrap_or(false);
@@ -100,11 +109,9 @@ fn is_executable(path: &Path) -> bool {
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        if let Ok(metadata) = path.metadata() {
-            let permissions = metadata.permissions();
-            return permissions.mode() & 0o111 != 0;
-        }
-        return false;
+        path.metadata()
+            .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
     }
 
     #[cfg(windows)]
@@ -1,10 +1,141 @@
 // 响应处理模块
 
 use anyhow::Result;
-use crate::mcp::types::Content;
+use crate::mcp::types::{Content, McpResponse, McpResponseContent};
 
-/// 解析 MCP 响应（CLI 版本简化实现）
+/// 解析 MCP 响应内容
+///
+/// 支持新的结构化格式和旧格式的兼容性，并生成适当的 Content 对象
 pub fn parse_mcp_response(response: &str) -> Result<Vec<Content>> {
-    // 简化的响应解析，返回文本内容
-    Ok(vec![Content::text(response.to_string())])
+    if response.trim() == "CANCELLED" || response.trim() == "用户取消了操作" {
+        return Ok(vec![Content::text("用户取消了操作".to_string())]);
+    }
+
+    // 首先尝试解析为新的结构化格式
+    if let Ok(structured_response) = serde_json::from_str::<McpResponse>(response) {
+        return Ok(parse_structured_response(structured_response));
+    }
+
+    // 回退到旧格式兼容性解析
+    match serde_json::from_str::<Vec<McpResponseContent>>(response) {
+        Ok(content_array) => {
+            let mut result = Vec::new();
+            let mut image_count = 0;
+
+            // 分别收集用户文本和图片信息
+            let mut user_text_parts = Vec::new();
+            let mut image_info_parts = Vec::new();
+
+            for content in content_array {
+                match content.content_type.as_str() {
+                    "image" => {
+                        if let Some(source) = content.source.filter(|source| source.source_type == "base64") {
+                            image_count += 1;
+                            image_info_parts.push(format_image_info(image_count, None, &source.media_type, &source.data));
+
+                            // 先添加图片到结果中（图片在前）
+                            result.push(Content::image(source.data, source.media_type));
+                        }
+                    }
+                    // 文本及未知类型都作为文本处理
+                    _ => {
+                        if let Some(text) = content.text {
+                            user_text_parts.push(text);
+                        }
+                    }
+                }
+            }
+
+            Ok(finish_contents(result, user_text_parts, image_info_parts, image_count))
+        }
+        Err(_) => {
+            // 如果不是JSON格式，作为纯文本处理
+            Ok(vec![Content::text(response.to_string())])
+        }
+    }
+}
+
+/// 解析新的结构化响应格式
+fn parse_structured_response(response: McpResponse) -> Vec<Content> {
+    let mut result = Vec::new();
+    let mut text_parts = Vec::new();
+
+    // 1. 处理选择的选项
+    if !response.selected_options.is_empty() {
+        text_parts.push(format!("选择的选项: {}", response.selected_options.join(", ")));
+    }
+
+    // 2. 处理用户输入文本
+    if let Some(user_input) = response.user_input {
+        if !user_input.trim().is_empty() {
+            text_parts.push(user_input.trim().to_string());
+        }
+    }
+
+    // 3. 处理图片附件
+    let mut image_info_parts = Vec::new();
+    let image_count = response.images.len();
+    for (index, image) in response.images.into_iter().enumerate() {
+        image_info_parts.push(format_image_info(index + 1, image.filename.as_deref(), &image.media_type, &image.data));
+
+        // 添加图片到结果中（图片在前）
+        result.push(Content::image(image.data, image.media_type));
+    }
+
+    finish_contents(result, text_parts, image_info_parts, image_count)
+}
+
+/// 在图片之后追加合并的文本：用户文本 + 图片信息 + 兼容性说明
+fn finish_contents(
+    mut result: Vec<Content>,
+    text_parts: Vec<String>,
+    image_info_parts: Vec<String>,
+    image_count: usize,
+) -> Vec<Content> {
+    let mut all_text_parts = text_parts;
+    all_text_parts.extend(image_info_parts);
+
+    if image_count > 0 {
+        all_text_parts.push(format!(
+            "💡 注意：用户提供了 {} 张图片。如果 AI 助手无法显示图片，图片数据已包含在上述 Base64 信息中。",
+            image_count
+        ));
+    }
+
+    if !all_text_parts.is_empty() {
+        result.push(Content::text(all_text_parts.join("\n\n")));
+    }
+
+    // 如果没有任何内容，添加默认响应
+    if result.is_empty() {
+        result.push(Content::text("用户未提供任何内容".to_string()));
+    }
+
+    result
+}
+
+/// 生成图片的文字说明，供无法显示图片的客户端使用
+fn format_image_info(index: usize, filename: Option<&str>, media_type: &str, data: &str) -> String {
+    let base64_len = data.len();
+    let preview = match data.get(..50) {
+        Some(prefix) if base64_len > 50 => format!("{}...", prefix),
+        _ => data.to_string(),
+    };
+
+    // 计算图片大小（base64 编码后大约增加 33%）
+    let estimated_size = (base64_len * 3) / 4;
+    let size_str = if estimated_size < 1024 {
+        format!("{} B", estimated_size)
+    } else if estimated_size < 1024 * 1024 {
+        format!("{:.1} KB", estimated_size as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", estimated_size as f64 / (1024.0 * 1024.0))
+    };
+
+    let filename_info = filename.map(|f| format!("\n文件名: {}", f)).unwrap_or_default();
+
+    format!(
+        "=== 图片 {} ==={}\n类型: {}\n大小: {}\nBase64 预览: {}\n完整 Base64 长度: {} 字符",
+        index, filename_info, media_type, size_str, preview, base64_len
+    )
 }
@@ -0,0 +1,140 @@
+// 服务器使用的内存配置
+//
+// 启动时读取一次配置文件，之后定期检查文件内容，变化时重新加载并广播变更，
+// 处理请求时不再读取磁盘（工具通过 `RequestContext::config` 取得快照）
+//
+// 这里用轮询而不是文件系统事件：配置文件很小，每次只读一个文件，开销可以忽略；
+// 轮询不依赖平台的监视机制，也能处理编辑器以替换文件方式保存和配置目录被重建的情况。
+// 代价是修改最多在 `CONFIG_POLL_INTERVAL` 之后才生效
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+
+use crate::config::{get_standalone_config_path, load_standalone_config, AppConfig};
+use crate::log_important;
+
+/// 检查配置文件是否变化的间隔
+pub const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 配置变更中客户端关心的部分
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigChange {
+    /// `mcp_config.tools` 发生变化，工具列表需要刷新
+    pub tools_changed: bool,
+    /// `prompt_config` 发生变化，提示词列表需要刷新
+    pub prompts_changed: bool,
+}
+
+struct State {
+    config: AppConfig,
+    /// 上次加载的文件内容，用于判断文件是否变化
+    raw: Option<String>,
+}
+
+/// 随配置文件自动更新的配置
+#[derive(Clone)]
+pub struct LiveConfig {
+    path: Option<PathBuf>,
+    state: Arc<RwLock<State>>,
+    changes: broadcast::Sender<ConfigChange>,
+}
+
+impl LiveConfig {
+    /// 加载默认位置的配置文件，加载失败时使用默认配置
+    pub fn load() -> Self {
+        let config = load_standalone_config().unwrap_or_else(|e| {
+            log_important!(warn, "无法加载配置文件，使用默认配置: {}", e);
+            AppConfig::default()
+        });
+        let path = get_standalone_config_path().ok();
+        let raw = path.as_ref().and_then(|path| std::fs::read_to_string(path).ok());
+        Self::with_state(path, config, raw)
+    }
+
+    /// 监视指定路径的配置文件
+    pub fn from_path(path: PathBuf) -> Self {
+        let raw = std::fs::read_to_string(&path).ok();
+        let config = raw.as_deref().and_then(|raw| parse_config(raw).ok()).unwrap_or_default();
+        Self::with_state(Some(path), config, raw)
+    }
+
+    fn with_state(path: Option<PathBuf>, config: AppConfig, raw: Option<String>) -> Self {
+        let (changes, _) = broadcast::channel(16);
+        Self {
+            path,
+            state: Arc::new(RwLock::new(State { config, raw })),
+            changes,
+        }
+    }
+
+    /// 当前配置
+    pub fn get(&self) -> AppConfig {
+        self.state.read().unwrap().config.clone()
+    }
+
+    /// 在不复制配置的情况下读取其中一部分
+    pub fn read<R>(&self, f: impl FnOnce(&AppConfig) -> R) -> R {
+        f(&self.state.read().unwrap().config)
+    }
+
+    /// 订阅配置变更
+    pub fn subscribe(&self) -> broadcast::Receiver<ConfigChange> {
+        self.changes.subscribe()
+    }
+
+    /// 检查配置文件，内容变化时重新加载
+    ///
+    /// 文件格式错误时保留原配置；返回本次加载带来的变更
+    pub fn reload(&self) -> Option<ConfigChange> {
+        let path = self.path.as_ref()?;
+        let raw = std::fs::read_to_string(path).ok()?;
+
+        let mut state = self.state.write().unwrap();
+        if state.raw.as_deref() == Some(raw.as_str()) {
+            return None;
+        }
+
+        let config = match parse_config(&raw) {
+            Ok(config) => config,
+            Err(e) => {
+                log_important!(warn, "配置文件已修改但无法加载，继续使用原配置: {}", e);
+                state.raw = Some(raw);
+                return None;
+            }
+        };
+
+        let change = ConfigChange {
+            tools_changed: config.mcp_config.tools != state.config.mcp_config.tools,
+            prompts_changed: serde_json::to_value(&config.prompt_config).ok()
+                != serde_json::to_value(&state.config.prompt_config).ok(),
+        };
+        state.config = config;
+        state.raw = Some(raw);
+        drop(state);
+
+        log_important!(info, "配置文件已重新加载: {}", path.display());
+        let _ = self.changes.send(change);
+        Some(change)
+    }
+
+    /// 定期检查配置文件
+    pub fn watch(&self) -> tokio::task::JoinHandle<()> {
+        let config = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(CONFIG_POLL_INTERVAL);
+            loop {
+                ticker.tick().await;
+                config.reload();
+            }
+        })
+    }
+}
+
+fn parse_config(raw: &str) -> anyhow::Result<AppConfig> {
+    let config: AppConfig = serde_json::from_str(raw)
+        .map_err(|e| anyhow::anyhow!("配置文件格式错误: {}", e))?;
+    config.validate()?;
+    Ok(config)
+}
@@ -0,0 +1,345 @@
+// 服务器运行指标
+//
+// 进程级的计数器与直方图：工具调用次数、`zhi` 等待用户回复的时长与实际使用的交互方式、
+// 交互失败后的回退次数、记忆写入次数和活动会话数。
+// HTTP 模式在 `/metrics` 以 Prometheus 文本格式提供，`cunzhi server metrics` 经控制套接字读取
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::tools::memory::MemoryCategory;
+
+/// `zhi` 等待时长直方图各桶的上限（秒）
+pub const WAIT_BUCKETS: [f64; 10] = [1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0];
+
+/// `zhi` 获得用户回复的交互方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionBackend {
+    /// 客户端内的 elicitation 表单
+    Elicitation,
+    /// 独立 UI 进程弹窗
+    UiProcess,
+    /// 新终端窗口
+    Terminal,
+    /// 服务器所在终端的命令行
+    Cli,
+    /// 控制套接字（`cunzhi answer`）
+    Control,
+}
+
+impl InteractionBackend {
+    pub const ALL: [InteractionBackend; 5] = [
+        InteractionBackend::Elicitation,
+        InteractionBackend::UiProcess,
+        InteractionBackend::Terminal,
+        InteractionBackend::Cli,
+        InteractionBackend::Control,
+    ];
+
+    /// 指标标签值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InteractionBackend::Elicitation => "elicitation",
+            InteractionBackend::UiProcess => "ui_process",
+            InteractionBackend::Terminal => "terminal",
+            InteractionBackend::Cli => "cli",
+            InteractionBackend::Control => "control",
+        }
+    }
+
+    /// 由标签值解析
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|backend| backend.as_str() == name)
+    }
+
+    /// 显示名称
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            InteractionBackend::Elicitation => "客户端表单",
+            InteractionBackend::UiProcess => "弹窗",
+            InteractionBackend::Terminal => "终端",
+            InteractionBackend::Cli => "命令行",
+            InteractionBackend::Control => "控制套接字",
+        }
+    }
+}
+
+/// 工具调用的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolOutcome {
+    Success,
+    /// 工具执行失败或返回 `isError`
+    Error,
+    /// 参数未通过 inputSchema 校验
+    InvalidArguments,
+    /// 工具已在配置中禁用
+    Disabled,
+    /// 请求在工具完成前被取消
+    Cancelled,
+}
+
+impl ToolOutcome {
+    pub const ALL: [ToolOutcome; 5] = [
+        ToolOutcome::Success,
+        ToolOutcome::Error,
+        ToolOutcome::InvalidArguments,
+        ToolOutcome::Disabled,
+        ToolOutcome::Cancelled,
+    ];
+
+    /// 指标标签值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolOutcome::Success => "success",
+            ToolOutcome::Error => "error",
+            ToolOutcome::InvalidArguments => "invalid_arguments",
+            ToolOutcome::Disabled => "disabled",
+            ToolOutcome::Cancelled => "cancelled",
+        }
+    }
+
+    /// 由标签值解析
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|outcome| outcome.as_str() == name)
+    }
+
+    /// 显示名称
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ToolOutcome::Success => "成功",
+            ToolOutcome::Error => "失败",
+            ToolOutcome::InvalidArguments => "参数错误",
+            ToolOutcome::Disabled => "已禁用",
+            ToolOutcome::Cancelled => "已取消",
+        }
+    }
+}
+
+/// 时长直方图
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// 各桶（非累积）的计数，与 `WAIT_BUCKETS` 对应，最后一项为超出最大上限的次数
+    pub buckets: Vec<u64>,
+    /// 所有观测值之和（秒）
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        self.buckets.resize(WAIT_BUCKETS.len() + 1, 0);
+        let index = WAIT_BUCKETS.iter().position(|&bound| secs <= bound).unwrap_or(WAIT_BUCKETS.len());
+        self.buckets[index] += 1;
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        self.buckets.resize(WAIT_BUCKETS.len() + 1, 0);
+        for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    /// 平均值（秒）
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// 估算分位数：返回该分位数所在桶的上限（秒），落在最大上限之外时为 `f64::INFINITY`
+    pub fn quantile_upper_bound(&self, quantile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let target = (quantile * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Some(WAIT_BUCKETS.get(index).copied().unwrap_or(f64::INFINITY));
+            }
+        }
+        Some(f64::INFINITY)
+    }
+}
+
+/// 某一时刻的全部指标，可在多个服务器进程间汇总
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// 工具 → 结果 → 调用次数
+    #[serde(default)]
+    pub tool_calls: BTreeMap<String, BTreeMap<String, u64>>,
+    /// 交互方式 → `zhi` 等待用户回复的时长
+    #[serde(default)]
+    pub zhi_waits: BTreeMap<String, Histogram>,
+    /// 失败后回退到下一种方式的交互方式 → 次数
+    #[serde(default)]
+    pub zhi_fallbacks: BTreeMap<String, u64>,
+    /// 记忆分类 → 写入次数
+    #[serde(default)]
+    pub memory_writes: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub active_sessions: u64,
+}
+
+impl MetricsSnapshot {
+    /// 累加另一个进程的指标
+    pub fn merge(&mut self, other: &MetricsSnapshot) {
+        for (tool, outcomes) in &other.tool_calls {
+            let entry = self.tool_calls.entry(tool.clone()).or_default();
+            for (outcome, count) in outcomes {
+                *entry.entry(outcome.clone()).or_default() += count;
+            }
+        }
+        for (backend, histogram) in &other.zhi_waits {
+            self.zhi_waits.entry(backend.clone()).or_default().merge(histogram);
+        }
+        for (backend, count) in &other.zhi_fallbacks {
+            *self.zhi_fallbacks.entry(backend.clone()).or_default() += count;
+        }
+        for (category, count) in &other.memory_writes {
+            *self.memory_writes.entry(category.clone()).or_default() += count;
+        }
+        self.active_sessions += other.active_sessions;
+    }
+
+    /// 某个工具各结果的调用次数之和
+    pub fn tool_call_total(&self, tool: &str) -> u64 {
+        self.tool_calls.get(tool).map(|outcomes| outcomes.values().sum()).unwrap_or(0)
+    }
+
+    /// 以 Prometheus 文本格式输出
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        write_header(&mut out, "cunzhi_tool_calls_total", "counter", "工具调用次数，按工具和结果统计");
+        for (tool, outcomes) in &self.tool_calls {
+            for (outcome, count) in outcomes {
+                let _ = writeln!(out, "cunzhi_tool_calls_total{{tool=\"{}\",outcome=\"{}\"}} {}", escape_label(tool), escape_label(outcome), count);
+            }
+        }
+
+        write_header(&mut out, "cunzhi_zhi_wait_seconds", "histogram", "zhi 等待用户回复的时长，按实际使用的交互方式统计");
+        for (backend, histogram) in &self.zhi_waits {
+            let backend = escape_label(backend);
+            let mut cumulative = 0;
+            for (index, bound) in WAIT_BUCKETS.iter().enumerate() {
+                cumulative += histogram.buckets.get(index).copied().unwrap_or(0);
+                let _ = writeln!(out, "cunzhi_zhi_wait_seconds_bucket{{backend=\"{}\",le=\"{}\"}} {}", backend, bound, cumulative);
+            }
+            let _ = writeln!(out, "cunzhi_zhi_wait_seconds_bucket{{backend=\"{}\",le=\"+Inf\"}} {}", backend, histogram.count);
+            let _ = writeln!(out, "cunzhi_zhi_wait_seconds_sum{{backend=\"{}\"}} {}", backend, histogram.sum);
+            let _ = writeln!(out, "cunzhi_zhi_wait_seconds_count{{backend=\"{}\"}} {}", backend, histogram.count);
+        }
+
+        write_header(&mut out, "cunzhi_zhi_fallbacks_total", "counter", "交互方式失败后回退到下一种方式的次数");
+        for (backend, count) in &self.zhi_fallbacks {
+            let _ = writeln!(out, "cunzhi_zhi_fallbacks_total{{backend=\"{}\"}} {}", escape_label(backend), count);
+        }
+
+        write_header(&mut out, "cunzhi_memory_writes_total", "counter", "记忆写入次数，按分类统计");
+        for (category, count) in &self.memory_writes {
+            let _ = writeln!(out, "cunzhi_memory_writes_total{{category=\"{}\"}} {}", escape_label(category), count);
+        }
+
+        write_header(&mut out, "cunzhi_active_sessions", "gauge", "活动的 MCP 会话数");
+        let _ = writeln!(out, "cunzhi_active_sessions {}", self.active_sessions);
+
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// 转义标签值中的反斜杠、引号和换行
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// 进程内的指标记录
+pub struct Metrics {
+    state: Mutex<MetricsSnapshot>,
+}
+
+/// 本进程的指标
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics { state: Mutex::new(MetricsSnapshot::default()) })
+}
+
+impl Metrics {
+    /// 记录一次工具调用
+    pub fn record_tool_call(&self, tool: &str, outcome: ToolOutcome) {
+        let mut state = self.state.lock().unwrap();
+        *state.tool_calls.entry(tool.to_string()).or_default()
+            .entry(outcome.as_str().to_string()).or_default() += 1;
+    }
+
+    /// 记录 `zhi` 通过某种交互方式获得回复前等待的时长
+    pub fn record_interaction(&self, backend: InteractionBackend, waited: Duration) {
+        self.state.lock().unwrap().zhi_waits
+            .entry(backend.as_str().to_string()).or_default()
+            .observe(waited.as_secs_f64());
+    }
+
+    /// 记录一次交互方式失败后的回退
+    pub fn record_fallback(&self, backend: InteractionBackend) {
+        *self.state.lock().unwrap().zhi_fallbacks.entry(backend.as_str().to_string()).or_default() += 1;
+    }
+
+    /// 记录一次记忆写入
+    pub fn record_memory_write(&self, category: MemoryCategory) {
+        *self.state.lock().unwrap().memory_writes.entry(category.as_str().to_string()).or_default() += 1;
+    }
+
+    /// 会话建立
+    pub fn session_opened(&self) {
+        self.state.lock().unwrap().active_sessions += 1;
+    }
+
+    /// 会话结束
+    pub fn sessions_closed(&self, count: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.active_sessions = state.active_sessions.saturating_sub(count as u64);
+    }
+
+    /// 当前指标的副本
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.state.lock().unwrap().clone()
+    }
+}
+
+/// 跟踪一次工具调用，在 `finish` 时记录结果
+///
+/// 未调用 `finish` 就被丢弃时（请求被取消）记为取消
+pub struct ToolCallTracker {
+    tool: String,
+    finished: bool,
+}
+
+impl ToolCallTracker {
+    pub fn start(tool: &str) -> Self {
+        Self { tool: tool.to_string(), finished: false }
+    }
+
+    pub fn finish(mut self, outcome: ToolOutcome) {
+        self.finished = true;
+        metrics().record_tool_call(&self.tool, outcome);
+    }
+}
+
+impl Drop for ToolCallTracker {
+    fn drop(&mut self) {
+        if !self.finished {
+            metrics().record_tool_call(&self.tool, ToolOutcome::Cancelled);
+        }
+    }
+}
@@ -1,14 +1,29 @@
+pub mod client;
 pub mod commands;
+pub mod completion;
 pub mod config;
+pub mod control;
+pub mod daemon;
+pub mod gateway;
+pub mod guard;
+pub mod live_config;
+pub mod metrics;
+pub mod prompts;
+pub mod recording;
 pub mod server;
+pub mod session;
 pub mod tools;
 pub mod types;
+pub mod unix_socket;
 pub mod handlers;
 pub mod utils;
 
 pub use commands::*;
 pub use config::*;
+pub use daemon::{DaemonPaths, DaemonState, RunningDaemon};
+pub use live_config::{ConfigChange, LiveConfig};
 pub use server::*;
+pub use session::*;
 pub use tools::*;
 pub use types::*;
 pub use handlers::*;
@@ -0,0 +1,102 @@
+// MCP 提示词
+//
+// 内置的 `continue` 提示词来自 reply_config.continue_prompt，
+// 其余来自 prompt_config.templates，同名模板会覆盖内置提示词
+use serde_json::{json, Map, Value};
+
+use crate::config::{AppConfig, PromptArgument, PromptTemplate};
+use crate::mcp::types::McpError;
+
+/// 内置继续提示词的名称
+pub const CONTINUE_PROMPT_NAME: &str = "continue";
+
+/// 当前配置下可用的提示词模板
+pub fn prompt_templates(config: &AppConfig) -> Vec<PromptTemplate> {
+    let mut templates = Vec::new();
+
+    if !config.prompt_config.templates.iter().any(|t| t.name == CONTINUE_PROMPT_NAME) {
+        templates.push(PromptTemplate {
+            name: CONTINUE_PROMPT_NAME.to_string(),
+            description: "继续回复提示词（reply_config.continue_prompt）".to_string(),
+            template: config.reply_config.continue_prompt.clone(),
+            arguments: Vec::new(),
+        });
+    }
+
+    templates.extend(config.prompt_config.templates.iter().cloned());
+    templates
+}
+
+/// `prompts/list` 的结果
+pub fn list_prompts(config: &AppConfig) -> Value {
+    let prompts: Vec<Value> = prompt_templates(config)
+        .iter()
+        .map(|template| {
+            let arguments: Vec<Value> = template.arguments.iter().map(argument_json).collect();
+            json!({
+                "name": template.name,
+                "description": template.description,
+                "arguments": arguments
+            })
+        })
+        .collect();
+
+    json!({ "prompts": prompts })
+}
+
+/// `prompts/get` 的结果
+pub fn get_prompt(config: &AppConfig, name: &str, arguments: &Value) -> Result<Value, McpError> {
+    let template = prompt_templates(config)
+        .into_iter()
+        .find(|template| template.name == name)
+        .ok_or_else(|| McpError::invalid_params(format!("未知的提示词: {}", name), None))?;
+
+    let empty = Map::new();
+    let provided = match arguments {
+        Value::Object(map) => map,
+        Value::Null => &empty,
+        _ => return Err(McpError::invalid_params("arguments 必须是对象".to_string(), None)),
+    };
+
+    let mut values = Vec::new();
+    for argument in &template.arguments {
+        let value = match provided.get(&argument.name) {
+            Some(Value::String(value)) => value.clone(),
+            Some(Value::Null) | None => match (&argument.default, argument.required) {
+                (Some(default), _) => default.clone(),
+                (None, true) => {
+                    return Err(McpError::invalid_params(format!("缺少必需参数: {}", argument.name), None));
+                }
+                (None, false) => String::new(),
+            },
+            Some(other) => other.to_string(),
+        };
+        values.push((argument.name.as_str(), value));
+    }
+
+    Ok(json!({
+        "description": template.description,
+        "messages": [{
+            "role": "user",
+            "content": {
+                "type": "text",
+                "text": render_template(&template.template, &values)
+            }
+        }]
+    }))
+}
+
+/// 把模板中的 `{{参数名}}` 替换为参数值
+pub fn render_template(template: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{{{}}}}}", name), value)
+    })
+}
+
+fn argument_json(argument: &PromptArgument) -> Value {
+    json!({
+        "name": argument.name,
+        "description": argument.description,
+        "required": argument.required
+    })
+}
@@ -0,0 +1,326 @@
+// MCP 通信录制与回放
+//
+// 录制文件为 JSONL，每行一帧：时间戳、会话、方向和原始 JSON-RPC 消息。
+// 回放时把录制的客户端消息依次交给 `handle_jsonrpc_request`，与录制的响应逐字段比较；
+// 交互式工具（`zhi`）不再弹窗，直接返回录制中的结果
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::server::ZhiServer;
+use super::session::{McpSession, RequestContext};
+use super::tools::{McpTool, ToolFuture};
+use super::types::{CallToolResult, McpError};
+use crate::log_important;
+
+/// 回放时使用录制结果的交互式工具
+const INTERACTIVE_TOOLS: &[&str] = &["zhi"];
+
+/// 消息方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// 客户端发往服务器
+    In,
+    /// 服务器发往客户端
+    Out,
+}
+
+/// 录制的一帧消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    pub timestamp: DateTime<Utc>,
+    /// 所属会话，同一文件可以包含多个会话
+    #[serde(default)]
+    pub session: String,
+    pub direction: Direction,
+    /// JSON-RPC 消息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<Value>,
+    /// 无法解析为 JSON 的原始行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<String>,
+}
+
+impl Frame {
+    /// 消息的原始文本
+    pub fn text(&self) -> String {
+        match (&self.message, &self.raw) {
+            (Some(message), _) => message.to_string(),
+            (None, Some(raw)) => raw.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// 通信录制器，可在多个会话间共享
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<LineWriter<File>>>,
+    session: String,
+}
+
+impl Recorder {
+    /// 以追加方式打开录制文件
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| anyhow::anyhow!("无法打开录制文件 {}: {}", path.display(), e))?;
+        Ok(Self { file: Arc::new(Mutex::new(LineWriter::new(file))), session: String::new() })
+    }
+
+    /// 绑定到指定会话的录制器
+    pub fn for_session(&self, session: &str) -> Self {
+        Self { file: self.file.clone(), session: session.to_string() }
+    }
+
+    /// 记录一行消息，写入失败只记录日志，不影响通信
+    pub fn record(&self, direction: Direction, line: &str) {
+        let (message, raw) = match serde_json::from_str::<Value>(line) {
+            Ok(message) => (Some(message), None),
+            Err(_) => (None, Some(line.to_string())),
+        };
+        let frame = Frame { timestamp: Utc::now(), session: self.session.clone(), direction, message, raw };
+
+        let written = serde_json::to_string(&frame)
+            .map_err(std::io::Error::other)
+            .and_then(|json| writeln!(self.file.lock().unwrap(), "{}", json));
+        if let Err(e) = written {
+            log_important!(warn, "写入录制文件失败: {}", e);
+        }
+    }
+}
+
+/// 读取录制文件
+pub fn load_recording(path: &Path) -> Result<Vec<Frame>> {
+    let file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("无法打开录制文件 {}: {}", path.display(), e))?;
+
+    let mut frames = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("录制文件第 {} 行格式错误: {}", index + 1, e))?;
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+/// 与录制不一致的响应
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub session: String,
+    pub id: Value,
+    pub method: String,
+    /// 每项描述一处差异
+    pub differences: Vec<String>,
+}
+
+/// 回放结果
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    /// 回放的请求数
+    pub requests: usize,
+    /// 与录制一致的响应数
+    pub matched: usize,
+    /// 录制中没有对应响应（如请求被取消）而未比较的响应数
+    pub skipped: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ReplayReport {
+    /// 所有响应都与录制一致
+    pub fn is_success(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// 回放录制的客户端消息，并与录制的服务器响应比较
+///
+/// 每个录制会话对应一个新会话；请求按录制顺序逐个处理
+pub async fn replay(mut server: ZhiServer, frames: &[Frame]) -> ReplayReport {
+    // 录制的响应，按会话和请求 ID 索引
+    let mut recorded: HashMap<(String, String), VecDeque<Value>> = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.direction == Direction::Out) {
+        for message in frame.message.iter().flat_map(messages) {
+            if is_response(message) {
+                recorded.entry((frame.session.clone(), message["id"].to_string()))
+                    .or_default()
+                    .push_back(message.clone());
+            }
+        }
+    }
+
+    // 交互式工具的录制结果
+    let mut interactions: HashMap<&'static str, Vec<(Value, Value)>> = HashMap::new();
+    let mut methods = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.direction == Direction::In) {
+        for message in frame.message.iter().flat_map(messages) {
+            let Some(method) = message["method"].as_str() else { continue };
+            let key = (frame.session.clone(), message["id"].to_string());
+            methods.insert(key.clone(), method.to_string());
+
+            if method != "tools/call" {
+                continue;
+            }
+            let Some(tool) = message["params"]["name"].as_str().and_then(|name| server.tools().find(name)) else {
+                continue;
+            };
+            let result = recorded.get(&key).and_then(|responses| responses.front()).map(|response| &response["result"]);
+            if let Some(result) = result.filter(|result| !result.is_null()) {
+                if let Some(&name) = INTERACTIVE_TOOLS.iter().find(|name| **name == tool.name()) {
+                    interactions.entry(name).or_default()
+                        .push((message["params"]["arguments"].clone(), result.clone()));
+                }
+            }
+        }
+    }
+
+    for name in INTERACTIVE_TOOLS {
+        if let Some(inner) = server.tools().find(name).cloned() {
+            let results = interactions.remove(name).unwrap_or_default();
+            server.register_tool(RecordedTool { inner, results: Mutex::new(results) });
+        }
+    }
+
+    let mut report = ReplayReport::default();
+    let mut contexts: HashMap<String, RequestContext> = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.direction == Direction::In) {
+        // 客户端对服务器请求的应答已随工具结果一并录制，不再回放
+        if frame.message.as_ref().is_some_and(is_response) {
+            continue;
+        }
+
+        let ctx = contexts.entry(frame.session.clone())
+            .or_insert_with(|| RequestContext::new(Arc::new(McpSession::new("replay")), None));
+        let Some(response) = server.handle_jsonrpc_request(&frame.text(), ctx).await else {
+            continue;
+        };
+        let response: Value = serde_json::from_str(&response).unwrap_or(Value::Null);
+
+        for actual in messages(&response) {
+            report.requests += 1;
+            let key = (frame.session.clone(), actual["id"].to_string());
+            let Some(expected) = recorded.get_mut(&key).and_then(VecDeque::pop_front) else {
+                report.skipped += 1;
+                continue;
+            };
+
+            let mut differences = Vec::new();
+            diff_values("", &expected, actual, &mut differences);
+            if differences.is_empty() {
+                report.matched += 1;
+            } else {
+                report.mismatches.push(Mismatch {
+                    session: frame.session.clone(),
+                    id: actual["id"].clone(),
+                    method: methods.get(&key).cloned().unwrap_or_default(),
+                    differences,
+                });
+            }
+        }
+    }
+    report
+}
+
+/// 单条消息或批量消息中的每一条
+fn messages(message: &Value) -> impl Iterator<Item = &Value> {
+    match message {
+        Value::Array(batch) => batch.iter().collect::<Vec<_>>().into_iter(),
+        message => vec![message].into_iter(),
+    }
+}
+
+/// 是否为 JSON-RPC 响应
+fn is_response(message: &Value) -> bool {
+    message.get("method").is_none() && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+/// 比较两个 JSON 值，把差异以「路径: 期望 …，实际 …」的形式追加到 `out`
+pub fn diff_values(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    let at = if path.is_empty() { "/" } else { path };
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected_value) in expected {
+                let child = format!("{}/{}", path, key);
+                match actual.get(key) {
+                    Some(actual_value) => diff_values(&child, expected_value, actual_value, out),
+                    None => out.push(format!("{}: 缺少字段", child)),
+                }
+            }
+            for key in actual.keys().filter(|key| !expected.contains_key(*key)) {
+                out.push(format!("{}/{}: 多出字段", path, key));
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            if expected.len() != actual.len() {
+                out.push(format!("{}: 期望 {} 个元素，实际 {} 个", at, expected.len(), actual.len()));
+            }
+            for (index, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                diff_values(&format!("{}/{}", path, index), expected, actual, out);
+            }
+        }
+        (expected, actual) if expected != actual => {
+            out.push(format!("{}: 期望 {}，实际 {}", at, expected, actual));
+        }
+        _ => {}
+    }
+}
+
+/// 回放时代替交互式工具，按参数返回录制的结果
+struct RecordedTool {
+    inner: Arc<dyn McpTool>,
+    /// 录制的（参数，结果），按调用顺序排列
+    results: Mutex<Vec<(Value, Value)>>,
+}
+
+impl McpTool for RecordedTool {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        self.inner.aliases()
+    }
+
+    fn can_disable(&self) -> bool {
+        self.inner.can_disable()
+    }
+
+    fn input_schema(&self) -> Value {
+        self.inner.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        self.inner.output_schema()
+    }
+
+    fn call<'a>(&'a self, arguments: Value, _ctx: &'a RequestContext) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let mut results = self.results.lock().unwrap();
+            let index = results.iter().position(|(recorded, _)| *recorded == arguments)
+                .ok_or_else(|| McpError::internal_error(
+                    format!("录制中没有与参数匹配的 {} 交互结果", self.name()), None,
+                ))?;
+            let (_, result) = results.remove(index);
+            serde_json::from_value::<CallToolResult>(result)
+                .map_err(|e| McpError::internal_error(format!("录制的 {} 结果格式错误: {}", self.name(), e), None))
+        })
+    }
+}
@@ -1,11 +1,36 @@
 // MCP 服务器实现 - 简化版本
 use anyhow::Result;
-use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use http_body_util::combinators::BoxBody;
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Frame};
 use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
 
-use super::tools::{InteractionTool, MemoryTool};
-use super::types::{ZhiRequest, JiyiRequest, McpError, CallToolResult};
-use crate::config::load_standalone_config;
+use super::session::{McpSession, RequestContext, SessionManager};
+use super::tools::{builtin_tools, validate_arguments, McpTool, ToolRegistry};
+use super::gateway::Gateway;
+use super::tools::memory::resources::{self, MemoryUri, MEMORY_URI_SCHEME};
+use super::tools::memory::{known_projects, subscribe_memory_events};
+use super::types::{McpError, CallToolResult};
+use super::prompts;
+use super::completion;
+use super::live_config::{ConfigChange, LiveConfig};
+use super::control::start_control_socket;
+use super::metrics::{metrics, ToolCallTracker, ToolOutcome};
+use super::unix_socket::bind_unix_socket;
+use super::recording::{Direction, Recorder};
+use crate::config::{load_or_create_http_token, AppConfig, HttpConfig};
+use crate::utils::{add_log_forwarder, set_forward_level, LogForwarderGuard};
 use crate::{log_important, log_debug};
 
 /// 服务器运行模式
@@ -15,12 +40,40 @@ pub enum ServerMode {
     Stdio,
     /// HTTP 服务器模式
     Http { port: u16 },
+    /// Unix 域套接字模式，协议与 stdio 相同
+    Unix { path: PathBuf },
 }
 
+/// 服务器支持的 MCP 协议版本（按新到旧排列）
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
+/// HTTP 传输中携带会话 ID 的请求/响应头
+pub const MCP_SESSION_HEADER: &str = "Mcp-Session-Id";
+
+/// SSE 流的保活间隔，避免代理因长时间无数据而断开连接
+const SSE_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 关闭时等待进行中连接完成的最长时间
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// 清理空闲 HTTP 会话的间隔
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// HTTP 响应体类型
+type HttpBody = BoxBody<Bytes, Infallible>;
+
 /// 简化的MCP服务器
 #[derive(Clone)]
 pub struct ZhiServer {
-    enabled_tools: HashMap<String, bool>,
+    config: LiveConfig,
+    tools: ToolRegistry,
+    sessions: SessionManager,
+    /// 按行传输的通信录制（`mcp-server --record`）
+    recorder: Option<Recorder>,
+    /// HTTP 传输要求的访问令牌，None 表示不认证
+    http_token: Option<Arc<str>>,
+    /// 代理的下游 MCP 服务器
+    gateway: Gateway,
 }
 
 impl Default for ZhiServer {
@@ -31,55 +84,79 @@ impl Default for ZhiServer {
 
 impl ZhiServer {
     pub fn new() -> Self {
-        // 从配置加载启用的工具
-        let enabled_tools = match load_standalone_config() {
-            Ok(config) => config.mcp_config.tools,
-            Err(e) => {
-                log_important!(warn, "无法加载配置文件，使用默认工具配置: {}", e);
-                let mut tools = HashMap::new();
-                tools.insert("zhi".to_string(), true);
-                tools.insert("ji".to_string(), true);
-                tools
-            }
-        };
+        Self::with_config(LiveConfig::load())
+    }
 
+    /// 使用指定的配置创建服务器
+    pub fn with_config(config: LiveConfig) -> Self {
         Self {
-            enabled_tools,
+            config,
+            tools: builtin_tools(),
+            sessions: SessionManager::new(),
+            recorder: None,
+            http_token: None,
+            gateway: Gateway::default(),
+        }
+    }
+
+    /// 注册额外的工具，同名工具会替换内置实现
+    pub fn register_tool(&mut self, tool: impl McpTool + 'static) {
+        self.tools.register(tool);
+    }
+
+    /// 把按行传输的所有收发消息录制到指定录制器
+    pub fn record_to(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// 要求 HTTP 请求携带 `Authorization: Bearer <令牌>`，None 表示不认证
+    pub fn with_http_token(mut self, token: Option<String>) -> Self {
+        self.http_token = token.map(Arc::from);
+        self
+    }
+
+    /// 代理指定的下游服务器：注册它们的工具，并导出它们的资源和提示词
+    pub fn with_gateway(mut self, gateway: Gateway) -> Self {
+        for tool in gateway.tools() {
+            self.tools.register(tool);
+        }
+        self.gateway = gateway;
+        self
+    }
+
+    /// 连接配置中的下游服务器（`gateway_config.servers`），未配置时原样返回
+    pub async fn connect_gateway(self) -> Self {
+        let servers = self.config.read(|config| config.gateway_config.servers.clone());
+        if servers.is_empty() {
+            return self;
         }
+        let gateway = Gateway::connect(&servers).await;
+        self.with_gateway(gateway)
+    }
+
+    /// 已注册的工具
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// 当前活动的会话
+    pub fn sessions(&self) -> &SessionManager {
+        &self.sessions
+    }
+
+    /// 服务器使用的配置，配置文件变化时自动更新
+    pub fn config(&self) -> &LiveConfig {
+        &self.config
     }
 
     /// 检查工具是否启用
     pub fn is_tool_enabled(&self, tool_name: &str) -> bool {
-        // 动态读取最新配置
-        match load_standalone_config() {
-            Ok(config) => {
-                let enabled = config.mcp_config.tools.get(tool_name).copied().unwrap_or(true);
-                log_debug!("工具 {} 当前状态: {}", tool_name, enabled);
-                enabled
-            }
-            Err(e) => {
-                log_important!(warn, "读取配置失败，使用缓存状态: {}", e);
-                self.enabled_tools.get(tool_name).copied().unwrap_or(true)
-            }
-        }
+        self.config.read(|config| config.mcp_config.tools.get(tool_name).copied().unwrap_or(true))
     }
 
     /// 获取启用的工具列表
     pub fn get_enabled_tools(&self) -> Vec<String> {
-        match load_standalone_config() {
-            Ok(config) => {
-                config.mcp_config.tools
-                    .iter()
-                    .filter_map(|(name, &enabled)| if enabled { Some(name.clone()) } else { None })
-                    .collect()
-            }
-            Err(_) => {
-                self.enabled_tools
-                    .iter()
-                    .filter_map(|(name, &enabled)| if enabled { Some(name.clone()) } else { None })
-                    .collect()
-            }
-        }
+        self.config.read(|config| config.mcp_config.get_enabled_tools())
     }
 
     /// 启动MCP协议服务器
@@ -98,14 +175,12 @@ impl ZhiServer {
         }
 
         log_important!(info, "启用的工具: {:?}", enabled_tools);
+        // 供 `cunzhi pending / answer / cancel` 处理等待中的交互
+        let _control = start_control_socket();
         log_important!(info, "MCP 服务器已启动，等待连接...");
         log_important!(info, "可用工具:");
-        for tool in &enabled_tools {
-            match tool.as_str() {
-                "zhi" => log_important!(info, "  ✅ zhi - 智能代码审查工具"),
-                "ji" => log_important!(info, "  ✅ ji - 记忆管理工具"),
-                _ => log_important!(info, "  ❓ {} - 未知工具", tool),
-            }
+        for tool in self.list_tools().iter().filter(|tool| tool.enabled) {
+            log_important!(info, "  ✅ {} - {}", tool.name, tool.description);
         }
 
         match mode {
@@ -114,238 +189,731 @@ impl ZhiServer {
                 self.run_mcp_protocol().await
             }
             ServerMode::Http { port } => {
-                log_important!(info, "HTTP 模式暂未实现，使用 stdio 模式，端口: {}", port);
-                self.run_mcp_protocol().await
+                let (listener, token) = bind_http(&self.current_config().http_config, port).await?;
+                log_important!(info, "使用 HTTP 模式，监听地址: http://{}/mcp", listener.local_addr()?);
+                self.clone().with_http_token(token).serve_http(listener, shutdown_signal()).await
+            }
+            ServerMode::Unix { path } => {
+                let listener = bind_unix_socket(&path)?;
+                log_important!(info, "使用 Unix 域套接字模式，监听: {}", path.display());
+                self.serve_unix(listener, shutdown_signal()).await
+            }
+        }
+    }
+
+    /// 在已绑定的监听器上提供 HTTP 服务，直到 `shutdown` 完成
+    ///
+    /// 每个连接在独立任务中处理；收到关闭信号后停止接受新连接，并等待已有连接处理完毕
+    pub async fn serve_http<F>(&self, listener: TcpListener, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        use hyper::server::conn::http1;
+        use hyper::service::service_fn;
+        use hyper_util::rt::TokioIo;
+        use hyper_util::server::graceful::GracefulShutdown;
+
+        let graceful = GracefulShutdown::new();
+        tokio::pin!(shutdown);
+        let forwarders = self.forward_notifications("http");
+        let session_sweeper = self.sweep_idle_sessions();
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            log_important!(warn, "接受 HTTP 连接失败: {}", e);
+                            continue;
+                        }
+                    };
+                    log_debug!("新的 HTTP 连接: {}", peer);
+
+                    let server = self.clone();
+                    let service = service_fn(move |req| {
+                        let server = server.clone();
+                        async move { server.handle_http_request(req).await }
+                    });
+                    let conn = http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+                    let conn = graceful.watch(conn);
+
+                    tokio::spawn(async move {
+                        if let Err(e) = conn.await {
+                            log_debug!("HTTP 连接 {} 异常结束: {}", peer, e);
+                        }
+                    });
+                }
+                _ = &mut shutdown => {
+                    log_important!(info, "收到关闭信号，停止接受新的 HTTP 连接");
+                    break;
+                }
             }
         }
+
+        drop(listener);
+        drop(forwarders);
+        session_sweeper.abort();
+        // 结束所有推送流，否则长连接会一直阻塞关闭
+        self.sessions.close_all();
+        if tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, graceful.shutdown()).await.is_err() {
+            log_important!(warn, "仍有请求未完成，强制关闭 HTTP 服务器");
+        }
+        log_important!(info, "HTTP 服务器已关闭");
+        Ok(())
+    }
+
+    /// 定期移除空闲的 HTTP 会话，客户端断开而没有发送 DELETE 时会话不会一直保留
+    fn sweep_idle_sessions(&self) -> tokio::task::JoinHandle<()> {
+        let sessions = self.sessions.clone();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let timeout = config.get().http_config.session_idle_timeout_seconds;
+                if timeout == 0 {
+                    continue;
+                }
+                let expired = sessions.expire_idle("http", Duration::from_secs(timeout));
+                if expired > 0 {
+                    refresh_forward_level(&sessions);
+                    log_important!(info, "已移除 {} 个空闲的 HTTP 会话", expired);
+                }
+            }
+        })
     }
 
     /// 运行MCP协议服务器
     async fn run_mcp_protocol(&self) -> Result<()> {
-        use std::io::{self, BufRead, Write};
+        self.serve_lines(tokio::io::stdin(), tokio::io::stdout(), "stdio").await
+    }
 
-        let stdin = io::stdin();
-        let mut stdout = io::stdout();
+    /// 在一对异步读写流上运行按行分隔的 JSON-RPC 协议
+    ///
+    /// 每个请求在独立任务中并发处理，等待用户回复的 `zhi` 调用不会阻塞其它请求；
+    /// 所有输出经由同一个写入任务逐行写出，保证消息不会交错。
+    /// 读到 EOF 后等待进行中的请求完成再返回
+    pub async fn serve_lines<R, W>(&self, reader: R, writer: W, transport: &'static str) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let _forwarders = self.forward_notifications(transport);
+        self.serve_connection(reader, writer, transport).await
+    }
+
+    /// 处理一个按行分隔的 JSON-RPC 连接
+    ///
+    /// 日志、记忆和配置变更的转发由调用方为所有连接统一登记（见 `serve_lines` 与 `serve_unix`），
+    /// 连接结束或处理任务被中止时移除会话
+    pub async fn serve_connection<R, W>(&self, reader: R, writer: W, transport: &'static str) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        // 一个连接只有一个客户端，整个生命周期共用一个会话
+        let session = self.sessions.create(transport);
+        let _session_guard = SessionGuard { sessions: self.sessions.clone(), id: session.id().to_string() };
+        let ctx = RequestContext::new(session.clone(), None);
+        let recorder = self.recorder.as_ref().map(|recorder| recorder.for_session(session.id()));
+
+        let (responses, response_rx) = mpsc::unbounded_channel::<String>();
+        let (push, push_rx) = mpsc::unbounded_channel::<Value>();
+        session.attach_push(push);
+        let writer_task = tokio::spawn(write_lines(writer, response_rx, push_rx, recorder.clone()));
 
-        // 读取stdin的每一行作为JSON-RPC请求
-        for line in stdin.lock().lines() {
-            let line = line?;
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
             if line.trim().is_empty() {
                 continue;
             }
+            if let Some(recorder) = &recorder {
+                recorder.record(Direction::In, &line);
+            }
 
-            // 解析JSON-RPC请求
-            match self.handle_jsonrpc_request(&line).await {
-                Ok(response) => {
-                    // 发送响应
-                    writeln!(stdout, "{}", response)?;
-                    stdout.flush()?;
+            let message: Option<Value> = serde_json::from_str(&line).ok();
+            let server = self.clone();
+            let task_ctx = ctx.clone();
+            let task_responses = responses.clone();
+            let task = async move {
+                // 通知不需要回复
+                if let Some(response) = server.handle_jsonrpc_request(&line, &task_ctx).await {
+                    let _ = task_responses.send(response);
                 }
-                Err(e) => {
-                    // 发送错误响应
-                    let error_response = serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "error": {
-                            "code": -32000,
-                            "message": e.to_string()
-                        },
-                        "id": null
-                    });
-                    writeln!(stdout, "{}", error_response)?;
-                    stdout.flush()?;
+            };
+
+            match message {
+                // 请求并发处理，并登记以便客户端取消
+                Some(ref message) if request_id(message).is_some() => {
+                    session.spawn_request(request_id(message).unwrap(), task);
                 }
+                // 批量请求同样并发处理
+                Some(Value::Array(_)) => {
+                    tokio::spawn(task);
+                }
+                // 通知（包括取消通知）和格式错误的消息按到达顺序立即处理
+                _ => task.await,
             }
         }
 
+        log_debug!("{} 输入流已关闭，等待进行中的请求完成", transport);
+        drop(responses);
+        if let Err(e) = writer_task.await {
+            log_important!(warn, "输出任务异常结束: {}", e);
+        }
         Ok(())
     }
 
-
-
     /// 处理HTTP请求
-    async fn handle_http_request(&self, req: hyper::Request<hyper::body::Incoming>) -> Result<hyper::Response<http_body_util::Full<hyper::body::Bytes>>, std::convert::Infallible> {
-        use hyper::{Method, StatusCode, Response};
-        use hyper::body::Bytes;
-        use http_body_util::{BodyExt, Full};
-        match (req.method(), req.uri().path()) {
-            (&Method::POST, "/mcp") => {
-                // 处理 MCP JSON-RPC 请求
-                let body = match req.collect().await {
-                    Ok(collected) => collected.to_bytes(),
-                    Err(_) => {
-                        return Ok(Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .body(Full::new(Bytes::from("Failed to read request body")))
-                            .unwrap());
-                    }
-                };
+    ///
+    /// 实现 MCP Streamable HTTP 传输：
+    /// - `POST /mcp` 发送 JSON-RPC 消息，客户端接受 `text/event-stream` 时以 SSE 流返回
+    /// - `GET /mcp` 打开服务器到客户端的 SSE 推送流
+    /// - `DELETE /mcp` 结束会话
+    async fn handle_http_request(&self, req: hyper::Request<hyper::body::Incoming>) -> Result<hyper::Response<HttpBody>, std::convert::Infallible> {
+        use hyper::{Method, StatusCode};
 
-                let request_str = match String::from_utf8(body.to_vec()) {
-                    Ok(s) => s,
-                    Err(_) => {
-                        return Ok(Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .body(Full::new(Bytes::from("Invalid UTF-8 in request body")))
-                            .unwrap());
-                    }
-                };
+        let http_config = self.config.read(|config| config.http_config.clone());
 
-                match self.handle_jsonrpc_request(&request_str).await {
-                    Ok(response) => {
-                        Ok(Response::builder()
-                            .status(StatusCode::OK)
-                            .header("Content-Type", "application/json")
-                            .header("Access-Control-Allow-Origin", "*")
-                            .header("Access-Control-Allow-Methods", "POST, OPTIONS")
-                            .header("Access-Control-Allow-Headers", "Content-Type")
-                            .body(Full::new(Bytes::from(response)))
-                            .unwrap())
-                    }
-                    Err(e) => {
-                        let error_response = serde_json::json!({
-                            "jsonrpc": "2.0",
-                            "error": {
-                                "code": -32000,
-                                "message": e.to_string()
-                            },
-                            "id": null
-                        });
-                        Ok(Response::builder()
-                            .status(StatusCode::INTERNAL_SERVER_ERROR)
-                            .header("Content-Type", "application/json")
-                            .body(Full::new(Bytes::from(error_response.to_string())))
-                            .unwrap())
-                    }
+        // 浏览器发起的请求都带 Origin，不在允许列表中的网页不能访问服务器
+        let origin = header_value(&req, "Origin");
+        if let Some(origin) = &origin {
+            if !http_config.allowed_origins.contains(origin) {
+                log_important!(warn, "拒绝来自 {} 的 HTTP 请求：来源不在允许列表中", origin);
+                return Ok(text_response(StatusCode::FORBIDDEN, "Origin not allowed"));
+            }
+        }
+
+        // CORS 预检请求不携带认证信息
+        let needs_token = req.uri().path() == "/mcp" && req.method() != Method::OPTIONS;
+        if let Some(token) = self.http_token.as_deref().filter(|_| needs_token) {
+            if !has_bearer_token(&req, token) {
+                log_debug!("拒绝未认证的 HTTP 请求: {} {}", req.method(), req.uri().path());
+                let mut response = text_response(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token");
+                response.headers_mut().insert("WWW-Authenticate", hyper::header::HeaderValue::from_static("Bearer"));
+                if let Some(origin) = &origin {
+                    add_cors_headers(&mut response, origin);
                 }
+                return Ok(response);
             }
+        }
+
+        let mut response = match (req.method(), req.uri().path()) {
+            (&Method::POST, "/mcp") => self.handle_mcp_post(req, http_config.max_body_bytes).await,
+            (&Method::GET, "/mcp") => self.handle_mcp_get(&req),
+            (&Method::DELETE, "/mcp") => self.handle_mcp_delete(&req),
             (&Method::OPTIONS, "/mcp") => {
                 // 处理 CORS 预检请求
-                Ok(Response::builder()
-                    .status(StatusCode::OK)
-                    .header("Access-Control-Allow-Origin", "*")
-                    .header("Access-Control-Allow-Methods", "POST, OPTIONS")
-                    .header("Access-Control-Allow-Headers", "Content-Type")
-                    .body(Full::new(Bytes::new()))
-                    .unwrap())
+                hyper::Response::builder().status(StatusCode::OK)
+                    .body(full_body(""))
+                    .unwrap()
             }
             (&Method::GET, "/health") => {
                 // 健康检查端点
                 let health_info = serde_json::json!({
                     "status": "healthy",
                     "server": "cunzhi-cli MCP Server",
-                    "version": "0.2.12",
-                    "tools": self.get_enabled_tools()
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "tools": self.get_enabled_tools(),
+                    "sessions": self.sessions.count()
                 });
-                Ok(Response::builder()
-                    .status(StatusCode::OK)
-                    .header("Content-Type", "application/json")
-                    .body(Full::new(Bytes::from(health_info.to_string())))
-                    .unwrap())
+                json_response(StatusCode::OK, health_info.to_string())
             }
-            _ => {
-                Ok(Response::builder()
-                    .status(StatusCode::NOT_FOUND)
-                    .body(Full::new(Bytes::from("Not Found")))
-                    .unwrap())
+            (&Method::GET, "/metrics") => {
+                hyper::Response::builder().status(StatusCode::OK)
+                    .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+                    .body(full_body(metrics().snapshot().render_prometheus()))
+                    .unwrap()
+            }
+            _ => text_response(StatusCode::NOT_FOUND, "Not Found"),
+        };
+
+        if let Some(origin) = &origin {
+            add_cors_headers(&mut response, origin);
+        }
+        Ok(response)
+    }
+
+    /// 为指定传输方式的所有会话登记日志、记忆变更和配置变更的转发，并开始检查配置文件
+    ///
+    /// 每个服务只登记一次，同一条日志或变更不会因连接数而重复推送；返回值被丢弃时停止转发
+    pub fn forward_notifications(&self, transport: &'static str) -> NotificationForwarders {
+        let targets = {
+            let sessions = self.sessions.clone();
+            move || sessions.all().into_iter().filter(|session| session.transport() == transport).collect()
+        };
+        NotificationForwarders {
+            _logs: self.forward_logs(),
+            tasks: vec![
+                forward_memory_events(targets.clone()),
+                self.config.watch(),
+                forward_config_changes(&self.config, targets),
+            ],
+        }
+    }
+
+    /// 把日志记录以 `notifications/message` 推送给各会话，返回的守卫被丢弃时停止推送
+    fn forward_logs(&self) -> LogForwarderGuard {
+        let sessions = self.sessions.clone();
+        add_log_forwarder(Arc::new(move |level, logger, message| {
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": {
+                    "level": mcp_log_level(level),
+                    "logger": logger,
+                    "data": message
+                }
+            });
+            for session in sessions.all() {
+                if level <= session.log_level() {
+                    session.send(notification.clone());
+                }
+            }
+        }))
+    }
+
+    /// 处理 POST /mcp，请求体超过 `max_body_bytes` 时返回 413
+    async fn handle_mcp_post(&self, req: hyper::Request<hyper::body::Incoming>, max_body_bytes: usize) -> hyper::Response<HttpBody> {
+        use hyper::StatusCode;
+        use http_body_util::{BodyExt, LengthLimitError, Limited};
+
+        let wants_sse = accepts_event_stream(&req);
+        let session_header = header_value(&req, MCP_SESSION_HEADER);
+
+        let body = match Limited::new(req.into_body(), max_body_bytes).collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) if e.is::<LengthLimitError>() => {
+                log_important!(warn, "拒绝超过 {} 字节的 HTTP 请求体", max_body_bytes);
+                return text_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+            }
+            Err(_) => return text_response(StatusCode::BAD_REQUEST, "Failed to read request body"),
+        };
+
+        let request_str = match String::from_utf8(body.to_vec()) {
+            Ok(s) => s,
+            Err(_) => return text_response(StatusCode::BAD_REQUEST, "Invalid UTF-8 in request body"),
+        };
+
+        let message: Option<Value> = serde_json::from_str(&request_str).ok();
+        let is_initialize = message.as_ref()
+            .map(|m| m["method"] == "initialize")
+            .unwrap_or(false);
+        // 只有包含请求的消息才以 SSE 流返回；通知、客户端响应和格式错误的消息直接处理
+        let has_request = message.as_ref().map(contains_request).unwrap_or(false);
+
+        // 初始化请求创建新会话；其余请求必须携带已有会话的 ID
+        let session = if is_initialize {
+            self.sessions.create("http")
+        } else if let Some(id) = session_header {
+            match self.sessions.get(&id) {
+                Some(session) => session,
+                None => return text_response(StatusCode::NOT_FOUND, "Unknown MCP session"),
+            }
+        } else {
+            return text_response(StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header");
+        };
+        session.touch();
+
+        let session_id = is_initialize.then(|| session.id().to_string());
+
+        if wants_sse && has_request {
+            let (sink, mut notifications) = mpsc::unbounded_channel::<Value>();
+            let ctx = RequestContext::new(session, Some(sink));
+            let (events, body) = ChannelBody::new();
+            let server = self.clone();
+            let id = message.as_ref().and_then(request_id).cloned();
+            let session = ctx.session().clone();
+
+            let task = async move {
+                let call = server.handle_jsonrpc_request(&request_str, &ctx);
+                tokio::pin!(call);
+                let mut keepalive = tokio::time::interval(SSE_KEEPALIVE_INTERVAL);
+                keepalive.tick().await;
+
+                loop {
+                    tokio::select! {
+                        result = &mut call => {
+                            // 先把请求处理期间产生的通知发完，再发送最终响应
+                            while let Ok(message) = notifications.try_recv() {
+                                let _ = events.send(sse_event(&message.to_string()));
+                            }
+                            if let Some(response) = result {
+                                let _ = events.send(sse_event(&response));
+                            }
+                            break;
+                        }
+                        Some(message) = notifications.recv() => {
+                            if events.send(sse_event(&message.to_string())).is_err() {
+                                break;
+                            }
+                        }
+                        _ = keepalive.tick() => {
+                            // 客户端断开时放弃处理
+                            if events.send(Bytes::from_static(b": keepalive\n\n")).is_err() {
+                                log_debug!("SSE 客户端已断开，取消请求处理");
+                                break;
+                            }
+                        }
+                    }
+                }
+                ctx.session().touch();
+            };
+
+            // 单个请求登记到会话中，以便通过 notifications/cancelled 取消
+            match id {
+                Some(id) => {
+                    session.spawn_request(&id, task);
+                }
+                None => {
+                    tokio::spawn(task);
+                }
+            }
+
+            let mut builder = hyper::Response::builder().status(StatusCode::OK)
+                .header("Content-Type", "text/event-stream")
+                .header("Cache-Control", "no-cache");
+            if let Some(id) = session_id {
+                builder = builder.header(MCP_SESSION_HEADER, id);
+            }
+            return builder.body(body.boxed()).unwrap();
+        }
+
+        let ctx = RequestContext::new(session, None);
+        let response = self.handle_jsonrpc_request(&request_str, &ctx).await;
+        // 处理耗时较长的请求（如等待用户回复）结束时同样算作一次访问
+        ctx.session().touch();
+        let Some(response) = response else {
+            // 通知和客户端响应只需确认收到
+            return hyper::Response::builder().status(StatusCode::ACCEPTED)
+                .body(full_body(""))
+                .unwrap();
+        };
+
+        let mut builder = hyper::Response::builder().status(StatusCode::OK)
+            .header("Content-Type", "application/json");
+        if let Some(id) = session_id {
+            builder = builder.header(MCP_SESSION_HEADER, id);
+        }
+        builder.body(full_body(response)).unwrap()
+    }
+
+    /// 处理 GET /mcp，打开服务器推送的 SSE 流
+    fn handle_mcp_get(&self, req: &hyper::Request<hyper::body::Incoming>) -> hyper::Response<HttpBody> {
+        use hyper::StatusCode;
+
+        if !accepts_event_stream(req) {
+            return text_response(StatusCode::METHOD_NOT_ALLOWED, "GET /mcp requires Accept: text/event-stream");
+        }
+
+        let session = match header_value(req, MCP_SESSION_HEADER) {
+            Some(id) => match self.sessions.get(&id) {
+                Some(session) => session,
+                None => return text_response(StatusCode::NOT_FOUND, "Unknown MCP session"),
+            },
+            None => return text_response(StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"),
+        };
+        session.touch();
+
+        let (push, mut messages) = mpsc::unbounded_channel::<Value>();
+        session.attach_push(push);
+        log_debug!("会话 {} 打开推送流", session.id());
+
+        let (events, body) = ChannelBody::new();
+        tokio::spawn(async move {
+            let mut keepalive = tokio::time::interval(SSE_KEEPALIVE_INTERVAL);
+            keepalive.tick().await;
+
+            loop {
+                tokio::select! {
+                    message = messages.recv() => {
+                        let Some(message) = message else { break };
+                        if events.send(sse_event(&message.to_string())).is_err() {
+                            break;
+                        }
+                    }
+                    _ = keepalive.tick() => {
+                        if events.send(Bytes::from_static(b": keepalive\n\n")).is_err() {
+                            break;
+                        }
+                    }
+                }
             }
+        });
+
+        hyper::Response::builder().status(StatusCode::OK)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .body(body.boxed())
+            .unwrap()
+    }
+
+    /// 处理 DELETE /mcp，结束会话
+    fn handle_mcp_delete(&self, req: &hyper::Request<hyper::body::Incoming>) -> hyper::Response<HttpBody> {
+        use hyper::StatusCode;
+
+        match header_value(req, MCP_SESSION_HEADER) {
+            Some(id) if self.sessions.remove(&id) => {
+                refresh_forward_level(&self.sessions);
+                log_debug!("会话 {} 已结束", id);
+                hyper::Response::builder().status(StatusCode::OK)
+                    .body(full_body(""))
+                    .unwrap()
+            }
+            Some(_) => text_response(StatusCode::NOT_FOUND, "Unknown MCP session"),
+            None => text_response(StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"),
         }
     }
 
-    /// 处理JSON-RPC请求
-    async fn handle_jsonrpc_request(&self, request: &str) -> Result<String> {
+    /// 处理JSON-RPC消息
+    ///
+    /// 支持单条消息和批量数组。返回需要写回客户端的响应；
+    /// 通知（以及只包含通知的批量请求）不需要回复，返回 `None`
+    pub async fn handle_jsonrpc_request(&self, request: &str, ctx: &RequestContext) -> Option<String> {
         log_debug!("收到 JSON-RPC 请求: {}", request);
 
-        let req: Value = serde_json::from_str(request)
-            .map_err(|e| anyhow::anyhow!("JSON 解析失败: {}", e))?;
+        let message: Value = match serde_json::from_str(request) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = McpError::parse_error(format!("JSON 解析失败: {}", e), None);
+                return Some(error.to_response(&Value::Null).to_string());
+            }
+        };
 
-        let method = req["method"].as_str().unwrap_or("");
-        let params = &req["params"];
-        let id = &req["id"];
+        match message {
+            Value::Array(batch) => {
+                if batch.is_empty() {
+                    let error = McpError::invalid_request("批量请求不能为空".to_string(), None);
+                    return Some(error.to_response(&Value::Null).to_string());
+                }
 
-        log_debug!("处理方法: {}, ID: {:?}", method, id);
+                let mut responses = Vec::new();
+                for message in batch {
+                    if let Some(response) = self.handle_jsonrpc_message(message, ctx).await {
+                        responses.push(response);
+                    }
+                }
+
+                (!responses.is_empty()).then(|| Value::Array(responses).to_string())
+            }
+            message => self.handle_jsonrpc_message(message, ctx).await
+                .map(|response| response.to_string()),
+        }
+    }
+
+    /// 处理单条 JSON-RPC 消息
+    async fn handle_jsonrpc_message(&self, message: Value, ctx: &RequestContext) -> Option<Value> {
+        let Value::Object(message) = message else {
+            let error = McpError::invalid_request("消息必须是 JSON 对象".to_string(), None);
+            return Some(error.to_response(&Value::Null));
+        };
+
+        let id = message.get("id").cloned();
+        // 无法识别的 id 不能原样回显
+        let reply_id = id.clone()
+            .filter(|id| id.is_string() || id.is_number())
+            .unwrap_or(Value::Null);
+
+        if message.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+            let error = McpError::invalid_request("jsonrpc 字段必须为 \"2.0\"".to_string(), None);
+            return Some(error.to_response(&reply_id));
+        }
+
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            if let Some(id) = id.as_ref().filter(|_| message.contains_key("result") || message.contains_key("error")) {
+                // 客户端对服务器请求（如 elicitation/create）的响应
+                let reply = match message.get("error") {
+                    Some(error) => Err(error.clone()),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                if !ctx.session().resolve_response(id, reply) {
+                    log_debug!("忽略无人等待的客户端响应: {}", id);
+                }
+                return None;
+            }
+            let error = McpError::invalid_request("缺少 method 字段".to_string(), None);
+            return Some(error.to_response(&reply_id));
+        };
+
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let Some(id) = id else {
+            self.handle_notification(method, &params, ctx);
+            return None;
+        };
+
+        if !(id.is_string() || id.is_number()) {
+            let error = McpError::invalid_request("id 必须是字符串或数字".to_string(), None);
+            return Some(error.to_response(&Value::Null));
+        }
 
+        log_debug!("处理方法: {}, ID: {}", method, id);
+
+        match self.dispatch_request(method, &params, ctx).await {
+            Ok(result) => Some(serde_json::json!({
+                "jsonrpc": "2.0",
+                "result": result,
+                "id": id
+            })),
+            Err(e) => {
+                log_debug!("请求 {} 处理失败: {}", id, e);
+                Some(e.to_response(&id))
+            }
+        }
+    }
+
+    /// 处理客户端通知，通知不产生任何响应
+    fn handle_notification(&self, method: &str, params: &Value, ctx: &RequestContext) {
+        match method {
+            "notifications/initialized" => {
+                log_debug!("会话 {} 初始化完成", ctx.session().id());
+                if ctx.session().supports_roots() {
+                    spawn_roots_refresh(ctx);
+                }
+            }
+            "notifications/roots/list_changed" => {
+                log_debug!("会话 {} 的根目录已变化", ctx.session().id());
+                ctx.session().set_roots(None);
+                spawn_roots_refresh(ctx);
+            }
+            "notifications/cancelled" => {
+                let request_id = &params["requestId"];
+                if ctx.session().cancel_request(request_id) {
+                    log_important!(info, "客户端取消了请求 {}", request_id);
+                } else {
+                    log_debug!("要取消的请求 {} 不存在或已完成", request_id);
+                }
+            }
+            _ => {
+                log_debug!("忽略未知通知: {}", method);
+            }
+        }
+    }
+
+    /// 分发 JSON-RPC 请求
+    async fn dispatch_request(&self, method: &str, params: &Value, ctx: &RequestContext) -> Result<Value, McpError> {
         let result = match method {
             "initialize" => {
                 // MCP初始化
+                ctx.session().set_client_info(params.clone());
+
+                // 客户端请求的版本受支持时沿用，否则返回服务器支持的最新版本
+                let requested = params["protocolVersion"].as_str().unwrap_or("");
+                let protocol_version = SUPPORTED_PROTOCOL_VERSIONS.iter()
+                    .find(|v| **v == requested)
+                    .copied()
+                    .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0]);
+
                 serde_json::json!({
-                    "protocolVersion": "2024-11-05",
+                    "protocolVersion": protocol_version,
                     "capabilities": {
-                        "tools": {}
+                        "tools": {
+                            "listChanged": true
+                        },
+                        "prompts": {
+                            "listChanged": true
+                        },
+                        "logging": {},
+                        "completions": {},
+                        "resources": {
+                            "subscribe": true,
+                            "listChanged": true
+                        }
                     },
                     "serverInfo": {
                         "name": "cunzhi-cli",
-                        "version": "0.2.12"
+                        "version": env!("CARGO_PKG_VERSION")
                     }
                 })
             }
-            "tools/list" => {
-                // 列出工具
-                let tools = self.list_tools();
-                let tool_list: Vec<Value> = tools.into_iter().map(|tool| {
-                    let input_schema = match tool.name.as_str() {
-                        "zhi" | "zhi_cunzhi" => serde_json::json!({
-                            "type": "object",
-                            "properties": {
-                                "message": {
-                                    "type": "string",
-                                    "description": "要显示给用户的消息"
-                                },
-                                "predefined_options": {
-                                    "type": "array",
-                                    "items": {"type": "string"},
-                                    "description": "预定义的选项列表（可选）"
-                                },
-                                "is_markdown": {
-                                    "type": "boolean",
-                                    "description": "消息是否为Markdown格式，默认为true",
-                                    "default": true
-                                },
-                                "terminal_mode": {
-                                    "type": "boolean",
-                                    "description": "是否在新终端窗口中启动交互，默认为false",
-                                    "default": false
-                                }
-                            },
-                            "required": ["message"]
-                        }),
-                        "ji" | "ji_cunzhi" => serde_json::json!({
-                            "type": "object",
-                            "properties": {
-                                "action": {
-                                    "type": "string",
-                                    "description": "操作类型：记忆(添加记忆), 回忆(获取项目信息)",
-                                    "enum": ["记忆", "回忆"]
-                                },
-                                "project_path": {
-                                    "type": "string",
-                                    "description": "项目路径（必需）"
-                                },
-                                "content": {
-                                    "type": "string",
-                                    "description": "记忆内容（记忆操作时必需）"
-                                },
-                                "category": {
-                                    "type": "string",
-                                    "description": "记忆分类：rule(规范规则), preference(用户偏好), pattern(最佳实践), context(项目上下文)",
-                                    "enum": ["rule", "preference", "pattern", "context"],
-                                    "default": "context"
-                                }
-                            },
-                            "required": ["action", "project_path"]
-                        }),
-                        _ => serde_json::json!({
-                            "type": "object",
-                            "properties": {},
-                            "required": []
-                        })
-                    };
+            "ping" => serde_json::json!({}),
+            "logging/setLevel" => {
+                let level = params["level"].as_str()
+                    .and_then(parse_log_level)
+                    .ok_or_else(|| McpError::invalid_params(
+                        format!("无效的日志级别: {}", params["level"]),
+                        None,
+                    ))?;
+                ctx.session().set_log_level(level);
 
-                    serde_json::json!({
-                        "name": tool.name,
-                        "description": tool.description,
-                        "inputSchema": input_schema
+                // 转发级别取所有会话中最详细的一个
+                let forward = self.sessions.all().iter()
+                    .map(|session| session.log_level())
+                    .chain(std::iter::once(level))
+                    .max()
+                    .unwrap_or(level);
+                set_forward_level(forward);
+                log_debug!("会话 {} 日志级别设为 {}", ctx.session().id(), level);
+                serde_json::json!({})
+            }
+            "completion/complete" => self.complete(params, ctx.session())?,
+            "prompts/list" => {
+                let mut result = prompts::list_prompts(&self.current_config());
+                if let Some(list) = result["prompts"].as_array_mut() {
+                    list.extend(self.gateway.list_prompts());
+                }
+                result
+            }
+            "prompts/get" => {
+                let name = params["name"].as_str()
+                    .ok_or_else(|| McpError::invalid_params("缺少提示词名称 name".to_string(), None))?;
+                match self.gateway.get_prompt(name, &params["arguments"]).await {
+                    Some(result) => result?,
+                    None => prompts::get_prompt(&self.current_config(), name, &params["arguments"])?,
+                }
+            }
+            "resources/list" => {
+                let mut list = resources::list_resources(&self.resource_projects(ctx.session()));
+                list.extend(self.gateway.list_resources());
+                serde_json::json!({ "resources": list })
+            }
+            "resources/templates/list" => {
+                let mut list = resources::resource_templates();
+                list.extend(self.gateway.list_resource_templates());
+                serde_json::json!({ "resourceTemplates": list })
+            }
+            "resources/read" => {
+                let uri = required_uri(params)?;
+                let forwarded = match MemoryUri::parse(uri) {
+                    Some(_) => None,
+                    None => self.gateway.read_resource(uri).await,
+                };
+                match forwarded {
+                    Some(result) => result?,
+                    None => resources::read_resource(uri)?,
+                }
+            }
+            "resources/subscribe" => {
+                let uri = required_uri(params)?;
+                if MemoryUri::parse(uri).is_none() {
+                    return Err(McpError::invalid_params(format!("无法识别的资源 URI: {}", uri), None));
+                }
+                ctx.session().subscribe(uri);
+                log_debug!("会话 {} 订阅资源 {}", ctx.session().id(), uri);
+                serde_json::json!({})
+            }
+            "resources/unsubscribe" => {
+                ctx.session().unsubscribe(required_uri(params)?);
+                serde_json::json!({})
+            }
+            "tools/list" => {
+                // 列出已启用的工具（包括兼容别名）
+                let tool_list: Vec<Value> = self.list_tools().into_iter()
+                    .filter(|tool| tool.enabled)
+                    .map(|tool| {
+                        let mut info = serde_json::json!({
+                            "name": tool.name,
+                            "description": tool.description,
+                            "inputSchema": tool.input_schema
+                        });
+                        if let Some(output_schema) = tool.output_schema {
+                            info["outputSchema"] = output_schema;
+                        }
+                        info
                     })
-                }).collect();
+                    .collect();
 
                 serde_json::json!({
                     "tools": tool_list
@@ -353,134 +921,220 @@ impl ZhiServer {
             }
             "tools/call" => {
                 // 调用工具
-                let tool_name = params["name"].as_str().unwrap_or("");
-                let arguments = params["arguments"].clone();
-
-                match self.call_tool(tool_name, arguments).await {
-                    Ok(result) => {
-                        serde_json::json!({
-                            "content": result.content
-                        })
-                    }
-                    Err(e) => {
-                        return Err(anyhow::anyhow!("工具调用失败: {}", e));
-                    }
-                }
+                let tool_name = params["name"].as_str()
+                    .ok_or_else(|| McpError::invalid_params("缺少工具名称 name".to_string(), None))?;
+                // 省略 arguments 等同于空对象
+                let arguments = match &params["arguments"] {
+                    Value::Null => serde_json::json!({}),
+                    arguments => arguments.clone(),
+                };
+
+                let ctx = ctx
+                    .clone()
+                    .with_progress_token(params["_meta"].get("progressToken").cloned())
+                    .with_config(self.config.get());
+                let result = self.call_tool_with_context(tool_name, arguments, &ctx).await?;
+                serde_json::to_value(result)
+                    .map_err(|e| McpError::internal_error(format!("序列化工具结果失败: {}", e), None))?
             }
             _ => {
-                return Err(anyhow::anyhow!("未知方法: {}", method));
+                return Err(McpError::method_not_found(format!("未知方法: {}", method), None));
             }
         };
 
-        // 构建JSON-RPC响应
-        let response = serde_json::json!({
-            "jsonrpc": "2.0",
-            "result": result,
-            "id": id
-        });
-
-        Ok(response.to_string())
+        Ok(result)
     }
 
     /// 处理工具调用请求
     pub async fn call_tool(&self, tool_name: &str, arguments: Value) -> Result<CallToolResult, McpError> {
-        log_debug!("收到工具调用请求: {}", tool_name);
+        self.call_tool_with_context(tool_name, arguments, &RequestContext::detached()).await
+    }
 
-        // 工具名称映射 - 支持 Augment 环境中的工具名称
-        let normalized_tool_name = match tool_name {
-            "zhi_cunzhi" => "zhi",
-            "ji_cunzhi" => "ji",
-            name => name,
-        };
+    /// 在指定请求上下文中调用工具
+    ///
+    /// 未知工具返回 JSON-RPC 错误；参数校验失败和工具执行失败以 `isError` 结果返回，
+    /// 便于调用方读取错误原因后修正重试
+    pub async fn call_tool_with_context(&self, tool_name: &str, arguments: Value, ctx: &RequestContext) -> Result<CallToolResult, McpError> {
+        log_debug!("收到工具调用请求: {}", tool_name);
 
-        log_debug!("标准化工具名称: {} -> {}", tool_name, normalized_tool_name);
+        // 工具名称和兼容别名（如 Augment 环境中的 zhi_cunzhi）都可以命中
+        let tool = self.tools.find(tool_name)
+            .ok_or_else(|| McpError::invalid_params(format!("未知的工具: {}", tool_name), None))?;
 
-        match normalized_tool_name {
-            "zhi" => {
-                let zhi_request: ZhiRequest = serde_json::from_value(arguments)
-                    .map_err(|e| McpError::invalid_params(format!("参数解析失败: {}", e), None))?;
+        log_debug!("标准化工具名称: {} -> {}", tool_name, tool.name());
 
-                InteractionTool::zhi(zhi_request).await
-            }
-            "ji" => {
-                // 检查记忆管理工具是否启用
-                if !self.is_tool_enabled("ji") {
-                    return Err(McpError::internal_error(
-                        "记忆管理工具已被禁用".to_string(),
-                        None
-                    ));
-                }
+        if !self.is_registered_tool_enabled(tool.as_ref()) {
+            metrics().record_tool_call(tool.name(), ToolOutcome::Disabled);
+            return Ok(CallToolResult::error(format!("工具 {} 已被禁用", tool.name())));
+        }
 
-                let ji_request: JiyiRequest = serde_json::from_value(arguments)
-                    .map_err(|e| McpError::invalid_params(format!("参数解析失败: {}", e), None))?;
+        if let Err(e) = validate_arguments(&tool.input_schema(), &arguments) {
+            log_debug!("工具 {} 参数校验失败: {}", tool.name(), e);
+            metrics().record_tool_call(tool.name(), ToolOutcome::InvalidArguments);
+            return Ok(CallToolResult::error(format!("参数校验失败: {}", e)));
+        }
 
-                MemoryTool::jiyi(ji_request).await
+        let tracker = ToolCallTracker::start(tool.name());
+        match tool.call(arguments, ctx).await {
+            Ok(result) => {
+                let outcome = if result.is_error == Some(true) { ToolOutcome::Error } else { ToolOutcome::Success };
+                tracker.finish(outcome);
+                Ok(result)
             }
-            _ => {
-                Err(McpError::invalid_request(
-                    format!("未知的工具: {} (标准化后: {})", tool_name, normalized_tool_name),
-                    None
-                ))
+            Err(e) => {
+                tracker.finish(ToolOutcome::Error);
+                log_important!(warn, "工具 {} 执行失败: {}", tool.name(), e);
+                Ok(CallToolResult::error(e.message().to_string()))
             }
         }
     }
 
+    /// 读取最新配置，失败时使用默认配置
+    fn current_config(&self) -> AppConfig {
+        self.config.get()
+    }
+
+    /// 提供记忆资源的项目：当前工作目录、客户端的根目录以及本进程中使用过 `ji` 的项目
+    fn resource_projects(&self, session: &McpSession) -> Vec<String> {
+        let mut projects: Vec<String> = std::env::current_dir()
+            .map(|dir| vec![dir.to_string_lossy().to_string()])
+            .unwrap_or_default();
+        projects.extend(session.roots().unwrap_or_default().iter().map(|root| root.to_string_lossy().to_string()));
+        projects.extend(known_projects());
+        projects
+    }
+
     /// 获取工具列表
+    ///
+    /// 包含所有已注册的工具及其兼容别名，`enabled` 反映当前配置
     pub fn list_tools(&self) -> Vec<ToolInfo> {
         let mut tools = Vec::new();
 
-        // zhi 工具始终可用 - 同时注册两个名称以支持不同环境
-        tools.push(ToolInfo {
-            name: "zhi".to_string(),
-            description: "智能代码审查工具".to_string(),
-            enabled: self.is_tool_enabled("zhi"),
-        });
+        for tool in self.tools.iter() {
+            let enabled = self.is_registered_tool_enabled(tool.as_ref());
+            let input_schema = tool.input_schema();
+            let output_schema = tool.output_schema();
 
-        tools.push(ToolInfo {
-            name: "zhi_cunzhi".to_string(),
-            description: "智能代码审查工具 (Augment 兼容)".to_string(),
-            enabled: self.is_tool_enabled("zhi"),
-        });
-
-        // ji 工具根据配置启用
-        if self.is_tool_enabled("ji") {
             tools.push(ToolInfo {
-                name: "ji".to_string(),
-                description: "记忆管理工具".to_string(),
-                enabled: true,
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                enabled,
+                input_schema: input_schema.clone(),
+                output_schema: output_schema.clone(),
             });
 
-            tools.push(ToolInfo {
-                name: "ji_cunzhi".to_string(),
-                description: "记忆管理工具 (Augment 兼容)".to_string(),
-                enabled: true,
-            });
+            for alias in tool.aliases() {
+                tools.push(ToolInfo {
+                    name: alias.to_string(),
+                    description: format!("{} (Augment 兼容)", tool.description()),
+                    enabled,
+                    input_schema: input_schema.clone(),
+                    output_schema: output_schema.clone(),
+                });
+            }
         }
 
         tools
     }
 
-    /// 停止服务器
-    pub async fn stop(&self) -> Result<String> {
-        log_important!(info, "正在停止 MCP 服务器...");
-        // 在简化实现中，我们只是返回成功消息
-        Ok("服务器已停止".to_string())
+    /// 工具在当前配置下是否可用，不可禁用的核心工具始终可用
+    fn is_registered_tool_enabled(&self, tool: &dyn McpTool) -> bool {
+        !tool.can_disable() || self.is_tool_enabled(tool.name())
     }
 
-    /// 获取服务器状态
-    pub async fn status(&self) -> Result<String> {
-        let enabled_tools = self.get_enabled_tools();
-        if enabled_tools.is_empty() {
-            Ok("未运行 - 没有启用的工具".to_string())
-        } else {
-            Ok(format!("运行中 - {} 个工具已启用", enabled_tools.len()))
+    /// `completion/complete`：除规范中的提示词和资源模板外，还支持以 `ref/tool` 补全工具参数
+    fn complete(&self, params: &Value, session: &McpSession) -> Result<Value, McpError> {
+        let argument = params["argument"]["name"].as_str()
+            .ok_or_else(|| McpError::invalid_params("缺少参数名称 argument.name".to_string(), None))?;
+        let value = params["argument"]["value"].as_str().unwrap_or("");
+        let reference = &params["ref"];
+
+        let values = match reference["type"].as_str() {
+            Some("ref/tool") => {
+                let name = reference["name"].as_str().unwrap_or("");
+                let tool = self.tools.find(name)
+                    .filter(|tool| self.is_registered_tool_enabled(tool.as_ref()))
+                    .ok_or_else(|| McpError::invalid_params(format!("未知的工具: {}", name), None))?;
+                if tool.name() == "ji" && argument == "project_path" {
+                    let roots: Vec<String> = session.roots().unwrap_or_default().iter()
+                        .map(|root| root.to_string_lossy().to_string())
+                        .collect();
+                    completion::complete_project_path(value, &roots, &self.resource_projects(session))
+                } else {
+                    completion::complete_from_schema(&tool.input_schema(), argument, value)
+                }
+            }
+            Some("ref/resource") => {
+                let uri = reference["uri"].as_str().unwrap_or("");
+                if uri.starts_with(MEMORY_URI_SCHEME) {
+                    let context = &params["context"]["arguments"];
+                    completion::complete_memory_uri(argument, value, context, &self.resource_projects(session))
+                } else {
+                    Vec::new()
+                }
+            }
+            // 提示词模板的参数没有预设取值
+            Some("ref/prompt") => Vec::new(),
+            _ => return Err(McpError::invalid_params(format!("不支持的补全引用: {}", reference), None)),
+        };
+        Ok(completion::completion_result(values))
+    }
+}
+
+/// 等待进程关闭信号（Ctrl+C，Unix 下还包括 SIGTERM）
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log_important!(warn, "无法监听 Ctrl+C 信号: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                log_important!(warn, "无法监听 SIGTERM 信号: {}", e);
+                std::future::pending::<()>().await;
+            }
         }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
 }
 
-/// 启动 MCP 服务器
+/// 按配置绑定 HTTP 监听地址，返回监听器和需要校验的访问令牌
+///
+/// 启用令牌认证时读取（首次生成）配置目录下的令牌；未启用认证时只允许监听本机地址
+pub async fn bind_http(config: &HttpConfig, port: u16) -> Result<(TcpListener, Option<String>)> {
+    let ip = config.bind_ip()?;
+    if !ip.is_loopback() && !config.require_token {
+        anyhow::bail!("监听非本机地址 {} 时必须启用令牌认证（http_config.require_token）", ip);
+    }
+
+    let token = match config.require_token {
+        true => Some(load_or_create_http_token()?),
+        false => None,
+    };
+
+    let addr = SocketAddr::new(ip, port);
+    let listener = TcpListener::bind(addr).await
+        .map_err(|e| anyhow::anyhow!("无法绑定 HTTP 地址 {}: {}", addr, e))?;
+    Ok((listener, token))
+}
+
+/// 启动 MCP 服务器，连接 `gateway_config` 中配置的下游服务器
 pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
-    let server = ZhiServer::new();
+    let server = ZhiServer::new().connect_gateway().await;
     server.start().await?;
     Ok(())
 }
@@ -491,16 +1145,289 @@ pub struct ToolInfo {
     pub name: String,
     pub description: String,
     pub enabled: bool,
+    /// 参数的 JSON Schema
+    pub input_schema: Value,
+    /// 结构化结果的 JSON Schema，未声明时为 None
+    pub output_schema: Option<Value>,
+}
+
+/// 读取资源请求中的 uri 参数
+fn required_uri(params: &Value) -> Result<&str, McpError> {
+    params["uri"].as_str()
+        .ok_or_else(|| McpError::invalid_params("缺少资源 uri".to_string(), None))
+}
+
+/// 把记忆变更转发为资源通知
+///
+/// `targets` 返回需要通知的会话；订阅了受影响资源的会话收到
+/// `notifications/resources/updated`，所有会话都会收到 `notifications/resources/list_changed`
+fn forward_memory_events<F>(targets: F) -> tokio::task::JoinHandle<()>
+where
+    F: Fn() -> Vec<Arc<McpSession>> + Send + 'static,
+{
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut events = subscribe_memory_events();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    log_debug!("记忆事件积压，跳过 {} 条", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            for session in targets() {
+                for uri in session.subscriptions().into_iter().filter(|uri| resources::is_affected(uri, &event)) {
+                    session.send(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/resources/updated",
+                        "params": { "uri": uri }
+                    }));
+                }
+                session.send(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/resources/list_changed"
+                }));
+            }
+        }
+    })
 }
 
-/// 构建 MCP 响应的辅助函数
-pub fn build_mcp_response(content: &str) -> serde_json::Value {
-    serde_json::json!({
-        "content": [
-            {
-                "type": "text",
-                "text": content
+/// 配置变化时通知客户端刷新工具列表或提示词列表
+fn forward_config_changes<F>(config: &LiveConfig, targets: F) -> tokio::task::JoinHandle<()>
+where
+    F: Fn() -> Vec<Arc<McpSession>> + Send + 'static,
+{
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut changes = config.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let change = match changes.recv().await {
+                Ok(change) => change,
+                Err(RecvError::Lagged(_)) => ConfigChange { tools_changed: true, prompts_changed: true },
+                Err(RecvError::Closed) => break,
+            };
+
+            let mut methods = Vec::new();
+            if change.tools_changed {
+                methods.push("notifications/tools/list_changed");
             }
-        ]
+            if change.prompts_changed {
+                methods.push("notifications/prompts/list_changed");
+            }
+            for session in targets() {
+                for method in &methods {
+                    session.send(serde_json::json!({ "jsonrpc": "2.0", "method": method }));
+                }
+            }
+        }
     })
 }
+
+/// 在后台向客户端请求根目录，失败时保持缓存不变，调用工具时再按需获取
+fn spawn_roots_refresh(ctx: &RequestContext) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Err(e) = ctx.refresh_roots().await {
+            log_debug!("获取客户端根目录失败: {}", e.message());
+        }
+    });
+}
+
+/// 依次写出响应和服务器推送的消息，每条消息占一行，启用录制时同时录制
+///
+/// 所有请求任务结束（响应通道关闭）后返回
+async fn write_lines<W>(
+    mut writer: W,
+    mut responses: mpsc::UnboundedReceiver<String>,
+    mut pushes: mpsc::UnboundedReceiver<Value>,
+    recorder: Option<Recorder>,
+) where
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let line = tokio::select! {
+            response = responses.recv() => match response {
+                Some(response) => response,
+                None => break,
+            },
+            Some(message) = pushes.recv() => message.to_string(),
+        };
+        if let Some(recorder) = &recorder {
+            recorder.record(Direction::Out, &line);
+        }
+
+        let written = async {
+            writer.write_all(line.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await
+        };
+        if let Err(e) = written.await {
+            log_important!(warn, "写出响应失败: {}", e);
+            break;
+        }
+    }
+}
+
+/// 服务运行期间的通知转发，被丢弃时停止
+pub struct NotificationForwarders {
+    _logs: LogForwarderGuard,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for NotificationForwarders {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// 按行分隔连接的会话守卫：连接结束或处理任务被中止（如服务器关闭）时移除会话
+struct SessionGuard {
+    sessions: SessionManager,
+    id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if self.sessions.remove(&self.id) {
+            refresh_forward_level(&self.sessions);
+        }
+    }
+}
+
+/// 会话关闭后重新计算转发级别：取剩余会话中最详细的一个，没有会话请求日志时停止转发
+fn refresh_forward_level(sessions: &SessionManager) {
+    let level = sessions.all().iter()
+        .map(|session| session.log_level())
+        .max()
+        .unwrap_or(log::LevelFilter::Off);
+    set_forward_level(level);
+}
+
+/// `log` 级别对应的 MCP 日志级别
+fn mcp_log_level(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "error",
+        log::Level::Warn => "warning",
+        log::Level::Info => "info",
+        log::Level::Debug | log::Level::Trace => "debug",
+    }
+}
+
+/// 解析 `logging/setLevel` 的级别；`log` 没有更高的级别，notice 视为 info，critical 及以上视为 error
+fn parse_log_level(level: &str) -> Option<log::LevelFilter> {
+    let level = match level {
+        "debug" => log::LevelFilter::Debug,
+        "info" | "notice" => log::LevelFilter::Info,
+        "warning" => log::LevelFilter::Warn,
+        "error" | "critical" | "alert" | "emergency" => log::LevelFilter::Error,
+        _ => return None,
+    };
+    Some(level)
+}
+
+/// 需要回复的单个请求的 ID；通知、批量消息和格式错误的消息返回 None
+fn request_id(message: &Value) -> Option<&Value> {
+    message.get("method")?;
+    message.get("id")
+}
+
+/// 判断消息（或批量消息）中是否包含需要回复的请求
+fn contains_request(message: &Value) -> bool {
+    match message {
+        Value::Array(batch) => batch.iter().any(contains_request),
+        Value::Object(obj) => obj.contains_key("method") && obj.contains_key("id"),
+        _ => false,
+    }
+}
+
+/// 由通道驱动的流式响应体，用于 SSE
+struct ChannelBody {
+    receiver: mpsc::UnboundedReceiver<Bytes>,
+}
+
+impl ChannelBody {
+    fn new() -> (mpsc::UnboundedSender<Bytes>, Self) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (sender, Self { receiver })
+    }
+}
+
+impl hyper::body::Body for ChannelBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        self.receiver.poll_recv(cx).map(|chunk| chunk.map(|bytes| Ok(Frame::data(bytes))))
+    }
+}
+
+/// 构造一条 SSE 消息事件
+fn sse_event(data: &str) -> Bytes {
+    Bytes::from(format!("event: message\ndata: {}\n\n", data))
+}
+
+fn full_body(data: impl Into<Bytes>) -> HttpBody {
+    http_body_util::Full::new(data.into()).boxed()
+}
+
+/// 为允许的浏览器来源添加 CORS 响应头
+fn add_cors_headers(response: &mut hyper::Response<HttpBody>, origin: &str) {
+    let headers = [
+        ("Access-Control-Allow-Origin", origin),
+        ("Access-Control-Allow-Methods", "GET, POST, DELETE, OPTIONS"),
+        ("Access-Control-Allow-Headers", "Authorization, Content-Type, Accept, Mcp-Session-Id, MCP-Protocol-Version, Last-Event-ID"),
+        ("Access-Control-Expose-Headers", MCP_SESSION_HEADER),
+        ("Vary", "Origin"),
+    ];
+    for (name, value) in headers {
+        if let Ok(value) = hyper::header::HeaderValue::from_str(value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+}
+
+/// 请求是否携带正确的 `Authorization: Bearer <令牌>`
+fn has_bearer_token<B>(req: &hyper::Request<B>, token: &str) -> bool {
+    header_value(req, "Authorization")
+        .and_then(|value| value.strip_prefix("Bearer ").map(|provided| provided.trim().to_string()))
+        .is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.as_bytes()))
+}
+
+/// 比较耗时与内容无关，避免通过响应时间逐字节猜出令牌
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn json_response(status: hyper::StatusCode, body: String) -> hyper::Response<HttpBody> {
+    hyper::Response::builder().status(status)
+        .header("Content-Type", "application/json")
+        .body(full_body(body))
+        .unwrap()
+}
+
+fn text_response(status: hyper::StatusCode, body: &'static str) -> hyper::Response<HttpBody> {
+    hyper::Response::builder().status(status)
+        .body(full_body(body))
+        .unwrap()
+}
+
+fn header_value<B>(req: &hyper::Request<B>, name: &str) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// 客户端是否接受 SSE 响应
+fn accepts_event_stream<B>(req: &hyper::Request<B>) -> bool {
+    header_value(req, "Accept")
+        .map(|accept| accept.contains("text/event-stream"))
+        .unwrap_or(false)
+}
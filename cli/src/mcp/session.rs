@@ -0,0 +1,558 @@
+// MCP 会话管理
+//
+// 每个客户端连接对应一个会话；HTTP 模式下通过 `Mcp-Session-Id` 头关联请求
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::config::AppConfig;
+use crate::mcp::metrics::metrics;
+use crate::mcp::tools::memory::resources::percent_decode;
+use crate::mcp::types::McpError;
+use crate::{log_debug, log_important};
+
+/// 发往客户端的 JSON-RPC 消息通道
+pub type MessageSender = mpsc::UnboundedSender<Value>;
+
+/// 客户端对服务器请求的应答：`Ok(result)` 或 `Err(error)`
+type ClientReply = Result<Value, Value>;
+
+/// 等待客户端响应 `roots/list` 的最长时间
+const ROOTS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 单个客户端会话
+pub struct McpSession {
+    id: String,
+    transport: &'static str,
+    created_at: Instant,
+    /// 最近一次收到客户端请求的时间
+    last_seen: Mutex<Instant>,
+    /// 初始化时客户端上报的信息
+    client_info: Mutex<Option<Value>>,
+    /// 服务器主动推送通道（HTTP 模式下对应 GET /mcp 打开的 SSE 流）
+    push: Mutex<Option<MessageSender>>,
+    /// 正在处理的请求，键为请求 ID 的 JSON 表示
+    in_flight: Mutex<HashMap<String, AbortHandle>>,
+    /// 订阅的资源 URI
+    subscriptions: Mutex<BTreeSet<String>>,
+    /// 服务器发往客户端、尚未收到响应的请求，键为请求 ID 的 JSON 表示
+    outgoing: Mutex<HashMap<String, oneshot::Sender<ClientReply>>>,
+    next_outgoing_id: AtomicU64,
+    /// 通过 `notifications/message` 推送给客户端的最低日志级别，客户端设置前不推送
+    log_level: Mutex<LevelFilter>,
+    /// 客户端通过 `roots/list` 提供的根目录，尚未获取或已失效时为 None
+    roots: Mutex<Option<Vec<PathBuf>>>,
+}
+
+impl McpSession {
+    pub fn new(transport: &'static str) -> Self {
+        Self {
+            id: crate::mcp::utils::generate_request_id(),
+            transport,
+            created_at: Instant::now(),
+            last_seen: Mutex::new(Instant::now()),
+            client_info: Mutex::new(None),
+            push: Mutex::new(None),
+            in_flight: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(BTreeSet::new()),
+            outgoing: Mutex::new(HashMap::new()),
+            next_outgoing_id: AtomicU64::new(1),
+            log_level: Mutex::new(LevelFilter::Off),
+            roots: Mutex::new(None),
+        }
+    }
+
+    /// 会话 ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 传输方式（stdio / http）
+    pub fn transport(&self) -> &'static str {
+        self.transport
+    }
+
+    /// 会话已存在的时长
+    pub fn age(&self) -> Duration {
+        self.created_at.elapsed()
+    }
+
+    /// 记录客户端的一次访问
+    pub fn touch(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    /// 会话是否已空闲超过 `timeout`：期间没有访问，也没有进行中的请求和打开的推送流
+    pub fn is_idle(&self, timeout: Duration) -> bool {
+        self.last_seen.lock().unwrap().elapsed() >= timeout
+            && self.in_flight.lock().unwrap().is_empty()
+            && self.outgoing.lock().unwrap().is_empty()
+            && self.push.lock().unwrap().as_ref().is_none_or(|push| push.is_closed())
+    }
+
+    /// 记录客户端信息
+    pub fn set_client_info(&self, info: Value) {
+        *self.client_info.lock().unwrap() = Some(info);
+    }
+
+    /// 获取客户端信息
+    pub fn client_info(&self) -> Option<Value> {
+        self.client_info.lock().unwrap().clone()
+    }
+
+    /// 客户端在 `initialize` 中声明的能力，未初始化时为 Null
+    pub fn client_capabilities(&self) -> Value {
+        self.client_info.lock().unwrap()
+            .as_ref()
+            .map(|info| info["capabilities"].clone())
+            .unwrap_or(Value::Null)
+    }
+
+    /// 客户端是否支持 `elicitation/create`
+    pub fn supports_elicitation(&self) -> bool {
+        self.client_capabilities()["elicitation"].is_object()
+    }
+
+    /// 客户端是否支持 `roots/list`
+    pub fn supports_roots(&self) -> bool {
+        self.client_capabilities()["roots"].is_object()
+    }
+
+    /// 缓存的客户端根目录，None 表示尚未获取或已失效
+    pub fn roots(&self) -> Option<Vec<PathBuf>> {
+        self.roots.lock().unwrap().clone()
+    }
+
+    /// 更新缓存的客户端根目录，传入 None 使缓存失效
+    pub fn set_roots(&self, roots: Option<Vec<PathBuf>>) {
+        *self.roots.lock().unwrap() = roots;
+    }
+
+    /// 设置推送给客户端的日志级别（`logging/setLevel`）
+    pub fn set_log_level(&self, level: LevelFilter) {
+        *self.log_level.lock().unwrap() = level;
+    }
+
+    /// 推送给客户端的日志级别
+    pub fn log_level(&self) -> LevelFilter {
+        *self.log_level.lock().unwrap()
+    }
+
+    /// 订阅资源更新
+    pub fn subscribe(&self, uri: &str) {
+        self.subscriptions.lock().unwrap().insert(uri.to_string());
+    }
+
+    /// 取消订阅，返回之前是否已订阅
+    pub fn unsubscribe(&self, uri: &str) -> bool {
+        self.subscriptions.lock().unwrap().remove(uri)
+    }
+
+    /// 当前订阅的资源 URI
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.subscriptions.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// 绑定推送通道，新的通道会替换旧的
+    pub fn attach_push(&self, sender: MessageSender) {
+        *self.push.lock().unwrap() = Some(sender);
+    }
+
+    /// 关闭推送通道并中止所有进行中的请求，对应的 SSE 流随之结束，
+    /// 等待客户端响应的请求会立即失败
+    pub fn close(&self) {
+        *self.push.lock().unwrap() = None;
+        self.outgoing.lock().unwrap().clear();
+        for (_, handle) in self.in_flight.lock().unwrap().drain() {
+            handle.abort();
+        }
+    }
+
+    /// 在独立任务中处理请求，处理期间可通过 `cancel_request` 中止
+    pub fn spawn_request<F>(self: &Arc<Self>, request_id: &Value, task: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let key = request_id.to_string();
+        let session = self.clone();
+
+        // 持有锁直到登记完成，保证任务结束时的移除发生在登记之后
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let handle = tokio::spawn({
+            let key = key.clone();
+            async move {
+                task.await;
+                session.in_flight.lock().unwrap().remove(&key);
+            }
+        });
+        in_flight.insert(key, handle.abort_handle());
+        handle
+    }
+
+    /// 取消进行中的请求，返回该请求是否仍在处理
+    pub fn cancel_request(&self, request_id: &Value) -> bool {
+        match self.in_flight.lock().unwrap().remove(&request_id.to_string()) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 正在处理的请求数
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+
+    /// 登记一个发往客户端的请求，返回请求 ID 和接收应答的通道
+    fn register_outgoing(&self) -> (Value, oneshot::Receiver<ClientReply>) {
+        let id = Value::from(format!("cunzhi-{}", self.next_outgoing_id.fetch_add(1, Ordering::Relaxed)));
+        let (tx, rx) = oneshot::channel();
+        self.outgoing.lock().unwrap().insert(id.to_string(), tx);
+        (id, rx)
+    }
+
+    /// 放弃等待发往客户端的请求
+    fn forget_outgoing(&self, id: &Value) {
+        self.outgoing.lock().unwrap().remove(&id.to_string());
+    }
+
+    /// 把客户端响应交给等待中的请求，返回该请求是否存在
+    pub fn resolve_response(&self, id: &Value, reply: Result<Value, Value>) -> bool {
+        match self.outgoing.lock().unwrap().remove(&id.to_string()) {
+            Some(tx) => tx.send(reply).is_ok(),
+            None => false,
+        }
+    }
+
+    /// 等待客户端响应的请求数
+    pub fn outgoing_count(&self) -> usize {
+        self.outgoing.lock().unwrap().len()
+    }
+
+    /// 向客户端推送消息，推送通道不存在或已关闭时返回 false
+    pub fn send(&self, message: Value) -> bool {
+        let mut push = self.push.lock().unwrap();
+        match push.as_ref() {
+            Some(sender) if sender.send(message).is_ok() => true,
+            Some(_) => {
+                *push = None;
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// 会话表
+#[derive(Clone, Default)]
+pub struct SessionManager {
+    sessions: Arc<Mutex<HashMap<String, Arc<McpSession>>>>,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建并登记新会话
+    pub fn create(&self, transport: &'static str) -> Arc<McpSession> {
+        let session = Arc::new(McpSession::new(transport));
+        self.sessions.lock().unwrap().insert(session.id().to_string(), session.clone());
+        metrics().session_opened();
+        session
+    }
+
+    /// 按 ID 查找会话
+    pub fn get(&self, id: &str) -> Option<Arc<McpSession>> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    /// 移除会话，返回是否存在
+    pub fn remove(&self, id: &str) -> bool {
+        match self.sessions.lock().unwrap().remove(id) {
+            Some(session) => {
+                session.close();
+                metrics().sessions_closed(1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 关闭并移除指定传输方式下空闲超过 `timeout` 的会话，返回移除的数量
+    pub fn expire_idle(&self, transport: &str, timeout: Duration) -> usize {
+        let expired: Vec<Arc<McpSession>> = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let ids: Vec<String> = sessions.values()
+                .filter(|session| session.transport() == transport && session.is_idle(timeout))
+                .map(|session| session.id().to_string())
+                .collect();
+            ids.iter().filter_map(|id| sessions.remove(id)).collect()
+        };
+        for session in &expired {
+            session.close();
+        }
+        if !expired.is_empty() {
+            metrics().sessions_closed(expired.len());
+        }
+        expired.len()
+    }
+
+    /// 关闭并移除所有会话
+    pub fn close_all(&self) {
+        let mut sessions = self.sessions.lock().unwrap();
+        metrics().sessions_closed(sessions.len());
+        for (_, session) in sessions.drain() {
+            session.close();
+        }
+    }
+
+    /// 所有活动会话
+    pub fn all(&self) -> Vec<Arc<McpSession>> {
+        self.sessions.lock().unwrap().values().cloned().collect()
+    }
+
+    /// 当前活动会话数
+    pub fn count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+}
+
+/// 单次请求的处理上下文
+///
+/// 请求处理期间产生的通知优先写入与该请求关联的通道（如 POST 的 SSE 流），
+/// 否则回退到会话的推送通道
+#[derive(Clone)]
+pub struct RequestContext {
+    session: Arc<McpSession>,
+    sink: Option<MessageSender>,
+    /// 请求 `_meta.progressToken`，存在时才发送进度通知
+    progress_token: Option<Value>,
+    /// 服务器处理该请求时的配置快照
+    config: Option<AppConfig>,
+    /// `zhi` 是否可以回退到命令行交互
+    cli_fallback: bool,
+}
+
+impl RequestContext {
+    pub fn new(session: Arc<McpSession>, sink: Option<MessageSender>) -> Self {
+        Self { session, sink, progress_token: None, config: None, cli_fallback: true }
+    }
+
+    /// 关联请求携带的进度令牌
+    pub fn with_progress_token(mut self, token: Option<Value>) -> Self {
+        self.progress_token = token.filter(|token| !token.is_null());
+        self
+    }
+
+    /// 请求携带的进度令牌
+    pub fn progress_token(&self) -> Option<&Value> {
+        self.progress_token.as_ref()
+    }
+
+    /// 附带服务器当前的配置，工具据此处理请求而不必再读取配置文件
+    pub fn with_config(mut self, config: AppConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// 服务器的配置快照；不经服务器调用时为 None
+    pub fn config(&self) -> Option<&AppConfig> {
+        self.config.as_ref()
+    }
+
+    /// 不在命令行中询问用户，只使用弹窗、终端窗口和控制套接字
+    ///
+    /// 用于控制终端被其它程序占用的场景，如作为客户端子进程运行的 `cunzhi guard`
+    pub fn without_cli_fallback(mut self) -> Self {
+        self.cli_fallback = false;
+        self
+    }
+
+    /// `zhi` 是否可以回退到命令行交互
+    pub fn cli_fallback(&self) -> bool {
+        self.cli_fallback
+    }
+
+    /// 不关联任何客户端连接的上下文（直接调用或测试时使用）
+    pub fn detached() -> Self {
+        Self::new(Arc::new(McpSession::new("direct")), None)
+    }
+
+    /// 所属会话
+    pub fn session(&self) -> &Arc<McpSession> {
+        &self.session
+    }
+
+    /// 发送 JSON-RPC 通知
+    pub fn notify(&self, method: &str, params: Value) {
+        self.deliver(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        }));
+    }
+
+    /// 向客户端发送请求并等待响应
+    ///
+    /// 没有可用的通道时立即失败；返回的 future 被丢弃时不再等待该响应
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let (id, reply) = self.session.register_outgoing();
+        let _pending = OutgoingGuard { session: &self.session, id: &id };
+
+        let delivered = self.deliver(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params
+        }));
+        if !delivered {
+            return Err(McpError::internal_error(format!("无法向客户端发送 {} 请求", method), None));
+        }
+
+        match reply.await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(error)) => Err(McpError::internal_error(
+                format!("客户端拒绝了 {} 请求: {}", method, error["message"].as_str().unwrap_or("未知错误")),
+                None,
+            )),
+            Err(_) => Err(McpError::internal_error("会话已关闭，未收到客户端响应".to_string(), None)),
+        }
+    }
+
+    /// 向客户端请求根目录列表并更新会话缓存
+    ///
+    /// 只保留 `file://` 根目录；客户端不支持、无法送达或超时时返回错误，缓存保持不变
+    pub async fn refresh_roots(&self) -> Result<Vec<PathBuf>, McpError> {
+        if !self.session.supports_roots() {
+            return Err(McpError::invalid_request("客户端不支持 roots".to_string(), None));
+        }
+
+        let result = tokio::time::timeout(ROOTS_TIMEOUT, self.request("roots/list", serde_json::json!({}))).await
+            .map_err(|_| McpError::internal_error("等待客户端响应 roots/list 超时".to_string(), None))??;
+        let roots: Vec<PathBuf> = result["roots"].as_array()
+            .map(|roots| roots.iter().filter_map(|root| root["uri"].as_str()).filter_map(root_path).collect())
+            .unwrap_or_default();
+
+        log_debug!("会话 {} 的根目录: {:?}", self.session.id(), roots);
+        self.session.set_roots(Some(roots.clone()));
+        Ok(roots)
+    }
+
+    /// 客户端根目录：优先使用缓存，客户端支持但尚未获取时向客户端请求
+    ///
+    /// 请求失败时缓存空列表，避免之后每次调用都等待，直到客户端通知列表变化
+    pub async fn roots(&self) -> Vec<PathBuf> {
+        if let Some(roots) = self.session.roots() {
+            return roots;
+        }
+        if !self.session.supports_roots() {
+            return Vec::new();
+        }
+
+        match self.refresh_roots().await {
+            Ok(roots) => roots,
+            Err(e) => {
+                log_important!(warn, "获取客户端根目录失败: {}", e.message());
+                self.session.set_roots(Some(Vec::new()));
+                Vec::new()
+            }
+        }
+    }
+
+    /// 默认项目目录：客户端恰好提供一个根目录时使用该目录
+    pub async fn default_project(&self) -> Option<PathBuf> {
+        match self.roots().await.as_slice() {
+            [root] => Some(root.clone()),
+            _ => None,
+        }
+    }
+
+    /// 把消息写入请求通道或会话推送通道，返回是否送出
+    fn deliver(&self, message: Value) -> bool {
+        if let Some(sink) = &self.sink {
+            if sink.send(message.clone()).is_ok() {
+                return true;
+            }
+        }
+        self.session.send(message)
+    }
+
+    /// 发送进度通知，请求未携带进度令牌时忽略
+    pub fn progress(&self, progress: f64, total: Option<f64>, message: &str) {
+        let Some(token) = &self.progress_token else {
+            return;
+        };
+
+        let mut params = serde_json::json!({
+            "progressToken": token,
+            "progress": progress,
+            "message": message
+        });
+        if let Some(total) = total {
+            params["total"] = serde_json::json!(total);
+        }
+        self.notify("notifications/progress", params);
+    }
+
+    /// 等待 `task` 完成，期间每隔 `interval` 发送一次进度通知
+    ///
+    /// 进度值为自 `started` 起经过的秒数，`message` 根据已等待的整秒数生成说明
+    pub async fn with_progress<F, M>(&self, started: Instant, interval: Duration, message: M, task: F) -> F::Output
+    where
+        F: Future,
+        M: Fn(u64) -> String,
+    {
+        if self.progress_token.is_none() {
+            return task.await;
+        }
+
+        tokio::pin!(task);
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            tokio::select! {
+                output = &mut task => return output,
+                _ = ticker.tick() => {
+                    let elapsed = started.elapsed();
+                    self.progress(elapsed.as_secs_f64(), None, &message(elapsed.as_secs()));
+                }
+            }
+        }
+    }
+}
+
+/// 把 `file://` 根目录 URI 转换为本地路径，其它协议返回 None
+pub fn root_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // 去掉主机部分（通常为空或 localhost）
+    let path = &rest[rest.find('/')?..];
+    let decoded = percent_decode(path)?;
+    // Windows 路径形如 /C:/work
+    let decoded = match decoded.as_bytes() {
+        [b'/', _, b':', ..] => decoded[1..].to_string(),
+        _ => decoded,
+    };
+    Some(PathBuf::from(decoded))
+}
+
+/// 请求结束（含被丢弃）时移除登记的客户端请求
+struct OutgoingGuard<'a> {
+    session: &'a McpSession,
+    id: &'a Value,
+}
+
+impl Drop for OutgoingGuard<'_> {
+    fn drop(&mut self) {
+        self.session.forget_outgoing(self.id);
+    }
+}
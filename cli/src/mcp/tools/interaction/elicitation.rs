@@ -0,0 +1,88 @@
+//! 通过 MCP elicitation 在客户端内询问用户
+//!
+//! 预定义选项映射为布尔字段（`option_1`、`option_2` ...），自由输入映射为 `user_input` 字段，
+//! 客户端的 accept / decline / cancel 应答统一转换为 `build_mcp_response` 的 JSON
+
+use serde_json::{json, Map, Value};
+
+use crate::mcp::types::{build_mcp_response, McpError, ZhiRequest};
+
+/// 自由输入字段名
+const USER_INPUT_FIELD: &str = "user_input";
+
+/// 响应元数据中的来源标识
+const SOURCE: &str = "elicitation";
+
+/// 构建 `elicitation/create` 的请求参数
+pub fn elicitation_params(request: &ZhiRequest) -> Value {
+    let mut properties = Map::new();
+    for (index, option) in request.predefined_options.iter().enumerate() {
+        properties.insert(
+            option_field(index),
+            json!({
+                "type": "boolean",
+                "title": option,
+                "default": false
+            }),
+        );
+    }
+    properties.insert(
+        USER_INPUT_FIELD.to_string(),
+        json!({
+            "type": "string",
+            "title": if request.predefined_options.is_empty() { "您的回复" } else { "补充说明" },
+            "description": "留空表示按照最佳实践继续"
+        }),
+    );
+
+    json!({
+        "message": request.message,
+        "requestedSchema": {
+            "type": "object",
+            "properties": properties
+        }
+    })
+}
+
+/// 把客户端的 elicitation 应答转换为与弹窗一致的响应 JSON
+pub fn elicitation_response(
+    request: &ZhiRequest,
+    result: &Value,
+    request_id: &str,
+    continue_prompt: &str,
+) -> Result<Value, McpError> {
+    let request_id = Some(request_id.to_string());
+
+    match result["action"].as_str() {
+        Some("accept") => {
+            let content = &result["content"];
+            let selected_options: Vec<String> = request.predefined_options.iter()
+                .enumerate()
+                .filter(|(index, _)| content[option_field(*index)].as_bool().unwrap_or(false))
+                .map(|(_, option)| option.clone())
+                .collect();
+            let user_input = content[USER_INPUT_FIELD].as_str()
+                .map(str::trim)
+                .filter(|input| !input.is_empty())
+                .map(str::to_string);
+
+            // 什么都没填等同于点击「继续」
+            let user_input = match (&user_input, selected_options.is_empty()) {
+                (None, true) => Some(continue_prompt.to_string()),
+                _ => user_input,
+            };
+            Ok(build_mcp_response(user_input, selected_options, vec![], request_id, SOURCE))
+        }
+        Some("decline") => Ok(build_mcp_response(
+            Some("用户拒绝回复".to_string()), vec![], vec![], request_id, SOURCE,
+        )),
+        Some("cancel") => Ok(build_mcp_response(
+            Some("用户取消了操作".to_string()), vec![], vec![], request_id, SOURCE,
+        )),
+        _ => Err(McpError::internal_error(format!("无法识别的 elicitation 应答: {}", result), None)),
+    }
+}
+
+fn option_field(index: usize) -> String {
+    format!("option_{}", index + 1)
+}
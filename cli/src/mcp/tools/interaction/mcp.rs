@@ -1,12 +1,24 @@
 use anyhow::Result;
-use crate::mcp::types::{McpError, CallToolResult, Content, PopupRequest};
-use crate::mcp::ZhiRequest;
-use crate::mcp::handlers::popup::create_cli_popup;
+use crate::mcp::types::{McpError, CallToolResult, Content, McpResponse, PopupRequest};
+use crate::mcp::handlers::response::parse_mcp_response;
+use crate::mcp::tools::registry::{input_schema_for, output_schema_for, parse_arguments, McpTool, ToolFuture};
+use super::elicitation::{elicitation_params, elicitation_response};
+use super::pending::{control_response, PendingInteraction};
+use super::reply::{popup_output_to_response, reply_to_response, CANCEL_REPLY, CONTINUE_REPLY};
+use crate::config::AppConfig;
+use crate::mcp::control::control_socket_running;
+use crate::mcp::metrics::{metrics, InteractionBackend};
+use crate::mcp::{RequestContext, ZhiRequest};
 use crate::utils::{colorize, colorize_with_style, colors, terminal_launcher::TerminalLauncher};
 use inquire::{Select, Text, InquireError};
 use console::style;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::env;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// 等待用户回复期间发送进度通知的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(15);
 
 /// 增强的CLI交互处理器
 pub struct EnhancedCliInteraction;
@@ -30,11 +42,11 @@ impl EnhancedCliInteraction {
             })?;
 
         if selection == "取消" {
-            return Ok("用户取消了操作".to_string());
+            Ok(CANCEL_REPLY.to_string())
         } else if selection == "自定义输入" {
-            return Self::handle_custom_input();
+            Self::handle_custom_input()
         } else {
-            return Ok(format!("用户选择: {}", selection));
+            Ok(format!("用户选择: {}", selection))
         }
     }
 
@@ -51,86 +63,306 @@ impl EnhancedCliInteraction {
             })?;
 
         if input.trim().is_empty() {
-            Ok("用户确认继续".to_string())
+            Ok(CONTINUE_REPLY.to_string())
         } else if input.trim().to_lowercase() == "cancel" {
-            Ok("用户取消了操作".to_string())
+            Ok(CANCEL_REPLY.to_string())
         } else if input.trim().to_lowercase() == "continue" {
-            Ok("用户确认继续".to_string())
+            Ok(CONTINUE_REPLY.to_string())
         } else {
             Ok(format!("用户输入: {}", input.trim()))
         }
     }
 }
 
+/// 终端交互期间持有的终端子进程
+///
+/// 交互未完成就被丢弃时（请求取消或超时）终止该进程，以及交互脚本所在的 shell：
+/// 多数终端模拟器由服务进程创建窗口，只终止启动器进程不会关闭窗口中的脚本
+struct TerminalChild {
+    child: Option<std::process::Child>,
+    /// 交互脚本启动时写入自身 PID 的文件
+    pid_file: PathBuf,
+}
+
+impl TerminalChild {
+    /// 交互已完成，不再终止进程
+    fn detach(&mut self) {
+        self.child.take();
+    }
+}
+
+impl Drop for TerminalChild {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            if let Some(pid) = std::fs::read_to_string(&self.pid_file).ok().and_then(|pid| pid.trim().parse::<u32>().ok()) {
+                kill_process(pid);
+            }
+            let _ = child.kill();
+            let _ = child.try_wait();
+        }
+        let _ = std::fs::remove_file(&self.pid_file);
+    }
+}
+
+/// 命令行交互子进程的标准输入和界面输出：控制终端，没有控制终端（如后台服务）时返回 None
+fn controlling_terminal() -> Option<(Stdio, Stdio)> {
+    #[cfg(windows)]
+    let (input, output) = ("CONIN$", "CONOUT$");
+    #[cfg(not(windows))]
+    let (input, output) = ("/dev/tty", "/dev/tty");
+
+    let input = std::fs::OpenOptions::new().read(true).write(true).open(input).ok()?;
+    let output = std::fs::OpenOptions::new().read(true).write(true).open(output).ok()?;
+    Some((input.into(), output.into()))
+}
+
+/// 运行 `cunzhi prompt` 的命令：当前程序就是 cunzhi 时使用自身，否则查找同目录或 PATH 中的 cunzhi
+fn find_prompt_command() -> Option<PathBuf> {
+    let current_exe = env::current_exe().ok()?;
+    if current_exe.file_stem().is_some_and(|stem| stem == "cunzhi") {
+        return Some(current_exe);
+    }
+    let sibling = current_exe.with_file_name(format!("cunzhi{}", env::consts::EXE_SUFFIX));
+    if sibling.is_file() {
+        return Some(sibling);
+    }
+    let found = std::process::Command::new("cunzhi")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    found.then(|| PathBuf::from("cunzhi"))
+}
+
+/// 终止指定 PID 的进程（终端窗口中运行交互脚本的 shell）
+fn kill_process(pid: u32) {
+    #[cfg(unix)]
+    let mut command = {
+        let mut command = std::process::Command::new("kill");
+        command.args(["-TERM", &pid.to_string()]);
+        command
+    };
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = std::process::Command::new("taskkill");
+        command.args(["/PID", &pid.to_string(), "/T", "/F"]);
+        command
+    };
+    let _ = command.stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
+
 /// 智能代码审查交互工具
 ///
 /// 支持预定义选项、自由文本输入和图片上传
 #[derive(Clone)]
 pub struct InteractionTool;
 
+impl McpTool for InteractionTool {
+    fn name(&self) -> &str {
+        "zhi"
+    }
+
+    fn description(&self) -> &str {
+        "智能代码审查工具"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["zhi_cunzhi"]
+    }
+
+    fn can_disable(&self) -> bool {
+        // 核心工具不可禁用
+        false
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        input_schema_for::<ZhiRequest>()
+    }
+
+    fn output_schema(&self) -> Option<serde_json::Value> {
+        Some(output_schema_for::<McpResponse>())
+    }
+
+    fn call<'a>(&'a self, arguments: serde_json::Value, ctx: &'a RequestContext) -> ToolFuture<'a> {
+        Box::pin(async move { Self::zhi_with_context(parse_arguments(arguments)?, ctx).await })
+    }
+}
+
 impl InteractionTool {
     pub async fn zhi(
         request: ZhiRequest,
     ) -> Result<CallToolResult, McpError> {
-        // 首先尝试使用独立UI进程（类似原始项目的GUI方案）
-        match Self::handle_ui_process_interaction(&request).await {
-            Ok(response) => {
-                let content = vec![Content::text(response)];
-                return Ok(CallToolResult::success(content));
+        Self::zhi_with_context(request, &RequestContext::detached()).await
+    }
+
+    /// 处理交互请求；请求携带进度令牌时，等待期间定期发送进度通知
+    ///
+    /// 等待期间可以通过控制套接字回答或取消，先到的回复生效；
+    /// 无论经由哪条交互路径，结果都是 `build_mcp_response` 格式的 structuredContent。
+    /// 获得回复后按实际使用的交互方式记录等待时长
+    pub async fn zhi_with_context(
+        request: ZhiRequest,
+        ctx: &RequestContext,
+    ) -> Result<CallToolResult, McpError> {
+        let started = Instant::now();
+        let request_id = uuid::Uuid::new_v4().to_string();
+        // 经服务器调用时使用服务器的配置，直接调用时读取配置文件
+        let config = ctx.config().cloned().or_else(|| crate::config::load_standalone_config().ok());
+        let continue_prompt = config.as_ref()
+            .map(|c| c.reply_config.continue_prompt.clone())
+            .unwrap_or_else(crate::config::default_continue_prompt);
+
+        let mut pending = PendingInteraction::register(&request_id, &request);
+        let project = ctx.default_project().await;
+        let interaction = Self::interact(&request, ctx, project.as_deref(), started, &request_id, config.as_ref(), &continue_prompt);
+        let result = tokio::select! {
+            result = interaction => result,
+            reply = pending.reply() => Ok(Some((InteractionBackend::Control, control_response(reply, &request_id, &continue_prompt)))),
+        };
+
+        let (backend, response) = match result? {
+            Some(result) => result,
+            // 没有可用的本地交互方式时（如无终端的后台服务），只要控制套接字在运行就在超时前等待控制端回复
+            None if control_socket_running() => {
+                let timeout_seconds = config.as_ref()
+                    .map(|c| c.terminal_config.timeout_seconds)
+                    .unwrap_or_else(crate::config::default_terminal_timeout);
+                log::warn!("没有可用的本地交互方式，等待通过控制套接字回复（最长 {} 秒）", timeout_seconds);
+                metrics().record_fallback(InteractionBackend::Cli);
+                let wait = tokio::time::timeout(Duration::from_secs(timeout_seconds.into()), pending.reply());
+                let reply = ctx
+                    .with_progress(started, PROGRESS_INTERVAL, |secs| waiting_message("控制套接字", secs), wait)
+                    .await
+                    .map_err(|_| McpError::internal_error(
+                        format!("等待控制端回复超时（{}秒），未收到用户响应", timeout_seconds),
+                        None
+                    ))?;
+                (InteractionBackend::Control, control_response(reply, &request_id, &continue_prompt))
+            }
+            None => {
+                metrics().record_fallback(InteractionBackend::Cli);
+                return Err(McpError::internal_error(
+                    "没有可用的交互方式：无法显示弹窗，也没有可用的控制终端".to_string(),
+                    None
+                ));
             }
+        };
+        metrics().record_interaction(backend, started.elapsed());
+        Ok(Self::interaction_result(response))
+    }
+
+    /// 依次尝试客户端表单、独立UI进程、终端和命令行，返回获得回复的交互方式和响应 JSON
+    ///
+    /// 命令行交互无法启动（没有控制终端、找不到 cunzhi 命令或请求不允许命令行交互）时返回 `Ok(None)`；
+    /// 用户取消或交互失败时返回错误
+    ///
+    /// `project` 为客户端的唯一根目录，显示在标题中并作为终端的工作目录
+    async fn interact(
+        request: &ZhiRequest,
+        ctx: &RequestContext,
+        project: Option<&Path>,
+        started: Instant,
+        request_id: &str,
+        config: Option<&AppConfig>,
+        continue_prompt: &str,
+    ) -> Result<Option<(InteractionBackend, serde_json::Value)>, McpError> {
+        // 客户端支持时优先在客户端内以表单询问用户
+        let elicitation_enabled = config.map(|c| c.mcp_config.elicitation).unwrap_or(true);
+        if elicitation_enabled && ctx.session().supports_elicitation() {
+            let elicitation_task = Self::handle_elicitation_interaction(request, ctx, request_id, continue_prompt);
+            let elicitation_result = ctx
+                .with_progress(started, PROGRESS_INTERVAL, |secs| waiting_message("客户端表单", secs), elicitation_task)
+                .await;
+            match elicitation_result {
+                Ok(response) => return Ok(Some((InteractionBackend::Elicitation, response))),
+                Err(e) => {
+                    log::warn!("elicitation 交互失败，回退到独立UI进程: {}", e.message());
+                    metrics().record_fallback(InteractionBackend::Elicitation);
+                }
+            }
+        }
+
+        // 其次尝试使用独立UI进程（类似原始项目的GUI方案）
+        let ui_task = Self::handle_ui_process_interaction(request, request_id, project);
+        let ui_result = ctx
+            .with_progress(started, PROGRESS_INTERVAL, |secs| waiting_message("弹窗", secs), ui_task)
+            .await;
+        match ui_result {
+            Ok(output) => Ok(Some((InteractionBackend::UiProcess, popup_output_to_response(&output, request_id, continue_prompt)))),
             Err(e) => {
                 log::warn!("独立UI进程失败: {}", e);
+                metrics().record_fallback(InteractionBackend::UiProcess);
 
                 // 检查是否请求终端模式作为回退
                 let terminal_mode = request.terminal_mode.unwrap_or(false);
-                if terminal_mode {
-                    if let Ok(config) = crate::config::load_standalone_config() {
-                        if config.terminal_config.enabled {
-                            // 尝试在新终端窗口中启动交互
-                            match Self::handle_terminal_interaction(&request).await {
-                                Ok(response) => {
-                                    let content = vec![Content::text(response)];
-                                    return Ok(CallToolResult::success(content));
-                                }
-                                Err(e) => {
-                                    log::warn!("终端模式也失败: {}", e);
-                                }
-                            }
+                if let Some(config) = config.filter(|c| terminal_mode && c.terminal_config.enabled) {
+                    // 尝试在新终端窗口中启动交互
+                    let terminal_task = Self::handle_terminal_interaction(request, project, config);
+                    let terminal_result = ctx
+                        .with_progress(started, PROGRESS_INTERVAL, |secs| waiting_message("终端", secs), terminal_task)
+                        .await;
+                    match terminal_result {
+                        Ok(reply) => {
+                            return Ok(Some((InteractionBackend::Terminal, reply_to_response(&reply, request_id, "terminal", continue_prompt))));
+                        }
+                        Err(e) => {
+                            log::warn!("终端模式也失败: {}", e);
+                            metrics().record_fallback(InteractionBackend::Terminal);
                         }
                     }
                 }
 
                 // 最后回退到CLI交互模式
+                if !ctx.cli_fallback() {
+                    log::info!("当前请求不使用命令行交互");
+                    return Ok(None);
+                }
+                let Some(terminal) = controlling_terminal() else {
+                    log::warn!("没有可用的控制终端，无法在命令行中询问用户");
+                    return Ok(None);
+                };
+                let Some(command) = find_prompt_command() else {
+                    log::warn!("找不到 cunzhi 命令，无法在命令行中询问用户");
+                    return Ok(None);
+                };
                 log::info!("回退到CLI交互模式");
-                let response = Self::handle_cli_interaction(&request).await?;
-                let content = vec![Content::text(response)];
-                Ok(CallToolResult::success(content))
+                let cli_task = Self::handle_cli_process_interaction(request, request_id, project, terminal, &command);
+                let reply = ctx
+                    .with_progress(started, PROGRESS_INTERVAL, |secs| waiting_message("命令行", secs), cli_task)
+                    .await?;
+                Ok(Some((InteractionBackend::Cli, reply_to_response(&reply, request_id, "cli", continue_prompt))))
             }
         }
     }
 
+    /// 交互结果：用户附带的图片作为图片内容，选项与输入的摘要作为文本内容，完整响应作为 structuredContent
+    fn interaction_result(response: serde_json::Value) -> CallToolResult {
+        let text = response.to_string();
+        let content = parse_mcp_response(&text).unwrap_or_else(|_| vec![Content::text(text)]);
+        CallToolResult::success(content).with_structured_content(response)
+    }
+
+    /// 通过客户端的 elicitation 表单询问用户
+    async fn handle_elicitation_interaction(
+        request: &ZhiRequest,
+        ctx: &RequestContext,
+        request_id: &str,
+        continue_prompt: &str,
+    ) -> Result<serde_json::Value, McpError> {
+        let result = ctx.request("elicitation/create", elicitation_params(request)).await?;
+        elicitation_response(request, &result, request_id, continue_prompt)
+    }
+
     /// 处理独立UI进程交互（类似原始项目的GUI方案）
-    async fn handle_ui_process_interaction(request: &ZhiRequest) -> Result<String, McpError> {
-        use crate::mcp::types::PopupRequest;
+    async fn handle_ui_process_interaction(request: &ZhiRequest, request_id: &str, project: Option<&Path>) -> Result<String, McpError> {
         use crate::mcp::handlers::popup::create_cli_popup;
 
-        // 生成请求ID
-        let request_id = uuid::Uuid::new_v4().to_string();
-
         // 创建弹窗请求
-        let popup_request = PopupRequest {
-            id: request_id,
-            message: request.message.clone(),
-            predefined_options: if request.predefined_options.is_empty() {
-                None
-            } else {
-                Some(request.predefined_options.clone())
-            },
-            is_markdown: request.is_markdown,
-        };
+        let popup_request = Self::popup_request(request, request_id, project);
 
         // 调用独立UI进程
-        match create_cli_popup(&popup_request) {
+        match create_cli_popup(&popup_request).await {
             Ok(response) => Ok(response),
             Err(e) => Err(McpError::internal_error(
                 format!("UI进程交互失败: {}", e),
@@ -139,24 +371,37 @@ impl InteractionTool {
         }
     }
 
-    /// 处理终端交互模式
-    async fn handle_terminal_interaction(request: &ZhiRequest) -> Result<String, McpError> {
-        // 加载配置获取超时设置
-        let config = crate::config::load_standalone_config()
-            .map_err(|e| McpError::internal_error(
-                format!("加载配置失败: {}", e),
+    /// 交给弹窗和命令行子进程的交互请求
+    fn popup_request(request: &ZhiRequest, request_id: &str, project: Option<&Path>) -> PopupRequest {
+        PopupRequest {
+            id: request_id.to_string(),
+            message: request.message.clone(),
+            predefined_options: if request.predefined_options.is_empty() {
                 None
-            ))?;
+            } else {
+                Some(request.predefined_options.clone())
+            },
+            is_markdown: request.is_markdown,
+            project: project.map(|project| project.to_string_lossy().to_string()),
+        }
+    }
 
+    /// 处理终端交互模式，终端在项目目录（没有时为当前目录）中打开
+    async fn handle_terminal_interaction(request: &ZhiRequest, project: Option<&Path>, config: &AppConfig) -> Result<String, McpError> {
         // 创建临时脚本来运行交互
-        let script_content = Self::generate_interaction_script(request)?;
+        let id = uuid::Uuid::new_v4();
+        let pid_file = env::temp_dir().join(format!("cunzhi_terminal_{}.pid", id));
+        let response_file = env::temp_dir().join(format!("cunzhi_response_{}.txt", id));
+        let script_content = Self::generate_interaction_script(request, project, &pid_file, &response_file)?;
         let temp_script_path = Self::create_temp_script(&script_content)?;
 
         // 配置终端启动器
-        let mut launcher_config = crate::utils::terminal_launcher::TerminalLauncherConfig::default();
-        launcher_config.window_title = Some(config.terminal_config.window_title.clone());
-        launcher_config.fallback_to_cli = config.terminal_config.fallback_to_cli;
-        launcher_config.working_directory = env::current_dir().ok();
+        let mut launcher_config = crate::utils::terminal_launcher::TerminalLauncherConfig {
+            window_title: Some(config.terminal_config.window_title.clone()),
+            fallback_to_cli: config.terminal_config.fallback_to_cli,
+            working_directory: project.map(Path::to_path_buf).or_else(|| env::current_dir().ok()),
+            ..Default::default()
+        };
 
         // 设置首选终端
         if let Some(ref preferred) = config.terminal_config.preferred_terminal {
@@ -167,10 +412,16 @@ impl InteractionTool {
         let launcher = TerminalLauncher::new(launcher_config);
 
         // 启动终端并等待结果
-        match launcher.launch_terminal_with_command("bash", &[temp_script_path.to_string_lossy().to_string()]).await {
-            Ok(_) => {
+        match launcher.spawn_terminal_with_command("bash", &[temp_script_path.to_string_lossy().to_string()]).await {
+            Ok(child) => {
+                // 请求被取消或超时时终止终端进程
+                let mut terminal = TerminalChild { child: Some(child), pid_file };
                 // 等待用户交互完成并读取结果
-                Self::wait_for_terminal_result(&temp_script_path, config.terminal_config.timeout_seconds).await
+                let result = Self::wait_for_terminal_result(&temp_script_path, &response_file, config.terminal_config.timeout_seconds).await;
+                if result.is_ok() {
+                    terminal.detach();
+                }
+                result
             }
             Err(e) => {
                 // 清理临时文件
@@ -202,42 +453,22 @@ impl InteractionTool {
     }
 
     /// 等待终端交互结果（使用配置的超时时间）
-    async fn wait_for_terminal_result(script_path: &PathBuf, timeout_seconds: u32) -> Result<String, McpError> {
+    ///
+    /// 只读取本次交互脚本的 `response_file`，并发的终端交互不会读到彼此的回复
+    async fn wait_for_terminal_result(script_path: &Path, response_file: &Path, timeout_seconds: u32) -> Result<String, McpError> {
         use tokio::time::{sleep, Duration};
 
-        // 解析响应文件路径
-        let temp_dir = env::temp_dir();
-        let mut response_files: Vec<_> = Vec::new();
-
         // 等待响应文件出现
         let max_iterations = timeout_seconds;
         for i in 0..max_iterations {
-            // 每次都重新扫描目录，寻找新的响应文件
-            if let Ok(entries) = std::fs::read_dir(&temp_dir) {
-                response_files = entries
-                    .filter_map(|entry| entry.ok())
-                    .filter(|entry| {
-                        entry.file_name()
-                            .to_string_lossy()
-                            .starts_with("cunzhi_response_")
-                    })
-                    .collect();
-            }
-
-            // 检查是否有响应文件包含内容
-            for response_file_entry in &response_files {
-                let response_path = response_file_entry.path();
-                if response_path.exists() {
-                    if let Ok(content) = std::fs::read_to_string(&response_path) {
-                        if !content.trim().is_empty() {
-                            // 清理临时文件
-                            let _ = std::fs::remove_file(script_path);
-                            let _ = std::fs::remove_file(&response_path);
-
-                            log::info!("收到用户响应，用时 {} 秒", i + 1);
-                            return Ok(content.trim().to_string());
-                        }
-                    }
+            if let Ok(content) = std::fs::read_to_string(response_file) {
+                if !content.trim().is_empty() {
+                    // 清理临时文件
+                    let _ = std::fs::remove_file(script_path);
+                    let _ = std::fs::remove_file(response_file);
+
+                    log::info!("收到用户响应，用时 {} 秒", i + 1);
+                    return Ok(content.trim().to_string());
                 }
             }
 
@@ -252,6 +483,7 @@ impl InteractionTool {
 
         // 超时清理
         let _ = std::fs::remove_file(script_path);
+        let _ = std::fs::remove_file(response_file);
 
         Err(McpError::internal_error(
             format!("终端交互超时（{}秒），未收到用户响应", timeout_seconds),
@@ -260,15 +492,21 @@ impl InteractionTool {
     }
 
     /// 生成交互脚本内容
-    fn generate_interaction_script(request: &ZhiRequest) -> Result<String, McpError> {
-        let temp_dir = env::temp_dir();
-        let response_file = temp_dir.join(format!("cunzhi_response_{}.txt", uuid::Uuid::new_v4()));
+    ///
+    /// 脚本启动时把 shell 的 PID 写入 `pid_file`，交互被取消时据此终止脚本；回复写入 `response_file`
+    fn generate_interaction_script(
+        request: &ZhiRequest,
+        project: Option<&Path>,
+        pid_file: &Path,
+        response_file: &Path,
+    ) -> Result<String, McpError> {
 
         let script = format!(r#"#!/bin/bash
 
 # 寸止 CLI 终端交互脚本
+echo $$ > "{}"
 echo "🤖 寸止 AI 助手"
-echo "════════════════════════════════════════════════"
+{}echo "════════════════════════════════════════════════"
 
 # 显示消息
 cat << 'EOF'
@@ -284,7 +522,7 @@ if [ {} -gt 0 ]; then
     echo ""
     echo "请选择一个选项 (输入数字)，或输入自定义内容："
 
-    read -p "> " user_input
+    read -r -p "> " user_input
 
     # 检查是否是数字选择
     if [[ "$user_input" =~ ^[0-9]+$ ]] && [ "$user_input" -ge 1 ] && [ "$user_input" -le {} ]; then
@@ -296,7 +534,7 @@ if [ {} -gt 0 ]; then
     fi
 else
     echo "请输入您的回复:"
-    read -p "> " user_input
+    read -r -p "> " user_input
     echo "用户输入: $user_input" > "{}"
 fi
 
@@ -305,6 +543,10 @@ echo "回复已记录，您可以关闭此终端窗口。"
 echo "按任意键退出..."
 read -n 1
 "#,
+            pid_file.to_string_lossy(),
+            // 单引号内的路径不做任何展开
+            project.map(|project| format!("echo '📁 {}'\n", project.display().to_string().replace('\'', "'\\''")))
+                .unwrap_or_default(),
             request.message,
             request.predefined_options.len(),
             Self::format_options_for_script(&request.predefined_options),
@@ -354,7 +596,7 @@ read -n 1
         {
             use std::os::unix::fs::PermissionsExt;
             let mut perms = std::fs::metadata(&script_path)
-                .and_then(|m| Ok(m.permissions()))
+                .map(|m| m.permissions())
                 .map_err(|e| McpError::internal_error(
                     format!("获取文件权限失败: {}", e),
                     None
@@ -370,29 +612,78 @@ read -n 1
         Ok(script_path)
     }
 
-    /// 处理CLI交互
-    async fn handle_cli_interaction(request: &ZhiRequest) -> Result<String, McpError> {
+    /// 在子进程（`cunzhi prompt`）中通过控制终端询问用户
+    ///
+    /// 子进程的输入和界面都使用控制终端，不会读写 stdio 传输的 JSON-RPC 流；
+    /// 返回的 future 被丢弃（如请求被取消）时子进程被终止，不会继续占用终端
+    async fn handle_cli_process_interaction(
+        request: &ZhiRequest,
+        request_id: &str,
+        project: Option<&Path>,
+        (input, output): (Stdio, Stdio),
+        command: &Path,
+    ) -> Result<String, McpError> {
+        use crate::mcp::handlers::popup::TempRequestFile;
+
+        let popup_request = Self::popup_request(request, request_id, project);
+        let temp_file = TempRequestFile(env::temp_dir().join(format!("cunzhi_prompt_{}.json", request_id)));
+        let request_json = serde_json::to_string(&popup_request)
+            .map_err(|e| McpError::internal_error(format!("序列化交互请求失败: {}", e), None))?;
+        std::fs::write(&temp_file.0, request_json)
+            .map_err(|e| McpError::internal_error(format!("创建交互请求文件失败: {}", e), None))?;
+
+        let result = tokio::process::Command::new(command)
+            .arg("prompt")
+            .arg("--request")
+            .arg(&temp_file.0)
+            .stdin(input)
+            .stdout(Stdio::piped())
+            .stderr(output)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| McpError::internal_error(format!("启动命令行交互失败: {}", e), None))?;
+
+        // 用户按 Ctrl+C 取消时子进程以非零状态退出，原因已显示在终端中
+        let reply = String::from_utf8_lossy(&result.stdout).trim().to_string();
+        if result.status.success() && !reply.is_empty() {
+            Ok(reply)
+        } else {
+            Err(McpError::internal_error(format!("命令行交互未完成（{}）", result.status), None))
+        }
+    }
+
+    /// `cunzhi prompt` 子进程：读取交互请求文件，在终端中询问用户，回复写到标准输出
+    ///
+    /// 界面输出到标准错误（即父进程传入的控制终端），标准输出只包含回复
+    pub fn run_cli_prompt(request_file: &Path) -> Result<String, McpError> {
+        let request_json = std::fs::read_to_string(request_file)
+            .map_err(|e| McpError::internal_error(format!("读取交互请求失败: {}", e), None))?;
+        let request: PopupRequest = serde_json::from_str(&request_json)
+            .map_err(|e| McpError::internal_error(format!("解析交互请求失败: {}", e), None))?;
+
         // 显示消息头部
-        println!("\n{}", style("🤖 AI助手").cyan().bold());
-        println!("{}", style("─".repeat(50)).dim());
+        eprintln!("\n{}", style("🤖 AI助手").cyan().bold());
+        if let Some(ref project) = request.project {
+            eprintln!("{}", style(format!("📁 {}", project)).dim());
+        }
+        eprintln!("{}", style("─".repeat(50)).dim());
 
         // 显示消息内容
         if request.is_markdown {
-            let formatted_message = Self::render_simple_markdown(&request.message);
-            println!("{}", formatted_message);
+            eprintln!("{}", Self::render_simple_markdown(&request.message));
         } else {
-            println!("{}", request.message);
+            eprintln!("{}", request.message);
         }
 
-        println!("{}", style("─".repeat(50)).dim());
+        eprintln!("{}", style("─".repeat(50)).dim());
 
         // 处理用户交互
-        if !request.predefined_options.is_empty() {
+        match request.predefined_options {
             // 有预定义选项，使用增强的选择界面
-            EnhancedCliInteraction::handle_option_selection(&request.predefined_options)
-        } else {
+            Some(ref options) if !options.is_empty() => EnhancedCliInteraction::handle_option_selection(options),
             // 没有预定义选项，直接获取用户输入
-            EnhancedCliInteraction::handle_custom_input()
+            _ => EnhancedCliInteraction::handle_custom_input(),
         }
     }
 
@@ -426,40 +717,9 @@ read -n 1
 
         result
     }
+}
 
-    /// 处理MCP交互（现在也支持交互式输入）
-    async fn handle_mcp_interaction(request: &ZhiRequest) -> Result<String, McpError> {
-        // 修改：现在 MCP 模式也支持交互式输入
-        // 直接调用 CLI 交互处理逻辑
-        Self::handle_cli_interaction(request).await
-    }
-
-    /// 简单的Markdown渲染（纯文本版本）
-    fn render_simple_markdown_plain(text: &str) -> String {
-        let mut result = text.to_string();
-
-        // 移除粗体标记 **text**
-        while let Some(start) = result.find("**") {
-            if let Some(end) = result[start + 2..].find("**") {
-                let end = start + 2 + end;
-                let bold_text = result[start + 2..end].to_string();
-                result.replace_range(start..end + 2, &bold_text);
-            } else {
-                break;
-            }
-        }
-
-        // 移除代码块标记 `code`
-        while let Some(start) = result.find('`') {
-            if let Some(end) = result[start + 1..].find('`') {
-                let end = start + 1 + end;
-                let code_text = result[start + 1..end].to_string();
-                result.replace_range(start..end + 1, &code_text);
-            } else {
-                break;
-            }
-        }
-
-        result
-    }
+/// 进度通知中的等待说明
+fn waiting_message(channel: &str, secs: u64) -> String {
+    format!("等待用户在{}中回复，已等待 {} 秒", channel, secs)
 }
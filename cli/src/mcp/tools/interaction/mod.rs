@@ -2,7 +2,15 @@
 //!
 //! 提供智能代码审查交互功能，支持预定义选项、自由文本输入和图片上传
 
+pub mod elicitation;
 pub mod mcp;
+pub mod pending;
+pub mod reply;
 
 // 重新导出主要类型和功能
 pub use mcp::InteractionTool;
+
+/// 注册本模块提供的工具（`zhi`）
+pub fn register(registry: &mut super::ToolRegistry) {
+    registry.register(InteractionTool);
+}
@@ -0,0 +1,158 @@
+//! 等待用户回复的交互
+//!
+//! 每次 `zhi` 调用在等待期间登记在这里，控制套接字可以列出、回答或取消它们；
+//! 控制端的回答与弹窗等交互路径竞争，先到者生效
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use crate::mcp::types::{build_send_response, ZhiRequest};
+
+/// 响应元数据中的来源标识
+const SOURCE: &str = "control";
+
+/// 列表中消息预览的最大字符数
+const PREVIEW_CHARS: usize = 60;
+
+/// 控制端对交互的处理
+#[derive(Debug, Clone)]
+pub enum ControlReply {
+    /// 回复文本和选中的选项
+    Answer { text: Option<String>, options: Vec<String> },
+    /// 取消交互
+    Cancel,
+}
+
+/// 等待中的交互概要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingInfo {
+    pub request_id: String,
+    /// 消息首行预览
+    pub message: String,
+    pub predefined_options: Vec<String>,
+    /// 已等待的秒数
+    pub age_secs: u64,
+}
+
+/// 回答或取消交互失败的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlError {
+    /// 没有该 ID 的等待中交互
+    NotFound,
+    /// 回答不合法
+    Invalid(String),
+}
+
+struct Entry {
+    message: String,
+    predefined_options: Vec<String>,
+    created: Instant,
+    reply: oneshot::Sender<ControlReply>,
+}
+
+fn pending() -> &'static Mutex<HashMap<String, Entry>> {
+    static PENDING: OnceLock<Mutex<HashMap<String, Entry>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 登记中的交互，离开作用域时自动注销
+pub struct PendingInteraction {
+    request_id: String,
+    reply: oneshot::Receiver<ControlReply>,
+}
+
+impl PendingInteraction {
+    pub fn register(request_id: &str, request: &ZhiRequest) -> Self {
+        let (tx, rx) = oneshot::channel();
+        pending().lock().unwrap().insert(request_id.to_string(), Entry {
+            message: request.message.clone(),
+            predefined_options: request.predefined_options.clone(),
+            created: Instant::now(),
+            reply: tx,
+        });
+        Self { request_id: request_id.to_string(), reply: rx }
+    }
+
+    /// 等待控制端的回答
+    pub async fn reply(&mut self) -> ControlReply {
+        match (&mut self.reply).await {
+            Ok(reply) => reply,
+            // 发送端只会随登记一起移除，不会出现；保险起见永远等待
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+impl Drop for PendingInteraction {
+    fn drop(&mut self) {
+        pending().lock().unwrap().remove(&self.request_id);
+    }
+}
+
+/// 当前等待中的交互，等待最久的在前
+pub fn list_pending() -> Vec<PendingInfo> {
+    let mut list: Vec<PendingInfo> = pending().lock().unwrap().iter()
+        .map(|(request_id, entry)| PendingInfo {
+            request_id: request_id.clone(),
+            message: preview(&entry.message),
+            predefined_options: entry.predefined_options.clone(),
+            age_secs: entry.created.elapsed().as_secs(),
+        })
+        .collect();
+    list.sort_by_key(|info| std::cmp::Reverse(info.age_secs));
+    list
+}
+
+/// 回答交互；选项必须是交互的预定义选项之一
+pub fn answer_pending(request_id: &str, text: Option<String>, options: Vec<String>) -> Result<(), ControlError> {
+    let mut pending = pending().lock().unwrap();
+    let entry = pending.get(request_id).ok_or(ControlError::NotFound)?;
+    if let Some(unknown) = options.iter().find(|option| !entry.predefined_options.contains(option)) {
+        return Err(ControlError::Invalid(format!(
+            "选项「{}」不在预定义选项中: {:?}", unknown, entry.predefined_options
+        )));
+    }
+
+    let entry = pending.remove(request_id).ok_or(ControlError::NotFound)?;
+    entry.reply.send(ControlReply::Answer { text, options }).map_err(|_| ControlError::NotFound)
+}
+
+/// 取消交互
+pub fn cancel_pending(request_id: &str) -> Result<(), ControlError> {
+    let entry = pending().lock().unwrap().remove(request_id).ok_or(ControlError::NotFound)?;
+    entry.reply.send(ControlReply::Cancel).map_err(|_| ControlError::NotFound)
+}
+
+/// 把控制端的处理转换为与弹窗一致的响应 JSON
+pub fn control_response(reply: ControlReply, request_id: &str, continue_prompt: &str) -> Value {
+    let request_id = Some(request_id.to_string());
+    let response = match reply {
+        ControlReply::Answer { text, options } => {
+            let text = text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty());
+            // 什么都没给等同于点击「继续」
+            let user_input = match (&text, options.is_empty()) {
+                (None, true) => Some(continue_prompt.to_string()),
+                _ => text,
+            };
+            build_send_response(user_input, options, vec![], request_id, SOURCE)
+        }
+        ControlReply::Cancel => {
+            build_send_response(Some("用户取消了操作".to_string()), vec![], vec![], request_id, SOURCE)
+        }
+    };
+    serde_json::from_str(&response).unwrap_or(Value::String(response))
+}
+
+fn preview(message: &str) -> String {
+    let first_line = message.lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim();
+    let mut preview: String = first_line.chars().take(PREVIEW_CHARS).collect();
+    if first_line.chars().count() > PREVIEW_CHARS || message.trim().lines().count() > 1 {
+        preview.push('…');
+    }
+    preview
+}
@@ -0,0 +1,69 @@
+//! 把各交互路径的文本回复统一为 `build_mcp_response` 的 JSON
+//!
+//! 终端脚本和命令行交互返回「用户选择: X」「用户输入: Y」这样的文本，
+//! 独立 UI 进程输出的最后一行是响应 JSON（旧版本同样是文本）
+
+use serde_json::Value;
+
+use crate::mcp::types::{build_mcp_response, ImageAttachment, McpResponse, McpResponseContent};
+
+/// 用户确认继续时的回复
+pub const CONTINUE_REPLY: &str = "用户确认继续";
+
+/// 用户取消时的回复
+pub const CANCEL_REPLY: &str = "用户取消了操作";
+
+/// 把交互回复文本转换为响应 JSON
+pub fn reply_to_response(reply: &str, request_id: &str, source: &str, continue_prompt: &str) -> Value {
+    let reply = reply.trim();
+    let request_id = Some(request_id.to_string());
+
+    if let Some(option) = reply.strip_prefix("用户选择:") {
+        return build_mcp_response(None, vec![option.trim().to_string()], vec![], request_id, source);
+    }
+
+    let user_input = match reply.strip_prefix("用户输入:").map(str::trim) {
+        // 终端中直接回车等同于继续
+        Some("") => continue_prompt.to_string(),
+        Some(input) => input.to_string(),
+        None if reply == CONTINUE_REPLY || reply.is_empty() => continue_prompt.to_string(),
+        None => reply.to_string(),
+    };
+    build_mcp_response(Some(user_input), vec![], vec![], request_id, source)
+}
+
+/// 解析独立 UI 进程的输出：最后一行是响应 JSON 时直接使用，否则按文本回复处理
+///
+/// 旧版 UI 输出的内容数组（文本与 base64 图片）会转换为新格式
+pub fn popup_output_to_response(output: &str, request_id: &str, continue_prompt: &str) -> Value {
+    let last_line = output.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("");
+
+    if let Ok(response) = serde_json::from_str::<McpResponse>(last_line) {
+        return serde_json::to_value(response).unwrap_or_default();
+    }
+    if let Ok(contents) = serde_json::from_str::<Vec<McpResponseContent>>(last_line) {
+        return legacy_to_response(contents, request_id);
+    }
+    reply_to_response(last_line, request_id, "popup", continue_prompt)
+}
+
+fn legacy_to_response(contents: Vec<McpResponseContent>, request_id: &str) -> Value {
+    let mut texts = Vec::new();
+    let mut images = Vec::new();
+
+    for content in contents {
+        match content.source {
+            Some(source) if content.content_type == "image" && source.source_type == "base64" => {
+                images.push(ImageAttachment {
+                    data: source.data,
+                    media_type: source.media_type,
+                    filename: None,
+                });
+            }
+            _ => texts.extend(content.text),
+        }
+    }
+
+    let user_input = (!texts.is_empty()).then(|| texts.join("\n\n"));
+    build_mcp_response(user_input, vec![], images, Some(request_id.to_string()), "popup")
+}
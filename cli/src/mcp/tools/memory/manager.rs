@@ -1,9 +1,35 @@
 use anyhow::Result;
 use chrono::Utc;
+use std::collections::BTreeSet;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast;
 
-use super::types::{MemoryEntry, MemoryCategory, MemoryMetadata};
+use super::types::{MemoryEntry, MemoryCategory, MemoryEvent, MemoryMetadata};
+use crate::utils::expand_home;
+
+/// 记忆变更事件通道
+fn memory_events() -> &'static broadcast::Sender<MemoryEvent> {
+    static EVENTS: OnceLock<broadcast::Sender<MemoryEvent>> = OnceLock::new();
+    EVENTS.get_or_init(|| broadcast::channel(64).0)
+}
+
+/// 本进程中使用过的项目
+fn used_projects() -> &'static Mutex<BTreeSet<String>> {
+    static PROJECTS: OnceLock<Mutex<BTreeSet<String>>> = OnceLock::new();
+    PROJECTS.get_or_init(|| Mutex::new(BTreeSet::new()))
+}
+
+/// 订阅记忆变更事件
+pub fn subscribe_memory_events() -> broadcast::Receiver<MemoryEvent> {
+    memory_events().subscribe()
+}
+
+/// 本进程中通过记忆管理器访问过的项目路径（规范化后）
+pub fn known_projects() -> Vec<String> {
+    used_projects().lock().unwrap().iter().cloned().collect()
+}
 
 /// 记忆管理器
 pub struct MemoryManager {
@@ -33,13 +59,34 @@ impl MemoryManager {
 
         // 初始化记忆文件结构
         manager.initialize_memory_structure()?;
+        used_projects().lock().unwrap().insert(manager.project_path.clone());
 
         Ok(manager)
     }
 
+    /// 打开已有的记忆目录，不存在时返回错误且不会创建
+    pub fn open_existing(project_path: &str) -> Result<Self> {
+        let normalized_path = Self::normalize_project_path(project_path)?;
+        let memory_dir = normalized_path.join(".cunzhi-memory");
+        if !memory_dir.is_dir() {
+            anyhow::bail!("项目中没有记忆目录: {}", memory_dir.display());
+        }
+
+        Ok(Self {
+            memory_dir,
+            project_path: normalized_path.to_string_lossy().to_string(),
+        })
+    }
+
+    /// 规范化后的项目路径
+    pub fn project_path(&self) -> &str {
+        &self.project_path
+    }
+
     /// 规范化项目路径
     fn normalize_project_path(project_path: &str) -> Result<PathBuf> {
-        let path = Path::new(project_path);
+        // 路径补全会给出 `~/...` 形式的路径
+        let path = expand_home(project_path);
 
         // 转换为绝对路径
         let absolute_path = if path.is_absolute() {
@@ -58,8 +105,8 @@ impl MemoryManager {
     /// 初始化记忆文件结构
     fn initialize_memory_structure(&self) -> Result<()> {
         // 创建分类目录
-        for category in &["rules", "preferences", "patterns", "context"] {
-            let category_dir = self.memory_dir.join(category);
+        for category in MemoryCategory::ALL {
+            let category_dir = self.memory_dir.join(category.dir_name());
             fs::create_dir_all(&category_dir)?;
         }
 
@@ -93,15 +140,8 @@ impl MemoryManager {
         };
 
         // 保存到对应分类目录
-        let category_name = match category {
-            MemoryCategory::Rule => "rules",
-            MemoryCategory::Preference => "preferences",
-            MemoryCategory::Pattern => "patterns",
-            MemoryCategory::Context => "context",
-        };
-
         let entry_file = self.memory_dir
-            .join(category_name)
+            .join(category.dir_name())
             .join(format!("{}.json", entry_id));
 
         let entry_json = serde_json::to_string_pretty(&entry)?;
@@ -110,6 +150,13 @@ impl MemoryManager {
         // 更新元数据
         self.update_metadata()?;
 
+        // 通知订阅者，没有订阅者时忽略
+        let _ = memory_events().send(MemoryEvent {
+            project_path: self.project_path.clone(),
+            category,
+            id: entry_id.clone(),
+        });
+
         Ok(entry_id)
     }
 
@@ -117,41 +164,19 @@ impl MemoryManager {
     pub fn get_all_memories(&self) -> Result<Vec<MemoryEntry>> {
         let mut memories = Vec::new();
 
-        for category_name in &["rules", "preferences", "patterns", "context"] {
-            let category_dir = self.memory_dir.join(category_name);
-            if !category_dir.exists() {
-                continue;
-            }
-
-            for entry in fs::read_dir(&category_dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                
-                if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                    let content = fs::read_to_string(&path)?;
-                    if let Ok(memory) = serde_json::from_str::<MemoryEntry>(&content) {
-                        memories.push(memory);
-                    }
-                }
-            }
+        for category in MemoryCategory::ALL {
+            memories.extend(self.get_memories_by_category(category)?);
         }
 
         // 按创建时间排序
-        memories.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        memories.sort_by_key(|m| std::cmp::Reverse(m.created_at));
 
         Ok(memories)
     }
 
     /// 按分类获取记忆条目
     pub fn get_memories_by_category(&self, category: MemoryCategory) -> Result<Vec<MemoryEntry>> {
-        let category_name = match category {
-            MemoryCategory::Rule => "rules",
-            MemoryCategory::Preference => "preferences",
-            MemoryCategory::Pattern => "patterns",
-            MemoryCategory::Context => "context",
-        };
-
-        let category_dir = self.memory_dir.join(category_name);
+        let category_dir = self.memory_dir.join(category.dir_name());
         let mut memories = Vec::new();
 
         if !category_dir.exists() {
@@ -171,7 +196,7 @@ impl MemoryManager {
         }
 
         // 按创建时间排序
-        memories.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        memories.sort_by_key(|m| std::cmp::Reverse(m.created_at));
 
         Ok(memories)
     }
@@ -219,7 +244,7 @@ impl MemoryManager {
             }
         }
 
-        summary.push_str(&format!("分类统计:\n"));
+        summary.push_str("分类统计:\n");
         summary.push_str(&format!("- 规范规则: {} 条\n", rule_count));
         summary.push_str(&format!("- 用户偏好: {} 条\n", preference_count));
         summary.push_str(&format!("- 最佳实践: {} 条\n", pattern_count));
@@ -1,7 +1,10 @@
 use anyhow::Result;
 use crate::mcp::types::{McpError, CallToolResult, Content};
 use super::{MemoryManager, MemoryCategory};
-use crate::mcp::JiyiRequest;
+use crate::mcp::metrics::metrics;
+use crate::mcp::tools::registry::{input_schema_for, parse_arguments, McpTool, ToolFuture};
+use crate::mcp::{JiyiRequest, RequestContext};
+use crate::utils::expand_home;
 
 /// 全局记忆管理工具
 ///
@@ -9,12 +12,57 @@ use crate::mcp::JiyiRequest;
 #[derive(Clone)]
 pub struct MemoryTool;
 
+impl McpTool for MemoryTool {
+    fn name(&self) -> &str {
+        "ji"
+    }
+
+    fn description(&self) -> &str {
+        "记忆管理工具"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["ji_cunzhi"]
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        input_schema_for::<JiyiRequest>()
+    }
+
+    fn call<'a>(&'a self, arguments: serde_json::Value, ctx: &'a RequestContext) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let mut request: JiyiRequest = parse_arguments(arguments)?;
+            if request.project_path.trim().is_empty() {
+                request.project_path = Self::default_project_path(ctx).await?;
+            }
+            Self::jiyi(request).await
+        })
+    }
+}
+
 impl MemoryTool {
+    /// 省略 `project_path` 时使用客户端提供的唯一根目录
+    async fn default_project_path(ctx: &RequestContext) -> Result<String, McpError> {
+        let roots = ctx.roots().await;
+        match roots.as_slice() {
+            [root] => Ok(root.to_string_lossy().to_string()),
+            [] => Err(McpError::invalid_params(
+                "缺少 project_path，且客户端未提供根目录（roots）".to_string(), None
+            )),
+            _ => {
+                let roots: Vec<String> = roots.iter().map(|root| root.to_string_lossy().to_string()).collect();
+                Err(McpError::invalid_params(
+                    format!("客户端提供了多个根目录，请通过 project_path 指定项目: {}", roots.join(", ")), None
+                ))
+            }
+        }
+    }
+
     pub async fn jiyi(
         request: JiyiRequest,
     ) -> Result<CallToolResult, McpError> {
         // 检查项目路径是否存在
-        if !std::path::Path::new(&request.project_path).exists() {
+        if !expand_home(&request.project_path).exists() {
             return Err(McpError::invalid_params(
                 format!("项目路径不存在: {}", request.project_path), None
             ));
@@ -29,16 +77,12 @@ impl MemoryTool {
                     return Err(McpError::invalid_params("缺少记忆内容".to_string(), None));
                 }
 
-                let category = match request.category.as_str() {
-                    "rule" => MemoryCategory::Rule,
-                    "preference" => MemoryCategory::Preference,
-                    "pattern" => MemoryCategory::Pattern,
-                    "context" => MemoryCategory::Context,
-                    _ => MemoryCategory::Context,
-                };
+                let category = MemoryCategory::parse(&request.category)
+                    .unwrap_or(MemoryCategory::Context);
 
                 let id = manager.add_memory(&request.content, category)
                     .map_err(|e| McpError::internal_error(format!("添加记忆失败: {}", e), None))?;
+                metrics().record_memory_write(category);
 
                 format!("✅ 记忆已添加，ID: {}\n📝 内容: {}\n📂 分类: {:?}", id, request.content, category)
             }
@@ -5,8 +5,14 @@
 pub mod manager;
 pub mod types;
 pub mod mcp;
+pub mod resources;
 
 // 重新导出主要类型和功能
-pub use manager::MemoryManager;
-pub use types::{MemoryEntry, MemoryCategory, MemoryMetadata};
+pub use manager::{MemoryManager, known_projects, subscribe_memory_events};
+pub use types::{MemoryEntry, MemoryCategory, MemoryEvent, MemoryMetadata};
 pub use mcp::MemoryTool;
+
+/// 注册本模块提供的工具（`ji`）
+pub fn register(registry: &mut super::ToolRegistry) {
+    registry.register(MemoryTool);
+}
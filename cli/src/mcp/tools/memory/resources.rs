@@ -0,0 +1,238 @@
+//! 以 MCP 资源的形式暴露项目记忆
+//!
+//! URI 格式：
+//! - `cunzhi-memory://<project>`：项目全部记忆
+//! - `cunzhi-memory://<project>/<category>`：某一分类下的记忆
+//! - `cunzhi-memory://<project>/<category>/<id>`：单条记忆
+//!
+//! `<project>` 是经过百分号编码的项目绝对路径，`<category>` 与 `ji` 工具的分类名相同
+
+use std::collections::HashSet;
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use super::{MemoryCategory, MemoryEntry, MemoryEvent, MemoryManager};
+use crate::mcp::types::McpError;
+
+/// 记忆资源的 URI 前缀
+pub const MEMORY_URI_SCHEME: &str = "cunzhi-memory://";
+
+const MIME_TYPE: &str = "text/markdown";
+
+/// 资源 URI 指向的记忆范围
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryUri {
+    Project { project: String },
+    Category { project: String, category: MemoryCategory },
+    Entry { project: String, category: MemoryCategory, id: String },
+}
+
+impl MemoryUri {
+    /// 解析资源 URI，格式不正确时返回 None
+    pub fn parse(uri: &str) -> Option<Self> {
+        let rest = uri.strip_prefix(MEMORY_URI_SCHEME)?;
+        let mut segments = rest.split('/');
+        let project = percent_decode(segments.next().filter(|s| !s.is_empty())?)?;
+
+        let uri = match (segments.next(), segments.next(), segments.next()) {
+            (None, _, _) => Self::Project { project },
+            (Some(category), None, _) => Self::Category {
+                project,
+                category: MemoryCategory::parse(category)?,
+            },
+            (Some(category), Some(id), None) if !id.is_empty() => Self::Entry {
+                project,
+                category: MemoryCategory::parse(category)?,
+                id: id.to_string(),
+            },
+            _ => return None,
+        };
+        Some(uri)
+    }
+
+    /// 所属项目路径
+    pub fn project(&self) -> &str {
+        match self {
+            Self::Project { project } | Self::Category { project, .. } | Self::Entry { project, .. } => project,
+        }
+    }
+}
+
+impl fmt::Display for MemoryUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", MEMORY_URI_SCHEME, percent_encode(self.project()))?;
+        match self {
+            Self::Project { .. } => Ok(()),
+            Self::Category { category, .. } => write!(f, "/{}", category.as_str()),
+            Self::Entry { category, id, .. } => write!(f, "/{}/{}", category.as_str(), id),
+        }
+    }
+}
+
+/// 资源模板列表
+pub fn resource_templates() -> Vec<Value> {
+    vec![
+        json!({
+            "uriTemplate": format!("{}{{project}}", MEMORY_URI_SCHEME),
+            "name": "项目记忆",
+            "description": "项目的全部记忆；project 为百分号编码的项目绝对路径",
+            "mimeType": MIME_TYPE
+        }),
+        json!({
+            "uriTemplate": format!("{}{{project}}/{{category}}", MEMORY_URI_SCHEME),
+            "name": "分类记忆",
+            "description": "项目某一分类下的记忆；category 取 rule、preference、pattern 或 context",
+            "mimeType": MIME_TYPE
+        }),
+        json!({
+            "uriTemplate": format!("{}{{project}}/{{category}}/{{id}}", MEMORY_URI_SCHEME),
+            "name": "单条记忆",
+            "description": "按 ID 读取单条记忆",
+            "mimeType": MIME_TYPE
+        }),
+    ]
+}
+
+/// 列出给定项目中的记忆资源，没有记忆目录的项目会被跳过
+pub fn list_resources(projects: &[String]) -> Vec<Value> {
+    let mut resources = Vec::new();
+    let mut seen = HashSet::new();
+
+    for project in projects {
+        let Ok(manager) = MemoryManager::open_existing(project) else {
+            continue;
+        };
+        if !seen.insert(manager.project_path().to_string()) {
+            continue;
+        }
+        let Ok(memories) = manager.get_all_memories() else {
+            continue;
+        };
+
+        let project = manager.project_path().to_string();
+        resources.push(json!({
+            "uri": MemoryUri::Project { project: project.clone() }.to_string(),
+            "name": format!("项目记忆: {}", project_name(&project)),
+            "description": format!("{} 中的全部记忆（{} 条）", project, memories.len()),
+            "mimeType": MIME_TYPE
+        }));
+
+        for memory in memories {
+            let uri = MemoryUri::Entry {
+                project: project.clone(),
+                category: memory.category,
+                id: memory.id.clone(),
+            };
+            resources.push(json!({
+                "uri": uri.to_string(),
+                "name": format!("[{}] {}", memory.category.label(), memory.content.chars().take(50).collect::<String>()),
+                "mimeType": MIME_TYPE
+            }));
+        }
+    }
+
+    resources
+}
+
+/// 读取记忆资源，返回 `resources/read` 的结果
+pub fn read_resource(uri: &str) -> Result<Value, McpError> {
+    let parsed = MemoryUri::parse(uri)
+        .ok_or_else(|| McpError::resource_not_found(format!("无法识别的资源 URI: {}", uri), None))?;
+
+    let manager = MemoryManager::open_existing(parsed.project())
+        .map_err(|e| McpError::resource_not_found(format!("{}: {}", uri, e), None))?;
+    let read_error = |e: anyhow::Error| McpError::internal_error(format!("读取记忆失败: {}", e), None);
+
+    let text = match &parsed {
+        MemoryUri::Project { .. } => {
+            let memories = manager.get_all_memories().map_err(read_error)?;
+            let mut text = format!("# 项目记忆: {}\n", manager.project_path());
+            for category in MemoryCategory::ALL {
+                let entries: Vec<&MemoryEntry> = memories.iter().filter(|m| m.category == category).collect();
+                if !entries.is_empty() {
+                    text.push_str(&format!("\n## {}\n\n{}", category.label(), format_entries(&entries)));
+                }
+            }
+            text
+        }
+        MemoryUri::Category { category, .. } => {
+            let memories = manager.get_memories_by_category(*category).map_err(read_error)?;
+            let entries: Vec<&MemoryEntry> = memories.iter().collect();
+            format!("# {}\n\n{}", category.label(), format_entries(&entries))
+        }
+        MemoryUri::Entry { category, id, .. } => {
+            manager.get_memories_by_category(*category).map_err(read_error)?
+                .into_iter()
+                .find(|memory| &memory.id == id)
+                .map(|memory| memory.content)
+                .ok_or_else(|| McpError::resource_not_found(format!("记忆不存在: {}", uri), None))?
+        }
+    };
+
+    Ok(json!({
+        "contents": [{
+            "uri": uri,
+            "mimeType": MIME_TYPE,
+            "text": text
+        }]
+    }))
+}
+
+/// 判断记忆变更是否影响订阅的资源（订阅项目或分类时，其中任一条目变化都算更新）
+pub fn is_affected(subscribed_uri: &str, event: &MemoryEvent) -> bool {
+    let Some(subscribed) = MemoryUri::parse(subscribed_uri) else {
+        return false;
+    };
+    if subscribed.project() != event.project_path {
+        return false;
+    }
+
+    match subscribed {
+        MemoryUri::Project { .. } => true,
+        MemoryUri::Category { category, .. } => category == event.category,
+        MemoryUri::Entry { category, id, .. } => category == event.category && id == event.id,
+    }
+}
+
+fn format_entries(entries: &[&MemoryEntry]) -> String {
+    if entries.is_empty() {
+        return "暂无记忆条目\n".to_string();
+    }
+    entries.iter().map(|memory| format!("- {}\n", memory.content)).collect()
+}
+
+fn project_name(project: &str) -> &str {
+    std::path::Path::new(project)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(project)
+}
+
+/// 对非保留字符以外的字节做百分号编码（`/` 也会被编码）
+pub fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// 解码百分号编码，格式不正确时返回 None
+pub fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -20,6 +20,60 @@ pub enum MemoryCategory {
     Context,     // 项目上下文信息
 }
 
+impl MemoryCategory {
+    /// 全部分类
+    pub const ALL: [MemoryCategory; 4] = [
+        MemoryCategory::Rule,
+        MemoryCategory::Preference,
+        MemoryCategory::Pattern,
+        MemoryCategory::Context,
+    ];
+
+    /// 在工具参数和资源 URI 中使用的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryCategory::Rule => "rule",
+            MemoryCategory::Preference => "preference",
+            MemoryCategory::Pattern => "pattern",
+            MemoryCategory::Context => "context",
+        }
+    }
+
+    /// 由名称解析分类
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| category.as_str() == name)
+    }
+
+    /// 存储目录名
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            MemoryCategory::Rule => "rules",
+            MemoryCategory::Preference => "preferences",
+            MemoryCategory::Pattern => "patterns",
+            MemoryCategory::Context => "context",
+        }
+    }
+
+    /// 中文显示名
+    pub fn label(&self) -> &'static str {
+        match self {
+            MemoryCategory::Rule => "规范规则",
+            MemoryCategory::Preference => "用户偏好",
+            MemoryCategory::Pattern => "最佳实践",
+            MemoryCategory::Context => "项目上下文",
+        }
+    }
+}
+
+/// 记忆变更事件
+#[derive(Debug, Clone)]
+pub struct MemoryEvent {
+    /// 规范化后的项目路径
+    pub project_path: String,
+    pub category: MemoryCategory,
+    pub id: String,
+}
+
 /// 记忆元数据
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MemoryMetadata {
@@ -1,9 +1,28 @@
 // MCP工具注册模块
-// 工具实现按各自的模块目录组织
+// 工具实现按各自的模块目录组织，每个模块提供 `register` 函数注册自己的工具；
+// 新增工具只需在此声明模块并把它的 `register` 加入 `TOOL_MODULES`
+
+pub mod registry;
+pub mod validation;
 
-pub mod memory;
 pub mod interaction;
+pub mod memory;
+
+// 重新导出注册表和校验接口以便访问
+pub use registry::{McpTool, ToolFuture, ToolRegistry, input_schema_for, output_schema_for, parse_arguments};
+pub use validation::{validate_arguments, ValidationError};
+
+/// 各工具模块的注册函数，按此顺序注册
+const TOOL_MODULES: &[fn(&mut ToolRegistry)] = &[
+    interaction::register,
+    memory::register,
+];
 
-// 重新导出工具以便访问
-pub use memory::MemoryTool;
-pub use interaction::InteractionTool;
+/// 内置工具
+pub fn builtin_tools() -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    for register in TOOL_MODULES {
+        register(&mut registry);
+    }
+    registry
+}
@@ -0,0 +1,121 @@
+// MCP 工具注册表
+//
+// 每个工具实现 `McpTool`，服务器只通过注册表枚举和调用工具
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::mcp::session::RequestContext;
+use crate::mcp::types::{CallToolResult, McpError};
+
+/// 工具调用返回的 future
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<CallToolResult, McpError>> + Send + 'a>>;
+
+/// MCP 工具
+pub trait McpTool: Send + Sync {
+    /// 工具名称
+    fn name(&self) -> &str;
+
+    /// 工具描述
+    fn description(&self) -> &str;
+
+    /// 兼容别名（如 Augment 环境中使用的 `zhi_cunzhi`）
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// 是否允许在配置中禁用，核心工具返回 false
+    fn can_disable(&self) -> bool {
+        true
+    }
+
+    /// 参数的 JSON Schema
+    fn input_schema(&self) -> Value;
+
+    /// 结构化结果（`structuredContent`）的 JSON Schema，返回 None 表示只返回文本内容
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
+
+    /// 执行工具
+    fn call<'a>(&'a self, arguments: Value, ctx: &'a RequestContext) -> ToolFuture<'a>;
+}
+
+/// 已注册的工具
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn McpTool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册工具，同名工具会在原位置被替换
+    pub fn register(&mut self, tool: impl McpTool + 'static) {
+        let tool: Arc<dyn McpTool> = Arc::new(tool);
+        match self.tools.iter_mut().find(|existing| existing.name() == tool.name()) {
+            Some(existing) => *existing = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    /// 按注册顺序遍历工具
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn McpTool>> {
+        self.tools.iter()
+    }
+
+    /// 按名称或别名查找工具
+    pub fn find(&self, name: &str) -> Option<&Arc<dyn McpTool>> {
+        self.tools
+            .iter()
+            .find(|tool| tool.name() == name || tool.aliases().contains(&name))
+    }
+
+    /// 已注册的工具数
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+}
+
+/// 由请求结构体生成工具参数的 JSON Schema
+///
+/// 子结构内联展开，去掉 `$schema` 与 `title`，便于客户端直接使用
+pub fn input_schema_for<T: JsonSchema>() -> Value {
+    schema_for::<T>()
+}
+
+/// 由响应结构体生成工具结果的 JSON Schema，格式与参数 Schema 相同
+pub fn output_schema_for<T: JsonSchema>() -> Value {
+    schema_for::<T>()
+}
+
+fn schema_for<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator();
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<T>())
+        .unwrap_or_else(|_| serde_json::json!({ "type": "object" }));
+
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$schema");
+        object.remove("title");
+    }
+    schema
+}
+
+/// 将调用参数解析为工具的请求结构体
+pub fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> Result<T, McpError> {
+    serde_json::from_value(arguments)
+        .map_err(|e| McpError::invalid_params(format!("参数解析失败: {}", e), None))
+}
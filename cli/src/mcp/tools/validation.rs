@@ -0,0 +1,173 @@
+// 工具参数校验
+//
+// 只实现工具 Schema 中用到的 JSON Schema 子集：
+// type、properties、required、additionalProperties、items、enum、
+// 以及数值、字符串长度和数组长度的上下限
+use serde_json::{Map, Value};
+
+/// 参数校验失败，`path` 指向出错的字段（如 `predefined_options[1]`）
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("参数 {path} {message}")]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: if path.is_empty() { "arguments".to_string() } else { path.to_string() },
+            message: message.into(),
+        }
+    }
+}
+
+/// 按 Schema 校验工具参数，返回遇到的第一个错误
+pub fn validate_arguments(schema: &Value, arguments: &Value) -> Result<(), ValidationError> {
+    validate(schema, arguments, "")
+}
+
+fn validate(schema: &Value, value: &Value, path: &str) -> Result<(), ValidationError> {
+    let Some(schema) = schema.as_object() else {
+        // `true` 或缺失的 Schema 接受任意值
+        return Ok(());
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+            return Err(ValidationError::new(
+                path,
+                format!("应为 {} 类型，实际为 {}", allowed.join(" 或 "), type_name(value)),
+            ));
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            return Err(ValidationError::new(
+                path,
+                format!("取值 {} 无效，可选值: {}", value, options.join(", ")),
+            ));
+        }
+    }
+
+    match value {
+        Value::Object(object) => validate_object(schema, object, path),
+        Value::Array(items) => validate_array(schema, items, path),
+        Value::String(text) => validate_length(schema, text.chars().count(), "minLength", "maxLength", "字符", path),
+        Value::Number(number) => validate_range(schema, number.as_f64().unwrap_or_default(), path),
+        _ => Ok(()),
+    }
+}
+
+fn validate_object(schema: &Map<String, Value>, object: &Map<String, Value>, path: &str) -> Result<(), ValidationError> {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for field in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(field) {
+                return Err(ValidationError::new(&join_field(path, field), "缺失（必需字段）"));
+            }
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (field, value) in object {
+        let field_path = join_field(path, field);
+        match properties.and_then(|p| p.get(field)) {
+            Some(field_schema) => validate(field_schema, value, &field_path)?,
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    return Err(ValidationError::new(&field_path, "不是可识别的字段"));
+                }
+                Some(extra) => validate(extra, value, &field_path)?,
+                None => {}
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_array(schema: &Map<String, Value>, items: &[Value], path: &str) -> Result<(), ValidationError> {
+    validate_length(schema, items.len(), "minItems", "maxItems", "项", path)?;
+
+    if let Some(item_schema) = schema.get("items") {
+        for (index, item) in items.iter().enumerate() {
+            validate(item_schema, item, &format!("{}[{}]", path, index))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_length(
+    schema: &Map<String, Value>,
+    len: usize,
+    min_key: &str,
+    max_key: &str,
+    unit: &str,
+    path: &str,
+) -> Result<(), ValidationError> {
+    if let Some(min) = schema.get(min_key).and_then(Value::as_u64) {
+        if (len as u64) < min {
+            return Err(ValidationError::new(path, format!("至少需要 {} {}，实际为 {}", min, unit, len)));
+        }
+    }
+    if let Some(max) = schema.get(max_key).and_then(Value::as_u64) {
+        if (len as u64) > max {
+            return Err(ValidationError::new(path, format!("最多允许 {} {}，实际为 {}", max, unit, len)));
+        }
+    }
+    Ok(())
+}
+
+fn validate_range(schema: &Map<String, Value>, number: f64, path: &str) -> Result<(), ValidationError> {
+    if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+        if number < min {
+            return Err(ValidationError::new(path, format!("不能小于 {}，实际为 {}", min, number)));
+        }
+    }
+    if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+        if number > max {
+            return Err(ValidationError::new(path, format!("不能大于 {}，实际为 {}", max, number)));
+        }
+    }
+    Ok(())
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn join_field(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
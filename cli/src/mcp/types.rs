@@ -1,7 +1,10 @@
 use chrono;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct ZhiRequest {
     /// 要显示给用户的消息
     pub message: String,
@@ -11,7 +14,7 @@ pub struct ZhiRequest {
     /// 消息是否为Markdown格式，默认为true
     #[serde(default = "default_is_markdown")]
     pub is_markdown: bool,
-    /// 是否启用终端模式，默认为false
+    /// 是否在新终端窗口中启动交互，默认为false
     #[serde(default = "default_terminal_mode")]
     pub terminal_mode: Option<bool>,
 }
@@ -24,17 +27,20 @@ fn default_terminal_mode() -> Option<bool> {
     Some(false)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct JiyiRequest {
     /// 操作类型：记忆(添加记忆), 回忆(获取项目信息)
+    #[schemars(schema_with = "jiyi_action_schema")]
     pub action: String,
-    /// 项目路径（必需）
+    /// 项目路径，可以用 `~/` 表示主目录；客户端只提供一个根目录（roots）时可以省略
+    #[serde(default)]
     pub project_path: String,
     /// 记忆内容（记忆操作时必需）
     #[serde(default)]
     pub content: String,
     /// 记忆分类：rule(规范规则), preference(用户偏好), pattern(最佳实践), context(项目上下文)
     #[serde(default = "default_category")]
+    #[schemars(schema_with = "jiyi_category_schema")]
     pub category: String,
 }
 
@@ -42,30 +48,97 @@ fn default_category() -> String {
     "context".to_string()
 }
 
+fn jiyi_action_schema(_gen: &mut SchemaGenerator) -> Schema {
+    string_enum_schema(&["记忆", "回忆"])
+}
+
+fn jiyi_category_schema(_gen: &mut SchemaGenerator) -> Schema {
+    string_enum_schema(&["rule", "preference", "pattern", "context"])
+}
+
+/// 只允许给定取值的字符串字段
+fn string_enum_schema(values: &[&str]) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(values.iter().map(|v| serde_json::json!(v)).collect()),
+        ..Default::default()
+    }
+    .into()
+}
+
 // 简化的 MCP 类型定义（替代 rmcp）
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Content {
-    #[serde(rename = "type")]
-    pub content_type: String,
-    pub text: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub annotations: Option<serde_json::Value>,
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Content {
+    Text {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        annotations: Option<serde_json::Value>,
+    },
+    Image {
+        /// Base64 编码的图片数据
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        annotations: Option<serde_json::Value>,
+    },
+    Audio {
+        /// Base64 编码的音频数据
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        annotations: Option<serde_json::Value>,
+    },
+    /// 嵌入的资源内容
+    Resource {
+        resource: serde_json::Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        annotations: Option<serde_json::Value>,
+    },
+    /// 指向资源的链接
+    #[serde(rename = "resource_link")]
+    ResourceLink {
+        uri: String,
+        /// name、mimeType 等其余字段
+        #[serde(flatten)]
+        fields: serde_json::Map<String, serde_json::Value>,
+    },
 }
 
 impl Content {
     pub fn text(text: String) -> Self {
-        Self {
-            content_type: "text".to_string(),
+        Self::Text {
             text,
             annotations: None,
         }
     }
+
+    pub fn image(data: String, mime_type: String) -> Self {
+        Self::Image {
+            data,
+            mime_type,
+            annotations: None,
+        }
+    }
+
+    /// 文本内容，其它类型返回 None
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text { text, .. } => Some(text),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CallToolResult {
     pub content: Vec<Content>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// 与工具 outputSchema 对应的结构化结果
+    #[serde(rename = "structuredContent", skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<serde_json::Value>,
+    #[serde(rename = "isError", skip_serializing_if = "Option::is_none")]
     pub is_error: Option<bool>,
 }
 
@@ -73,6 +146,7 @@ impl CallToolResult {
     pub fn success(content: Vec<Content>) -> Self {
         Self {
             content,
+            structured_content: None,
             is_error: None,
         }
     }
@@ -80,22 +154,39 @@ impl CallToolResult {
     pub fn error(message: String) -> Self {
         Self {
             content: vec![Content::text(message)],
+            structured_content: None,
             is_error: Some(true),
         }
     }
+
+    /// 附带结构化结果，保留已有的内容
+    pub fn with_structured_content(mut self, value: serde_json::Value) -> Self {
+        self.structured_content = Some(value);
+        self
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum McpError {
+    #[error("Parse error: {0}")]
+    ParseError(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
     #[error("Invalid parameters: {0}")]
     InvalidParams(String),
     #[error("Internal error: {0}")]
     InternalError(String),
     #[error("Method not found: {0}")]
     MethodNotFound(String),
+    #[error("Resource not found: {0}")]
+    ResourceNotFound(String),
 }
 
 impl McpError {
+    pub fn parse_error(msg: String, _data: Option<serde_json::Value>) -> Self {
+        Self::ParseError(msg)
+    }
+
     pub fn invalid_params(msg: String, _data: Option<serde_json::Value>) -> Self {
         Self::InvalidParams(msg)
     }
@@ -105,8 +196,65 @@ impl McpError {
     }
 
     pub fn invalid_request(msg: String, _data: Option<serde_json::Value>) -> Self {
+        Self::InvalidRequest(msg)
+    }
+
+    pub fn method_not_found(msg: String, _data: Option<serde_json::Value>) -> Self {
         Self::MethodNotFound(msg)
     }
+
+    pub fn resource_not_found(msg: String, _data: Option<serde_json::Value>) -> Self {
+        Self::ResourceNotFound(msg)
+    }
+
+    /// 按 JSON-RPC 错误码构造，未知的错误码视为内部错误
+    pub fn from_code(code: i64, msg: String) -> Self {
+        match code {
+            -32700 => Self::ParseError(msg),
+            -32600 => Self::InvalidRequest(msg),
+            -32601 => Self::MethodNotFound(msg),
+            -32602 => Self::InvalidParams(msg),
+            -32002 => Self::ResourceNotFound(msg),
+            _ => Self::InternalError(msg),
+        }
+    }
+
+    /// 不带错误类型前缀的错误信息
+    pub fn message(&self) -> &str {
+        match self {
+            Self::ParseError(msg)
+            | Self::InvalidRequest(msg)
+            | Self::InvalidParams(msg)
+            | Self::InternalError(msg)
+            | Self::MethodNotFound(msg)
+            | Self::ResourceNotFound(msg) => msg,
+        }
+    }
+
+    /// 对应的 JSON-RPC 2.0 错误码
+    pub fn code(&self) -> i64 {
+        match self {
+            Self::ParseError(_) => -32700,
+            Self::InvalidRequest(_) => -32600,
+            Self::MethodNotFound(_) => -32601,
+            Self::InvalidParams(_) => -32602,
+            Self::InternalError(_) => -32603,
+            // MCP 规范约定的资源不存在错误码
+            Self::ResourceNotFound(_) => -32002,
+        }
+    }
+
+    /// 构造携带请求 ID 的 JSON-RPC 错误响应
+    pub fn to_response(&self, id: &serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": self.code(),
+                "message": self.to_string()
+            },
+            "id": id
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -115,28 +263,41 @@ pub struct PopupRequest {
     pub message: String,
     pub predefined_options: Option<Vec<String>>,
     pub is_markdown: bool,
+    /// 显示在标题中的项目目录（来自客户端的根目录）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
 }
 
-/// 新的结构化响应数据格式
-#[derive(Debug, Deserialize)]
+/// 新的结构化响应数据格式，也是 `zhi` 工具的 structuredContent
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct McpResponse {
+    /// 用户输入的文本，没有输入时为 null
     pub user_input: Option<String>,
+    /// 用户选中的预定义选项
     pub selected_options: Vec<String>,
+    /// 用户附带的图片
     pub images: Vec<ImageAttachment>,
+    /// 响应元数据
     pub metadata: ResponseMetadata,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ImageAttachment {
+    /// Base64 编码的图片数据
     pub data: String,
+    /// 图片 MIME 类型，如 image/png
     pub media_type: String,
+    /// 原始文件名
     pub filename: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ResponseMetadata {
+    /// 响应时间（RFC 3339）
     pub timestamp: Option<String>,
+    /// 交互请求 ID
     pub request_id: Option<String>,
+    /// 响应来源：popup、terminal、cli、elicitation 等
     pub source: Option<String>,
 }
 
@@ -0,0 +1,195 @@
+// Unix 域套接字传输
+//
+// 与 stdio 相同的按行分隔 JSON-RPC，多个本地客户端可以共用一个服务器进程而无需开放网络端口；
+// 只支持 stdio 的客户端通过 `cunzhi mcp bridge` 接入
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use super::server::ZhiServer;
+use crate::config::get_runtime_dir;
+#[cfg(unix)]
+use crate::{log_debug, log_important};
+
+/// 默认的 MCP 套接字路径
+pub fn default_socket_path() -> PathBuf {
+    get_runtime_dir().join("mcp.sock")
+}
+
+/// 命令行指定的套接字路径，未指定时使用默认路径；相对路径按当前目录解析
+pub fn resolve_socket_path(path: Option<PathBuf>) -> Result<PathBuf> {
+    Ok(std::path::absolute(path.unwrap_or_else(default_socket_path))?)
+}
+
+/// 已绑定的 Unix 域套接字及其路径
+#[cfg(unix)]
+pub struct UnixSocketListener {
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocketListener {
+    /// 套接字文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// 监听 Unix 域套接字，套接字文件只允许所有者访问
+///
+/// 套接字先在只有所有者可访问的临时目录中创建并设置权限，再移动到目标路径，
+/// 其他用户没有机会在设置权限之前连接。
+/// 已有服务器在该路径监听时返回错误；残留的套接字文件会被替换
+#[cfg(unix)]
+pub fn bind_unix_socket(path: &Path) -> Result<UnixSocketListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(parent)?;
+
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("套接字 {} 已有服务器在监听", path.display());
+        }
+        std::fs::remove_file(path)
+            .map_err(|e| anyhow::anyhow!("无法删除残留的套接字文件 {}: {}", path.display(), e))?;
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let staging = parent.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)
+        .map_err(|e| anyhow::anyhow!("无法创建临时目录 {}: {}", staging.display(), e))?;
+    let staged = staging.join(path.file_name().unwrap_or_default());
+
+    let bound = tokio::net::UnixListener::bind(&staged)
+        .map_err(|e| anyhow::anyhow!("无法监听套接字 {}: {}", path.display(), e))
+        .and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&staged, path)
+                .map_err(|e| anyhow::anyhow!("无法移动套接字到 {}: {}", path.display(), e))?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_dir_all(&staging);
+
+    Ok(UnixSocketListener { listener: bound?, path: path.to_path_buf() })
+}
+
+#[cfg(not(unix))]
+pub fn bind_unix_socket(_path: &Path) -> Result<std::convert::Infallible> {
+    anyhow::bail!("当前平台不支持 Unix 域套接字")
+}
+
+impl ZhiServer {
+    /// 在 Unix 域套接字上提供服务，直到 `shutdown` 完成
+    ///
+    /// 每个连接是一个独立会话；日志、记忆和配置变更的转发为所有连接统一登记一次。
+    /// 关闭时断开所有连接并删除套接字文件
+    #[cfg(unix)]
+    pub async fn serve_unix<F>(&self, listener: UnixSocketListener, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        let UnixSocketListener { listener, path } = listener;
+        let mut connections = tokio::task::JoinSet::new();
+        let forwarders = self.forward_notifications("unix");
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let stream = match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(e) => {
+                            log_important!(warn, "接受套接字连接失败: {}", e);
+                            continue;
+                        }
+                    };
+                    log_debug!("新的套接字连接");
+
+                    let server = self.clone();
+                    connections.spawn(async move {
+                        let (reader, writer) = stream.into_split();
+                        if let Err(e) = server.serve_connection(reader, writer, "unix").await {
+                            log_debug!("套接字连接异常结束: {}", e);
+                        }
+                    });
+                }
+                // 回收已结束的连接
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = &mut shutdown => {
+                    log_important!(info, "收到关闭信号，断开 {} 个套接字连接", connections.len());
+                    break;
+                }
+            }
+        }
+
+        connections.shutdown().await;
+        drop(forwarders);
+        let _ = std::fs::remove_file(path);
+        log_important!(info, "套接字服务器已关闭");
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub async fn serve_unix<F>(&self, listener: std::convert::Infallible, _shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        match listener {}
+    }
+}
+
+/// 在标准输入输出与 Unix 域套接字之间双向转发，供只支持 stdio 的客户端使用
+#[cfg(unix)]
+pub async fn bridge_stdio(path: &Path) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let stream = tokio::net::UnixStream::connect(path).await
+        .map_err(|e| anyhow::anyhow!("无法连接套接字 {}: {}", path.display(), e))?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    // 标准输入在独立线程中读取：tokio 的 stdin 读取无法取消，服务器先断开时会阻止进程退出
+    let (input_tx, mut input_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(16);
+    std::thread::spawn(move || {
+        use std::io::Read;
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 8192];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 || input_tx.blocking_send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let upstream = async move {
+        while let Some(chunk) = input_rx.recv().await {
+            writer.write_all(&chunk).await?;
+        }
+        // 客户端关闭输入后通知服务器，服务器处理完进行中的请求后关闭连接
+        writer.shutdown().await
+    };
+    let downstream = async move {
+        let mut stdout = tokio::io::stdout();
+        tokio::io::copy(&mut reader, &mut stdout).await?;
+        stdout.flush().await
+    };
+
+    // 服务器关闭连接时立即退出；客户端关闭输入时继续转发剩余的响应
+    tokio::pin!(downstream);
+    tokio::select! {
+        result = &mut downstream => result?,
+        result = upstream => {
+            result?;
+            downstream.await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn bridge_stdio(_path: &Path) -> Result<()> {
+    anyhow::bail!("当前平台不支持 Unix 域套接字")
+}
@@ -1,5 +1,6 @@
 // CLI 辅助工具函数
 use std::io::{self, Write};
+use std::path::PathBuf;
 use std::time::Duration;
 
 /// 显示进度指示器
@@ -80,6 +81,17 @@ pub fn format_duration(duration: Duration) -> String {
     }
 }
 
+/// 展开路径开头的 `~`（当前用户的主目录），如 `~/code/app`；其它路径原样返回
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => match dirs::home_dir() {
+            Some(home) => home.join(rest.trim_start_matches('/')),
+            None => PathBuf::from(path),
+        },
+        _ => PathBuf::from(path),
+    }
+}
+
 /// 检查是否在 CI 环境中
 pub fn is_ci_environment() -> bool {
     std::env::var("CI").is_ok() ||
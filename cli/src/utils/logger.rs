@@ -1,13 +1,32 @@
 // 日志系统 - CLI 版本优化
+use std::cell::Cell;
 use std::env;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::sync::Once;
-use log::LevelFilter;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use log::{Level, LevelFilter, Log, Metadata, Record};
 use env_logger::{Builder, Target};
 
 static INIT: Once = Once::new();
 
+/// 日志转发回调，参数依次为级别、记录器名（模块路径）和日志内容
+pub type LogForwarder = Arc<dyn Fn(Level, &str, &str) + Send + Sync>;
+
+/// 已登记的日志转发目标（如 MCP 服务器）
+static FORWARDERS: Mutex<Vec<(u64, LogForwarder)>> = Mutex::new(Vec::new());
+static NEXT_FORWARDER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 文件 / stderr 输出的日志级别
+static BASE_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Off as usize);
+/// 转发给客户端的日志级别，客户端设置前不转发
+static FORWARD_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Off as usize);
+
+thread_local! {
+    /// 正在转发日志，防止转发过程中产生的日志再次被转发
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
 /// 日志配置
 #[derive(Debug, Clone)]
 pub struct LogConfig {
@@ -108,12 +127,94 @@ pub fn init_logger(config: LogConfig) -> anyhow::Result<()> {
             builder.target(Target::Stderr);
         }
 
-        builder.init();
+        let inner = builder.build();
+        BASE_LEVEL.store(inner.filter() as usize, Ordering::Relaxed);
+        if log::set_boxed_logger(Box::new(BridgeLogger { inner })).is_ok() {
+            update_max_level();
+        }
     });
 
     Ok(())
 }
 
+/// 在原有输出之外把日志转发给已登记的转发目标
+struct BridgeLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for BridgeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata) || metadata.level() <= forward_level()
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+
+        if record.level() > forward_level() || FORWARDING.with(Cell::get) {
+            return;
+        }
+
+        let forwarders: Vec<LogForwarder> = FORWARDERS.lock().unwrap()
+            .iter()
+            .map(|(_, forwarder)| forwarder.clone())
+            .collect();
+        if forwarders.is_empty() {
+            return;
+        }
+
+        FORWARDING.with(|forwarding| forwarding.set(true));
+        let logger = record.module_path().unwrap_or_else(|| record.target());
+        let message = record.args().to_string();
+        for forwarder in forwarders {
+            forwarder(record.level(), logger, &message);
+        }
+        FORWARDING.with(|forwarding| forwarding.set(false));
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// 登记日志转发目标，返回的守卫被丢弃时取消登记
+pub fn add_log_forwarder(forwarder: LogForwarder) -> LogForwarderGuard {
+    let id = NEXT_FORWARDER_ID.fetch_add(1, Ordering::Relaxed);
+    FORWARDERS.lock().unwrap().push((id, forwarder));
+    LogForwarderGuard { id }
+}
+
+/// 日志转发目标的登记守卫
+pub struct LogForwarderGuard {
+    id: u64,
+}
+
+impl Drop for LogForwarderGuard {
+    fn drop(&mut self) {
+        FORWARDERS.lock().unwrap().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// 设置转发给客户端的日志级别（运行时生效，不影响文件输出）
+pub fn set_forward_level(level: LevelFilter) {
+    FORWARD_LEVEL.store(level as usize, Ordering::Relaxed);
+    update_max_level();
+}
+
+/// 当前转发给客户端的日志级别
+pub fn forward_level() -> LevelFilter {
+    level_from_usize(FORWARD_LEVEL.load(Ordering::Relaxed))
+}
+
+/// 全局级别取文件输出与客户端转发两者中更详细的一个
+fn update_max_level() {
+    let base = level_from_usize(BASE_LEVEL.load(Ordering::Relaxed));
+    log::set_max_level(base.max(forward_level()));
+}
+
+fn level_from_usize(value: usize) -> LevelFilter {
+    LevelFilter::iter().find(|level| *level as usize == value).unwrap_or(LevelFilter::Off)
+}
+
 /// 自动检测模式并初始化日志系统 - CLI 版本优化
 pub fn auto_init_logger() -> anyhow::Result<()> {
     let args: Vec<String> = env::args().collect();
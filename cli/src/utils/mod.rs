@@ -5,7 +5,10 @@ pub mod ui;
 pub mod error_handler;
 pub mod terminal_launcher;
 
-pub use logger::{LogConfig, init_logger, auto_init_logger};
+pub use logger::{
+    LogConfig, LogForwarder, LogForwarderGuard, init_logger, auto_init_logger,
+    add_log_forwarder, set_forward_level, forward_level,
+};
 pub use cli_helpers::*;
 pub use ui::*;
 pub use error_handler::*;
@@ -1,6 +1,6 @@
 // 跨平台终端启动器模块
 use anyhow::Result;
-use std::process::Command;
+use std::process::{Child, Command};
 use std::path::PathBuf;
 use crate::{log_debug, log_important};
 
@@ -58,6 +58,11 @@ impl TerminalLauncher {
 
     /// 启动新的终端窗口并执行命令
     pub async fn launch_terminal_with_command(&self, command: &str, args: &[String]) -> Result<()> {
+        self.spawn_terminal_with_command(command, args).await.map(|_| ())
+    }
+
+    /// 启动新的终端窗口并执行命令，返回终端进程以便调用方在需要时终止
+    pub async fn spawn_terminal_with_command(&self, command: &str, args: &[String]) -> Result<Child> {
         log_debug!("尝试启动终端执行命令: {} {:?}", command, args);
 
         // 检测可用的终端
@@ -85,9 +90,9 @@ impl TerminalLauncher {
         let result = self.try_launch_with_retry(&mut cmd, &available_terminal).await;
 
         match result {
-            Ok(_) => {
+            Ok(child) => {
                 log_important!(info, "终端启动成功");
-                Ok(())
+                Ok(child)
             }
             Err(e) => {
                 log_important!(warn, "终端启动失败: {}", e);
@@ -134,7 +139,7 @@ impl TerminalLauncher {
     }
 
     /// 带重试机制的启动尝试
-    async fn try_launch_with_retry(&self, cmd: &mut Command, terminal: &TerminalType) -> Result<()> {
+    async fn try_launch_with_retry(&self, cmd: &mut Command, terminal: &TerminalType) -> Result<Child> {
         let max_retries = 3;
         let mut last_error = None;
 
@@ -155,11 +160,11 @@ impl TerminalLauncher {
                             }
                             Ok(None) => {
                                 // 进程仍在运行，成功
-                                return Ok(());
+                                return Ok(child);
                             }
                             Ok(Some(_)) => {
                                 // 进程正常退出（某些终端如 osascript 会立即退出）
-                                return Ok(());
+                                return Ok(child);
                             }
                             Err(e) => {
                                 last_error = Some(anyhow::anyhow!("检查进程状态失败: {}", e));
@@ -168,7 +173,7 @@ impl TerminalLauncher {
                         }
                     } else {
                         // 不需要等待的终端类型
-                        return Ok(());
+                        return Ok(child);
                     }
                 }
                 Err(e) => {
@@ -372,7 +377,7 @@ impl TerminalLauncher {
             "osascript" if cfg!(target_os = "macos") => {
                 // macOS osascript 检查
                 return Command::new("osascript")
-                    .args(&["-e", "return 1"])
+                    .args(["-e", "return 1"])
                     .output()
                     .map(|output| output.status.success())
                     .unwrap_or(false);
@@ -380,7 +385,7 @@ impl TerminalLauncher {
             "wt" if cfg!(target_os = "windows") => {
                 // Windows Terminal 特殊检查
                 return Command::new("wt")
-                    .args(&["--help"])
+                    .args(["--help"])
                     .output()
                     .map(|output| output.status.success())
                     .unwrap_or(false);
@@ -32,7 +32,6 @@ pub mod theme {
 /// 现代化进度条
 pub struct ModernProgressBar {
     pb: ProgressBar,
-    multi: Option<MultiProgress>,
 }
 
 impl ModernProgressBar {
@@ -45,7 +44,7 @@ impl ModernProgressBar {
                 .unwrap()
                 .progress_chars("#>-")
         );
-        Self { pb, multi: None }
+        Self { pb }
     }
 
     /// 创建不确定长度的进度条（旋转器）
@@ -57,7 +56,7 @@ impl ModernProgressBar {
                 .template("{spinner:.green} {msg}")
                 .unwrap()
         );
-        Self { pb, multi: None }
+        Self { pb }
     }
 
     /// 设置消息
@@ -92,6 +91,12 @@ pub struct TaskProgressManager {
     tasks: Vec<ProgressBar>,
 }
 
+impl Default for TaskProgressManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TaskProgressManager {
     pub fn new() -> Self {
         Self {
@@ -140,6 +145,12 @@ pub struct StatusIndicator {
     term: Term,
 }
 
+impl Default for StatusIndicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StatusIndicator {
     pub fn new() -> Self {
         Self {
@@ -200,6 +211,12 @@ pub struct ConfirmDialog {
     term: Term,
 }
 
+impl Default for ConfirmDialog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ConfirmDialog {
     pub fn new() -> Self {
         Self {
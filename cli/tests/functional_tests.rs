@@ -95,7 +95,7 @@ fn test_config_templates_comprehensive() {
 
     for template_name in template_names {
         let config = create_config_template(template_name)
-            .expect(&format!("Should create template '{}'", template_name));
+            .unwrap_or_else(|_| panic!("Should create template '{}'", template_name));
 
         // 验证基本结构
         assert!(!config.version.is_empty());
@@ -118,17 +118,12 @@ async fn test_mcp_server_lifecycle() {
     let server = ZhiServer::new();
 
     // 测试初始状态
-    let status = server.status().await.unwrap();
-    assert!(status.contains("运行中"), "Server should be running with enabled tools");
+    assert!(!server.get_enabled_tools().is_empty(), "Server should have enabled tools");
 
     // 测试启动
     let start_result = server.start().await;
     assert!(start_result.is_ok(), "Server should start successfully");
 
-    // 测试状态查询
-    let status = server.status().await;
-    assert!(status.is_ok(), "Should be able to get server status");
-
     // 测试工具列表
     let tools = server.list_tools();
     assert!(!tools.is_empty(), "Should have available tools");
@@ -139,10 +134,6 @@ async fn test_mcp_server_lifecycle() {
 
     let ji_tool = tools.iter().find(|t| t.name == "ji");
     assert!(ji_tool.is_some(), "Should have ji tool");
-
-    // 测试停止
-    let stop_result = server.stop().await;
-    assert!(stop_result.is_ok(), "Server should stop successfully");
 }
 
 #[test]
@@ -246,9 +237,9 @@ fn test_concurrent_config_operations() {
         let path = Arc::clone(&config_path);
         thread::spawn(move || {
             let content = fs::read_to_string(&*path)
-                .expect(&format!("Thread {} should read config", i));
+                .unwrap_or_else(|_| panic!("Thread {} should read config", i));
             let _: AppConfig = serde_json::from_str(&content)
-                .expect(&format!("Thread {} should parse config", i));
+                .unwrap_or_else(|_| panic!("Thread {} should parse config", i));
         })
     }).collect();
 
@@ -291,15 +282,1216 @@ async fn test_mcp_server_error_scenarios() {
     let _ = server.start().await; // 第一次启动
     let _second_start = server.start().await;
     // 第二次启动可能成功（幂等）或失败，但不应该崩溃
+}
+
+#[tokio::test]
+async fn test_jsonrpc_dispatch_compliance() {
+    use cunzhi_cli::mcp::RequestContext;
+    use serde_json::{json, Value};
+
+    let server = ZhiServer::new();
+    let ctx = RequestContext::detached();
+    let call = |message: String| {
+        let server = server.clone();
+        let ctx = ctx.clone();
+        async move {
+            server.handle_jsonrpc_request(&message, &ctx).await
+                .map(|response| serde_json::from_str::<Value>(&response).expect("Response should be JSON"))
+        }
+    };
+
+    // 通知不产生任何回复
+    let response = call(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}).to_string()).await;
+    assert!(response.is_none(), "Notifications must not be answered");
+
+    // 解析错误
+    let response = call("{not json".to_string()).await.unwrap();
+    assert_eq!(response["error"]["code"], -32700);
+    assert_eq!(response["id"], Value::Null);
+
+    // 非法请求
+    let response = call(json!({"jsonrpc": "1.0", "id": 1, "method": "ping"}).to_string()).await.unwrap();
+    assert_eq!(response["error"]["code"], -32600);
+    assert_eq!(response["id"], 1);
+
+    // 未知方法，回显请求 id
+    let response = call(json!({"jsonrpc": "2.0", "id": "abc", "method": "no/such"}).to_string()).await.unwrap();
+    assert_eq!(response["error"]["code"], -32601);
+    assert_eq!(response["id"], "abc");
+
+    // 参数错误
+    let response = call(json!({"jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": {}}).to_string()).await.unwrap();
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(response["id"], 7);
+
+    let response = call(json!({
+        "jsonrpc": "2.0", "id": 8, "method": "tools/call",
+        "params": {"name": "no_such_tool", "arguments": {}}
+    }).to_string()).await.unwrap();
+    assert_eq!(response["error"]["code"], -32602);
+
+    // 批量请求：通知不占位，错误逐条返回
+    let response = call(json!([
+        {"jsonrpc": "2.0", "id": 1, "method": "ping"},
+        {"jsonrpc": "2.0", "method": "notifications/initialized"},
+        {"jsonrpc": "2.0", "id": 2, "method": "no/such"},
+        42
+    ]).to_string()).await.unwrap();
+    let responses = response.as_array().expect("Batch should produce an array");
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["id"], 1);
+    assert!(responses[0]["result"].is_object());
+    assert_eq!(responses[1]["id"], 2);
+    assert_eq!(responses[1]["error"]["code"], -32601);
+    assert_eq!(responses[2]["error"]["code"], -32600);
+
+    // 只包含通知的批量请求不回复；空批量是非法请求
+    let response = call(json!([{"jsonrpc": "2.0", "method": "notifications/initialized"}]).to_string()).await;
+    assert!(response.is_none());
+    let response = call("[]".to_string()).await.unwrap();
+    assert_eq!(response["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn test_line_transport_concurrent_dispatch() {
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let server = ZhiServer::new();
+    let (client, server_side) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_side);
+    let serve = tokio::spawn(async move { server.serve_lines(server_read, server_write, "test").await });
+
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let requests = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}),
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+        json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 99}}),
+        json!([{"jsonrpc": "2.0", "id": 3, "method": "ping"}]),
+    ];
+    for request in &requests {
+        client_write.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+    }
+    client_write.shutdown().await.unwrap();
+
+    // 输入关闭后服务器处理完剩余请求并退出
+    tokio::time::timeout(std::time::Duration::from_secs(5), serve)
+        .await
+        .expect("Server should stop after EOF")
+        .unwrap()
+        .expect("Transport should finish cleanly");
+
+    let mut lines = BufReader::new(client_read).lines();
+    let mut ids = Vec::new();
+    while let Some(line) = lines.next_line().await.unwrap() {
+        let message: Value = serde_json::from_str(&line).expect("Each line should be one JSON message");
+        match message {
+            Value::Array(batch) => ids.extend(batch.iter().map(|m| m["id"].clone())),
+            message => ids.push(message["id"].clone()),
+        }
+    }
+    ids.sort_by_key(|id| id.as_i64());
+    assert_eq!(ids, vec![json!(1), json!(2), json!(3)], "Only requests should be answered");
+}
+
+#[tokio::test]
+async fn test_record_and_replay_session() {
+    use cunzhi_cli::mcp::recording::{load_recording, replay, Direction, Frame, Recorder};
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("session.jsonl");
+
+    // 录制一次会话
+    let mut server = ZhiServer::new();
+    server.record_to(Recorder::create(&path).unwrap());
+    let (client, server_side) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_side);
+    let serve = tokio::spawn(async move { server.serve_lines(server_read, server_write, "test").await });
+
+    let (mut client_read, mut client_write) = tokio::io::split(client);
+    let requests = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2025-03-26", "capabilities": {}, "clientInfo": {"name": "test", "version": "1"}}}).to_string(),
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}).to_string(),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}).to_string(),
+        "not json".to_string(),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "ping"}).to_string(),
+    ];
+    for request in &requests {
+        client_write.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+    }
+    client_write.shutdown().await.unwrap();
+    serve.await.unwrap().expect("Transport should finish cleanly");
+    client_read.read_to_end(&mut Vec::new()).await.unwrap();
+
+    let mut frames = load_recording(&path).expect("Recording should be readable");
+    let inbound: Vec<&Frame> = frames.iter().filter(|frame| frame.direction == Direction::In).collect();
+    assert_eq!(inbound.len(), 5, "Every inbound line should be recorded");
+    assert_eq!(inbound[3].raw.as_deref(), Some("not json"));
+    assert_eq!(frames.iter().filter(|frame| frame.direction == Direction::Out).count(), 4);
+    assert!(frames.iter().all(|frame| frame.session == frames[0].session));
+
+    let report = replay(ZhiServer::new(), &frames).await;
+    assert_eq!(report.requests, 4);
+    assert_eq!(report.matched, 4, "Unexpected mismatches: {:?}", report.mismatches);
+    assert!(report.is_success());
+
+    // 交互式工具直接返回录制的结果，不弹出界面
+    let session = frames[0].session.clone();
+    let frame = |direction: &str, message: Value| -> Frame {
+        serde_json::from_value(json!({
+            "timestamp": "2025-01-01T00:00:00Z", "session": session, "direction": direction, "message": message
+        })).unwrap()
+    };
+    let arguments = json!({"message": "确认回放", "predefined_options": ["是", "否"]});
+    let answer = json!({
+        "content": [{"type": "text", "text": "选择的选项: 是"}],
+        "structuredContent": {"selected_options": ["是"], "user_input": null}
+    });
+    frames.push(frame("in", json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {"name": "zhi", "arguments": arguments}})));
+    frames.push(frame("out", json!({"jsonrpc": "2.0", "id": 4, "result": answer})));
+
+    let report = tokio::time::timeout(std::time::Duration::from_secs(10), replay(ZhiServer::new(), &frames))
+        .await
+        .expect("Replay should not wait for user interaction");
+    assert_eq!(report.matched, 5, "Unexpected mismatches: {:?}", report.mismatches);
+
+    // 与录制不一致的响应逐字段报告
+    let ping = frames.iter_mut()
+        .find(|frame| frame.direction == Direction::Out && frame.message.as_ref().is_some_and(|m| m["id"] == 3))
+        .unwrap();
+    ping.message = Some(json!({"jsonrpc": "2.0", "id": 3, "result": {"pong": true}}));
+
+    let report = replay(ZhiServer::new(), &frames).await;
+    assert!(!report.is_success());
+    assert_eq!(report.mismatches.len(), 1);
+    assert_eq!(report.mismatches[0].method, "ping");
+    assert_eq!(report.mismatches[0].differences, vec!["/result/pong: 缺少字段"]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_transport() {
+    use cunzhi_cli::mcp::unix_socket::bind_unix_socket;
+    use serde_json::{json, Value};
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("run").join("mcp.sock");
+
+    let listener = bind_unix_socket(&path).expect("Should bind socket");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600, "Socket should only be accessible by its owner");
+    let dir_mode = fs::metadata(path.parent().unwrap()).unwrap().permissions().mode();
+    assert_eq!(dir_mode & 0o777, 0o700);
+
+    // 套接字在临时目录中创建后移入，目录中不留下临时文件
+    let entries: Vec<_> = fs::read_dir(path.parent().unwrap()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(entries, vec![std::ffi::OsString::from("mcp.sock")]);
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = ZhiServer::new();
+    let serve = tokio::spawn({
+        let server = server.clone();
+        async move { server.serve_unix(listener, async { let _ = shutdown_rx.await; }).await }
+    });
+
+    // 已有服务器监听时拒绝重复绑定
+    assert!(bind_unix_socket(&path).is_err());
+
+    // 每个连接都是独立会话，按行收发 JSON-RPC
+    for _ in 0..2 {
+        let stream = tokio::net::UnixStream::connect(&path).await.expect("Should connect");
+        let (reader, mut writer) = stream.into_split();
+        let requests = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2025-06-18", "capabilities": {}, "clientInfo": {"name": "test", "version": "1"}}}),
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+        ];
+        for request in &requests {
+            writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+
+        let mut lines = BufReader::new(reader).lines();
+        let mut responses = Vec::new();
+        while let Some(line) = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+            .await
+            .expect("Server should answer")
+            .unwrap()
+        {
+            responses.push(serde_json::from_str::<Value>(&line).unwrap());
+        }
+        responses.sort_by_key(|response| response["id"].as_i64());
+        assert_eq!(responses.len(), 2);
+        assert!(responses[0]["result"]["serverInfo"].is_object());
+        assert!(responses[1]["result"]["tools"].is_array());
+    }
+
+    // 关闭时仍连接着的客户端的会话同样被移除
+    let held = tokio::net::UnixStream::connect(&path).await.expect("Should connect");
+    let (reader, mut writer) = held.into_split();
+    let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+    writer.write_all(format!("{}\n", initialize).as_bytes()).await.unwrap();
+    let mut lines = BufReader::new(reader).lines();
+    tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap();
+    assert_eq!(server.sessions().count(), 1);
+
+    // 关闭后删除套接字文件
+    shutdown_tx.send(()).unwrap();
+    tokio::time::timeout(std::time::Duration::from_secs(5), serve)
+        .await
+        .expect("Server should stop on shutdown")
+        .unwrap()
+        .expect("Socket server should finish cleanly");
+    assert!(!path.exists(), "Socket file should be removed on shutdown");
+    assert_eq!(server.sessions().count(), 0, "Sessions of aborted connections should be removed");
+
+    // 残留的套接字文件可以被替换
+    let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    drop(stale);
+    assert!(path.exists());
+    bind_unix_socket(&path).expect("Stale socket file should be replaced");
+}
+
+#[tokio::test]
+async fn test_log_records_forwarded_to_client() {
+    use cunzhi_cli::utils::{init_logger, LogConfig};
+    use log::LevelFilter;
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    // MCP 模式且没有日志文件时，日志只转发给客户端
+    init_logger(LogConfig {
+        level: LevelFilter::Off,
+        file_path: None,
+        is_mcp_mode: true,
+        enable_colors: false,
+    }).unwrap();
+
+    let server = ZhiServer::new();
+    let (client, server_side) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_side);
+    let serve = tokio::spawn(async move { server.serve_lines(server_read, server_write, "test").await });
+
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_read).lines();
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": "logging/setLevel", "params": {"level": "loud"}});
+    client_write.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+    let response: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(response["error"]["code"], -32602);
+
+    let request = json!({"jsonrpc": "2.0", "id": 2, "method": "logging/setLevel", "params": {"level": "info"}});
+    client_write.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+    let response: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(response["result"], json!({}));
+
+    log::debug!("forwarding test: debug record");
+    log::warn!("forwarding test: warn record");
+
+    let message = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        loop {
+            let line = lines.next_line().await.unwrap().expect("Stream should stay open");
+            let message: Value = serde_json::from_str(&line).unwrap();
+            let data = message["params"]["data"].as_str().unwrap_or_default().to_string();
+            assert_ne!(data, "forwarding test: debug record", "Records below the level are not forwarded");
+            if data == "forwarding test: warn record" {
+                return message;
+            }
+        }
+    }).await.expect("Warn record should be forwarded");
+    assert_eq!(message["method"], "notifications/message");
+    assert_eq!(message["params"]["level"], "warning");
+    assert_eq!(message["params"]["logger"], "functional_tests");
+
+    client_write.shutdown().await.unwrap();
+    serve.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_config_changes_notify_clients() {
+    use cunzhi_cli::config::AppConfig;
+    use cunzhi_cli::mcp::{ConfigChange, LiveConfig};
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("config.json");
+    let mut config = AppConfig::default();
+    fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+    let live = LiveConfig::from_path(config_path.clone());
+    let server = ZhiServer::with_config(live.clone());
+    assert!(server.is_tool_enabled("ji"));
+
+    let (client, server_side) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_side);
+    let serve = tokio::spawn({
+        let server = server.clone();
+        async move { server.serve_lines(server_read, server_write, "test").await }
+    });
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_read).lines();
+
+    let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+    client_write.write_all(format!("{}\n", initialize).as_bytes()).await.unwrap();
+    let response: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(response["result"]["capabilities"]["tools"]["listChanged"], true);
+
+    // 内容未变化时不重新加载
+    assert_eq!(live.reload(), None);
+
+    // 禁用 ji 后无需重启即可生效
+    config.mcp_config.tools.insert("ji".to_string(), false);
+    fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+    // 后台轮询可能先一步完成重新加载
+    if let Some(change) = live.reload() {
+        assert_eq!(change, ConfigChange { tools_changed: true, prompts_changed: false });
+    }
+    assert!(!server.is_tool_enabled("ji"));
+
+    let notification: Value = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+        .await
+        .expect("Should notify the client")
+        .unwrap()
+        .map(|line| serde_json::from_str(&line).unwrap())
+        .unwrap();
+    assert_eq!(notification, json!({"jsonrpc": "2.0", "method": "notifications/tools/list_changed"}));
+
+    let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+    client_write.write_all(format!("{}\n", list).as_bytes()).await.unwrap();
+    let response: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    let names: Vec<&str> = response["result"]["tools"].as_array().unwrap()
+        .iter().map(|tool| tool["name"].as_str().unwrap()).collect();
+    assert!(!names.contains(&"ji") && names.contains(&"zhi"), "Disabled tool should disappear: {:?}", names);
+
+    // 格式错误的文件不会替换当前配置
+    fs::write(&config_path, "{ not json").unwrap();
+    assert_eq!(live.reload(), None);
+    assert!(!server.is_tool_enabled("ji"));
+
+    client_write.shutdown().await.unwrap();
+    serve.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_tools_receive_server_config() {
+    use cunzhi_cli::config::AppConfig;
+    use cunzhi_cli::mcp::{CallToolResult, Content, LiveConfig, McpTool, RequestContext, ToolFuture};
+    use serde_json::{json, Value};
+
+    // 工具从请求上下文取得服务器当前的配置，不再读取配置文件
+    struct PromptTool;
+    impl McpTool for PromptTool {
+        fn name(&self) -> &str { "prompt" }
+        fn description(&self) -> &str { "返回继续提示词" }
+        fn input_schema(&self) -> Value { json!({"type": "object"}) }
+        fn call<'a>(&'a self, _arguments: Value, ctx: &'a RequestContext) -> ToolFuture<'a> {
+            let prompt = ctx.config().map(|config| config.reply_config.continue_prompt.clone()).unwrap_or_default();
+            Box::pin(async move { Ok(CallToolResult::success(vec![Content::text(prompt)])) })
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("config.json");
+    let mut config = AppConfig::default();
+    config.reply_config.continue_prompt = "第一版".to_string();
+    fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+    let live = LiveConfig::from_path(config_path.clone());
+    let mut server = ZhiServer::with_config(live.clone());
+    server.register_tool(PromptTool);
+    let ctx = RequestContext::detached();
+    let call = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "prompt"}}).to_string();
+
+    let response: Value = serde_json::from_str(&server.handle_jsonrpc_request(&call, &ctx).await.unwrap()).unwrap();
+    assert_eq!(response["result"]["content"][0]["text"], "第一版");
+
+    config.reply_config.continue_prompt = "第二版".to_string();
+    fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+    live.reload();
+    let response: Value = serde_json::from_str(&server.handle_jsonrpc_request(&call, &ctx).await.unwrap()).unwrap();
+    assert_eq!(response["result"]["content"][0]["text"], "第二版");
+}
+
+#[tokio::test]
+async fn test_request_cancellation() {
+    use cunzhi_cli::mcp::{RequestContext, McpSession};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let session = Arc::new(McpSession::new("test"));
+    let (dropped_tx, dropped_rx) = tokio::sync::oneshot::channel::<()>();
+
+    // 模拟一个一直等待用户回复的请求
+    let handle = session.spawn_request(&json!(7), async move {
+        let _guard = dropped_tx;
+        std::future::pending::<()>().await;
+    });
+    assert_eq!(session.in_flight_count(), 1);
+
+    let server = ZhiServer::new();
+    let ctx = RequestContext::new(session.clone(), None);
+    let notification = json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 7}});
+    assert!(server.handle_jsonrpc_request(&notification.to_string(), &ctx).await.is_none());
+
+    assert!(handle.await.unwrap_err().is_cancelled());
+    assert!(dropped_rx.await.is_err(), "Cancelled task should be dropped");
+    assert_eq!(session.in_flight_count(), 0);
+    assert!(!session.cancel_request(&json!(7)), "Request is no longer in flight");
+}
+
+#[tokio::test]
+async fn test_progress_notifications() {
+    use cunzhi_cli::mcp::{CallToolResult, Content, McpSession, McpTool, RequestContext, ToolFuture};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    // 模拟一个需要等待用户的工具
+    struct SlowTool;
+    impl McpTool for SlowTool {
+        fn name(&self) -> &str { "slow" }
+        fn description(&self) -> &str { "等待一段时间" }
+        fn input_schema(&self) -> Value { json!({"type": "object"}) }
+        fn call<'a>(&'a self, _arguments: Value, ctx: &'a RequestContext) -> ToolFuture<'a> {
+            Box::pin(async move {
+                let wait = tokio::time::sleep(Duration::from_millis(130));
+                ctx.with_progress(Instant::now(), Duration::from_millis(40), |secs| format!("已等待 {} 秒", secs), wait).await;
+                Ok(CallToolResult::success(vec![Content::text("完成".to_string())]))
+            })
+        }
+    }
+
+    let mut server = ZhiServer::new();
+    server.register_tool(SlowTool);
+    let session = Arc::new(McpSession::new("test"));
+
+    // 携带进度令牌时定期发送进度通知
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let ctx = RequestContext::new(session.clone(), Some(tx));
+    let request = json!({
+        "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+        "params": {"name": "slow", "arguments": {}, "_meta": {"progressToken": "token-1"}}
+    });
+    let response: Value = serde_json::from_str(&server.handle_jsonrpc_request(&request.to_string(), &ctx).await.unwrap()).unwrap();
+    assert_eq!(response["result"]["content"][0]["text"], "完成");
+
+    let mut progress = Vec::new();
+    while let Ok(notification) = rx.try_recv() {
+        assert_eq!(notification["method"], "notifications/progress");
+        assert_eq!(notification["params"]["progressToken"], "token-1");
+        assert!(notification["params"]["message"].as_str().unwrap().starts_with("已等待"));
+        progress.push(notification["params"]["progress"].as_f64().unwrap());
+    }
+    assert!(progress.len() >= 2, "Should emit periodic progress, got {:?}", progress);
+    assert!(progress.windows(2).all(|w| w[0] < w[1]), "Progress should increase: {:?}", progress);
+
+    // 未携带进度令牌时不发送通知
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let ctx = RequestContext::new(session, Some(tx));
+    let request = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "slow", "arguments": {}}});
+    assert!(server.handle_jsonrpc_request(&request.to_string(), &ctx).await.is_some());
+    assert!(rx.try_recv().is_err(), "Should not emit progress without a token");
+}
+
+#[tokio::test]
+async fn test_zhi_uses_client_elicitation() {
+    use cunzhi_cli::mcp::{McpSession, RequestContext};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    let server = Arc::new(ZhiServer::new());
+    let session = Arc::new(McpSession::new("test"));
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Value>();
+    let ctx = RequestContext::new(session.clone(), Some(tx));
+
+    let initialize = json!({
+        "jsonrpc": "2.0", "id": 1, "method": "initialize",
+        "params": {"protocolVersion": "2025-06-18", "capabilities": {"elicitation": {}}, "clientInfo": {"name": "test"}}
+    });
+    server.handle_jsonrpc_request(&initialize.to_string(), &ctx).await.unwrap();
+    assert!(session.supports_elicitation());
+
+    let call = json!({
+        "jsonrpc": "2.0", "id": 2, "method": "tools/call",
+        "params": {"name": "zhi", "arguments": {"message": "继续吗？", "predefined_options": ["是", "否"]}}
+    });
+    let task = tokio::spawn({
+        let server = server.clone();
+        let ctx = ctx.clone();
+        async move { server.handle_jsonrpc_request(&call.to_string(), &ctx).await }
+    });
+
+    // 服务器向客户端发起 elicitation 请求
+    let request = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv()).await
+        .expect("Should send elicitation request")
+        .unwrap();
+    assert_eq!(request["method"], "elicitation/create");
+    assert_eq!(request["params"]["message"], "继续吗？");
+    assert_eq!(request["params"]["requestedSchema"]["properties"]["option_1"]["title"], "是");
+    assert_eq!(session.outgoing_count(), 1);
+
+    let reply = json!({"jsonrpc": "2.0", "id": request["id"], "result": {"action": "accept", "content": {"option_1": true}}});
+    assert!(server.handle_jsonrpc_request(&reply.to_string(), &ctx).await.is_none());
+
+    let response: Value = serde_json::from_str(&task.await.unwrap().unwrap()).unwrap();
+    let answer = &response["result"]["structuredContent"];
+    assert_eq!(answer["selected_options"], json!(["是"]));
+    // 文本内容是同一结果的摘要，供不支持结构化结果的客户端使用
+    assert_eq!(response["result"]["content"], json!([{"type": "text", "text": "选择的选项: 是"}]));
+    assert_eq!(answer["metadata"]["source"], "elicitation");
+    assert_eq!(session.outgoing_count(), 0);
+
+    // 无人等待的响应被忽略
+    assert!(server.handle_jsonrpc_request(&reply.to_string(), &ctx).await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_control_socket_answers_pending_zhi() {
+    use cunzhi_cli::mcp::control::{send_control_request, start_control_socket, ControlRequest, ControlResponse};
+    use cunzhi_cli::mcp::metrics::metrics;
+    use cunzhi_cli::mcp::tools::interaction::InteractionTool;
+    use cunzhi_cli::mcp::{McpSession, RequestContext, ZhiRequest};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    let control = start_control_socket().expect("Should start control socket");
+    let server = Arc::new(ZhiServer::new());
+    let ctx = RequestContext::new(Arc::new(McpSession::new("test")), None);
+    let control_waits = || metrics().snapshot().zhi_waits.get("control").map(|waits| waits.count).unwrap_or(0);
+    let waits_before = control_waits();
+
+    let call_zhi = |message: &str| {
+        let call = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": "zhi", "arguments": {"message": message, "predefined_options": ["是", "否"]}}
+        });
+        let server = server.clone();
+        let ctx = ctx.clone();
+        tokio::spawn(async move { server.handle_jsonrpc_request(&call.to_string(), &ctx).await })
+    };
+    let wait_pending = |message: &'static str| {
+        let path = control.path().to_path_buf();
+        async move {
+            for _ in 0..300 {
+                if let ControlResponse::Ok { pending } = send_control_request(&path, &ControlRequest::Pending).await.unwrap() {
+                    if let Some(info) = pending.into_iter().find(|info| info.message == message) {
+                        return info;
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+            panic!("Interaction should be listed as pending");
+        }
+    };
+
+    // 回答：选项必须来自预定义选项
+    let task = call_zhi("控制端回答测试");
+    let info = wait_pending("控制端回答测试").await;
+    assert_eq!(info.predefined_options, vec!["是", "否"]);
+
+    let invalid = ControlRequest::Answer { request_id: info.request_id.clone(), text: None, options: vec!["也许".to_string()] };
+    assert!(matches!(send_control_request(control.path(), &invalid).await.unwrap(), ControlResponse::Error { .. }));
+    let unknown = ControlRequest::Cancel { request_id: "unknown".to_string() };
+    assert!(matches!(send_control_request(control.path(), &unknown).await.unwrap(), ControlResponse::NotFound));
+
+    let answer = ControlRequest::Answer {
+        request_id: info.request_id.clone(),
+        text: Some("小心点".to_string()),
+        options: vec!["否".to_string()],
+    };
+    assert!(matches!(send_control_request(control.path(), &answer).await.unwrap(), ControlResponse::Ok { .. }));
+
+    let response: Value = serde_json::from_str(&task.await.unwrap().unwrap()).unwrap();
+    let answer = &response["result"]["structuredContent"];
+    assert_eq!(answer["selected_options"], json!(["否"]));
+    assert_eq!(answer["user_input"], "小心点");
+    assert_eq!(answer["metadata"]["source"], "control");
+    assert_eq!(answer["metadata"]["request_id"], info.request_id.as_str());
+
+    // 回答后不再列出，重复回答找不到
+    let again = ControlRequest::Cancel { request_id: info.request_id.clone() };
+    assert!(matches!(send_control_request(control.path(), &again).await.unwrap(), ControlResponse::NotFound));
+
+    // 取消
+    let task = call_zhi("控制端取消测试");
+    let info = wait_pending("控制端取消测试").await;
+    let cancel = ControlRequest::Cancel { request_id: info.request_id };
+    assert!(matches!(send_control_request(control.path(), &cancel).await.unwrap(), ControlResponse::Ok { .. }));
+
+    let response: Value = serde_json::from_str(&task.await.unwrap().unwrap()).unwrap();
+    assert_eq!(response["result"]["structuredContent"]["user_input"], "用户取消了操作");
+
+    // 两次交互都记为经由控制套接字获得回复
+    assert!(control_waits() >= waits_before + 2, "Control replies should be recorded as interaction waits");
+
+    // 等待控制端回复不超过终端交互的超时时间
+    let mut config = AppConfig::default();
+    config.terminal_config.timeout_seconds = 1;
+    let request = ZhiRequest {
+        message: "控制端超时测试".to_string(),
+        predefined_options: vec![],
+        is_markdown: false,
+        terminal_mode: None,
+    };
+    let result = tokio::time::timeout(
+        std::time::Duration::from_secs(30),
+        InteractionTool::zhi_with_context(request, &ctx.clone().with_config(config)),
+    )
+    .await
+    .expect("Waiting for a control reply should time out");
+    let error = result.expect_err("Unanswered interaction should fail");
+    assert!(error.message().contains("超时"), "Unexpected error: {}", error.message());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_guard_pauses_risky_tool_calls() {
+    use cunzhi_cli::config::{GuardConfig, GuardRule};
+    use cunzhi_cli::mcp::control::{send_control_request, start_control_socket, ControlRequest, ControlResponse};
+    use cunzhi_cli::mcp::guard::{AuditDecision, AuditEntry, Guard};
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let control = start_control_socket().expect("Should start control socket");
+    let temp_dir = TempDir::new().unwrap();
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project).unwrap();
+    let project = project.to_string_lossy().to_string();
+    let audit_log = temp_dir.path().join("audit.jsonl");
+
+    let config = GuardConfig {
+        rules: vec![GuardRule {
+            tool: "ji".to_string(),
+            argument: Some("project_path".to_string()),
+            pattern: None,
+            outside_project: true,
+            reason: None,
+        }],
+        audit_log: None,
+        approval_timeout_seconds: 300,
+    };
+    let guard = Guard::new(&config, std::path::Path::new(&project), &audit_log, "cunzhi mcp-server".to_string()).unwrap();
+    let command = vec![env!("CARGO_BIN_EXE_cunzhi").to_string(), "mcp-server".to_string()];
+
+    let (client, guard_io) = tokio::io::duplex(64 * 1024);
+    let (guard_input, guard_output) = tokio::io::split(guard_io);
+    let task = tokio::spawn(async move { guard.run(&command, guard_input, guard_output).await });
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_read).lines();
+
+    async fn send(writer: &mut tokio::io::WriteHalf<tokio::io::DuplexStream>, message: Value) {
+        writer.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+    }
+    async fn response(lines: &mut tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>, id: u64) -> Value {
+        loop {
+            let line = tokio::time::timeout(std::time::Duration::from_secs(30), lines.next_line()).await
+                .expect("Guard should respond").unwrap().expect("Guard should not close the stream");
+            let message: Value = serde_json::from_str(&line).unwrap();
+            if message["id"] == id {
+                return message;
+            }
+        }
+    }
+    let wait_pending = |marker: String| {
+        let path = control.path().to_path_buf();
+        async move {
+            for _ in 0..300 {
+                if let ControlResponse::Ok { pending } = send_control_request(&path, &ControlRequest::Pending).await.unwrap() {
+                    if let Some(info) = pending.into_iter().find(|info| info.message.contains(&marker)) {
+                        return info;
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+            panic!("Guarded call should be waiting for approval");
+        }
+    };
+    let call = |id: u64, project_path: &str| json!({
+        "jsonrpc": "2.0", "id": id, "method": "tools/call",
+        "params": {"name": "ji", "arguments": {"action": "回忆", "project_path": project_path}}
+    });
+
+    send(&mut client_write, json!({
+        "jsonrpc": "2.0", "id": 1, "method": "initialize",
+        "params": {"protocolVersion": "2025-06-18", "capabilities": {}, "clientInfo": {"name": "test", "version": "0"}}
+    })).await;
+    assert_eq!(response(&mut lines, 1).await["result"]["serverInfo"]["name"], "cunzhi-cli");
+
+    // 不匹配规则的调用直接转发
+    send(&mut client_write, call(2, &project)).await;
+    assert_ne!(response(&mut lines, 2).await["result"]["isError"], true);
+
+    // 拒绝：原因作为工具错误返回，不转发给下游
+    let outside = temp_dir.path().join("elsewhere").to_string_lossy().to_string();
+    send(&mut client_write, call(3, &outside)).await;
+    let info = wait_pending("工具调用需要批准: ji".to_string()).await;
+    assert_eq!(info.predefined_options, vec!["批准", "拒绝"]);
+    let deny = ControlRequest::Answer { request_id: info.request_id, text: Some("路径不对".to_string()), options: vec!["拒绝".to_string()] };
+    assert!(matches!(send_control_request(control.path(), &deny).await.unwrap(), ControlResponse::Ok { .. }));
+    let denied = response(&mut lines, 3).await;
+    assert_eq!(denied["result"]["isError"], true);
+    assert!(denied["result"]["content"][0]["text"].as_str().unwrap().contains("路径不对"), "{}", denied);
+
+    // 修改参数：不选择选项，按用户输入的参数转发
+    send(&mut client_write, call(4, &outside)).await;
+    let info = wait_pending("工具调用需要批准: ji".to_string()).await;
+    let edit = ControlRequest::Answer {
+        request_id: info.request_id,
+        text: Some(json!({"action": "回忆", "project_path": project}).to_string()),
+        options: vec![],
+    };
+    assert!(matches!(send_control_request(control.path(), &edit).await.unwrap(), ControlResponse::Ok { .. }));
+    assert_ne!(response(&mut lines, 4).await["result"]["isError"], true);
+
+    // 客户端关闭后网关结束
+    client_write.shutdown().await.unwrap();
+    drop(client_write);
+    tokio::time::timeout(std::time::Duration::from_secs(30), task).await
+        .expect("Guard should exit after the client disconnects").unwrap().unwrap();
+
+    let entries: Vec<AuditEntry> = fs::read_to_string(&audit_log).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2, "Every decision should be audited");
+    assert_eq!(entries[0].decision, AuditDecision::Denied);
+    assert_eq!(entries[0].arguments["project_path"], outside.as_str());
+    assert_eq!(entries[1].decision, AuditDecision::Edited);
+    assert_eq!(entries[1].forwarded_arguments.as_ref().unwrap()["project_path"], project.as_str());
+    assert_eq!(entries[1].source.as_deref(), Some("control"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_guard_approval_timeout_denies() {
+    use cunzhi_cli::config::{GuardConfig, GuardRule};
+    use cunzhi_cli::mcp::control::start_control_socket;
+    use cunzhi_cli::mcp::guard::{AuditDecision, AuditEntry, Guard, GuardDecision};
+    use serde_json::json;
+
+    // 控制套接字在运行时交互会一直等待回复，超过审批超时视为拒绝
+    let _control = start_control_socket().expect("Should start control socket");
+    let temp_dir = TempDir::new().unwrap();
+    let audit_log = temp_dir.path().join("audit.jsonl");
+    let rule = GuardRule { tool: "*".to_string(), argument: None, pattern: None, outside_project: false, reason: None };
+    let config = GuardConfig { rules: vec![rule.clone()], audit_log: None, approval_timeout_seconds: 1 };
+    let guard = Guard::new(&config, temp_dir.path(), &audit_log, "test".to_string()).unwrap();
+
+    let decision = tokio::time::timeout(std::time::Duration::from_secs(30), guard.review("delete_file", &json!({"path": "a"}), &rule))
+        .await
+        .expect("Review should stop at the approval timeout");
+    match decision {
+        GuardDecision::Deny { reason } => assert!(reason.contains("超时"), "{}", reason),
+        other => panic!("Timed out review should deny: {:?}", other),
+    }
+
+    let entries: Vec<AuditEntry> = fs::read_to_string(&audit_log).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].decision, AuditDecision::TimedOut);
+    assert!(entries[0].reason.as_deref().unwrap().contains("超时"));
+}
+
+#[tokio::test]
+async fn test_argument_completion() {
+    use cunzhi_cli::mcp::tools::memory::resources::percent_encode;
+    use cunzhi_cli::mcp::RequestContext;
+    use serde_json::{json, Value};
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    for dir in ["alpha", "beta", ".hidden"] {
+        fs::create_dir(root.join(dir)).unwrap();
+    }
+    let root_text = root.to_string_lossy().to_string();
+    let beta = root.join("beta").to_string_lossy().to_string();
+
+    let server = ZhiServer::new();
+    // 写入记忆后 beta 成为已有记忆存储的项目
+    server.call_tool("ji", json!({"action": "记忆", "project_path": beta, "content": "补全测试", "category": "rule"}))
+        .await.unwrap();
+
+    let ctx = RequestContext::detached();
+    let complete = |reference: Value, argument: &str, value: &str| {
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "completion/complete",
+            "params": {"ref": reference, "argument": {"name": argument, "value": value}}
+        });
+        let server = &server;
+        let ctx = &ctx;
+        async move {
+            let response: Value = serde_json::from_str(&server.handle_jsonrpc_request(&request.to_string(), ctx).await.unwrap()).unwrap();
+            response
+        }
+    };
+    let values = |response: &Value| -> Vec<String> {
+        serde_json::from_value(response["result"]["completion"]["values"].clone()).expect("Completion values should be strings")
+    };
+    let ji = json!({"type": "ref/tool", "name": "ji"});
+
+    // 取值来自 inputSchema 中的 enum
+    assert_eq!(values(&complete(ji.clone(), "action", "").await), vec!["记忆", "回忆"]);
+    assert_eq!(values(&complete(ji.clone(), "action", "回").await), vec!["回忆"]);
+    assert_eq!(values(&complete(json!({"type": "ref/tool", "name": "ji_cunzhi"}), "category", "p").await), vec!["preference", "pattern"]);
+    assert_eq!(values(&complete(json!({"type": "ref/tool", "name": "zhi"}), "is_markdown", "t").await), vec!["true"]);
+
+    // 项目路径：已有记忆的项目在前，然后是同级目录（不含隐藏目录）
+    let response = complete(ji.clone(), "project_path", &format!("{}/", root_text)).await;
+    let paths = values(&response);
+    assert_eq!(paths.first(), Some(&beta), "{:?}", paths);
+    assert!(paths.contains(&format!("{}/alpha", root_text)), "{:?}", paths);
+    assert!(!paths.iter().any(|path| path.ends_with(".hidden")), "{:?}", paths);
+    assert_eq!(response["result"]["completion"]["hasMore"], false);
+    assert_eq!(values(&complete(ji.clone(), "project_path", &format!("{}/al", root_text)).await), vec![format!("{}/alpha", root_text)]);
+    assert_eq!(values(&complete(ji.clone(), "project_path", &format!("{}/.h", root_text)).await), vec![format!("{}/.hidden", root_text)]);
+
+    // 主目录下的补全结果以 `~/` 开头，可以直接作为 ji 的 project_path
+    let home = dirs::home_dir().expect("Tests need a home directory");
+    let in_home = tempfile::Builder::new().prefix(".cunzhi-completion-").tempdir_in(&home).unwrap();
+    let name = in_home.path().file_name().unwrap().to_string_lossy().to_string();
+    let suggested = values(&complete(ji.clone(), "project_path", "~/.cunzhi-completion-").await);
+    let tilde_path = format!("~/{}", name);
+    assert!(suggested.contains(&tilde_path), "{:?}", suggested);
+    let stored = server.call_tool("ji", json!({"action": "记忆", "project_path": tilde_path, "content": "主目录补全", "category": "rule"}))
+        .await
+        .expect("ji should accept `~/` paths");
+    assert_ne!(stored.is_error, Some(true));
+    let recalled = server.call_tool("ji", json!({"action": "回忆", "project_path": tilde_path})).await.unwrap();
+    assert!(recalled.content[0].as_text().unwrap().contains("主目录补全"));
+
+    // 记忆资源模板
+    let template = json!({"type": "ref/resource", "uri": "cunzhi-memory://{project}/{category}"});
+    assert_eq!(values(&complete(template.clone(), "category", "r").await), vec!["rule"]);
+    assert!(values(&complete(template.clone(), "project", &beta).await).contains(&percent_encode(&beta)));
+
+    let unknown = complete(json!({"type": "ref/tool", "name": "no_such_tool"}), "x", "").await;
+    assert_eq!(unknown["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn test_tool_call_metrics() {
+    use cunzhi_cli::mcp::control::{handle_control_request, ControlRequest, ControlResponse};
+    use cunzhi_cli::mcp::metrics::{metrics, MetricsSnapshot};
+    use serde_json::json;
+
+    // 指标是进程级的，其它测试可能同时调用工具，只比较增量
+    let count = |snapshot: &MetricsSnapshot, outcome: &str| {
+        snapshot.tool_calls.get("ji").and_then(|outcomes| outcomes.get(outcome)).copied().unwrap_or(0)
+    };
+    let writes = |snapshot: &MetricsSnapshot| snapshot.memory_writes.get("rule").copied().unwrap_or(0);
+    let before = metrics().snapshot();
+
+    let project_dir = TempDir::new().unwrap();
+    let project = project_dir.path().to_string_lossy().to_string();
+    let server = ZhiServer::new();
+    let result = server.call_tool("ji", json!({"action": "记忆", "project_path": project, "content": "使用 4 空格缩进", "category": "rule"}))
+        .await.unwrap();
+    assert_ne!(result.is_error, Some(true));
+    server.call_tool("ji", json!({"project_path": project})).await.unwrap();
+    server.call_tool("ji_cunzhi", json!({"action": "回忆", "project_path": "/definitely/not/here"})).await.unwrap();
+
+    let after = metrics().snapshot();
+    assert!(count(&after, "success") > count(&before, "success"));
+    assert!(count(&after, "invalid_arguments") > count(&before, "invalid_arguments"));
+    assert!(count(&after, "error") > count(&before, "error"), "Aliases should be counted under the tool name");
+    assert!(writes(&after) > writes(&before));
+
+    // 控制套接字返回同样的指标
+    match handle_control_request(ControlRequest::Metrics) {
+        ControlResponse::Metrics { metrics } => assert!(count(&metrics, "success") >= count(&after, "success")),
+        _ => panic!("Control socket should return metrics"),
+    }
+}
+
+#[tokio::test]
+async fn test_tool_registry() {
+    use cunzhi_cli::mcp::{CallToolResult, Content, McpTool, RequestContext, ToolFuture};
+    use serde_json::{json, Value};
+
+    // 内置工具的参数 Schema 由请求结构体生成
+    let server = ZhiServer::new();
+    let tools = server.list_tools();
+    let zhi = tools.iter().find(|t| t.name == "zhi").expect("Should have zhi tool");
+    assert_eq!(zhi.input_schema["type"], "object");
+    assert_eq!(zhi.input_schema["required"], json!(["message"]));
+    assert_eq!(zhi.input_schema["properties"]["is_markdown"]["default"], true);
+    assert!(zhi.input_schema["properties"]["message"]["description"].is_string());
+    let output_schema = zhi.output_schema.as_ref().expect("zhi should declare an output schema");
+    assert_eq!(output_schema["required"], json!(["images", "metadata", "selected_options"]));
+    assert_eq!(output_schema["properties"]["metadata"]["properties"]["source"]["type"], json!(["string", "null"]));
+
+    let ji = tools.iter().find(|t| t.name == "ji_cunzhi").expect("Should have ji alias");
+    assert_eq!(ji.input_schema["properties"]["action"]["enum"], json!(["记忆", "回忆"]));
+    assert_eq!(ji.input_schema["properties"]["category"]["default"], "context");
+
+    // 注册自定义工具无需修改服务器
+    struct EchoTool;
+    impl McpTool for EchoTool {
+        fn name(&self) -> &str { "echo" }
+        fn description(&self) -> &str { "回显参数" }
+        fn input_schema(&self) -> Value { json!({"type": "object"}) }
+        fn call<'a>(&'a self, arguments: Value, _ctx: &'a RequestContext) -> ToolFuture<'a> {
+            Box::pin(async move { Ok(CallToolResult::success(vec![Content::text(arguments.to_string())])) })
+        }
+    }
+
+    let mut server = ZhiServer::new();
+    server.register_tool(EchoTool);
+    let ctx = RequestContext::detached();
+
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"});
+    let response: Value = serde_json::from_str(&server.handle_jsonrpc_request(&request.to_string(), &ctx).await.unwrap()).unwrap();
+    let listed: Vec<&str> = response["result"]["tools"].as_array().unwrap()
+        .iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert!(listed.contains(&"echo"));
+    assert!(listed.contains(&"zhi_cunzhi"));
+
+    let result = server.call_tool("echo", json!({"x": 1})).await.expect("Custom tool should be callable");
+    assert_eq!(result.content[0].as_text(), Some(r#"{"x":1}"#));
+}
+
+#[tokio::test]
+async fn test_tool_errors_are_reported_as_results() {
+    use cunzhi_cli::mcp::RequestContext;
+    use serde_json::{json, Value};
+
+    let server = ZhiServer::new();
+    let ctx = RequestContext::detached();
+    let call = |name: &str, arguments: Value| {
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": name, "arguments": arguments}
+        });
+        let server = server.clone();
+        let ctx = ctx.clone();
+        async move {
+            let response = server.handle_jsonrpc_request(&request.to_string(), &ctx).await.unwrap();
+            serde_json::from_str::<Value>(&response).unwrap()
+        }
+    };
+
+    // 参数校验错误指出出错的字段
+    let response = call("ji", json!({"project_path": "/tmp"})).await;
+    assert_eq!(response["result"]["isError"], true);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("action"), "Error should name the missing field: {}", text);
+
+    let response = call("zhi_cunzhi", json!({"message": "hi", "predefined_options": ["a", 2]})).await;
+    assert_eq!(response["result"]["isError"], true);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("predefined_options[1]"), "Error should point at the array item: {}", text);
+
+    let response = call("ji", json!({"action": "删除", "project_path": "/tmp"})).await;
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("action") && text.contains("回忆"), "Error should list allowed values: {}", text);
+
+    // 工具执行失败同样以 isError 结果返回，而不是 JSON-RPC 错误
+    let response = call("ji", json!({"action": "回忆", "project_path": "/definitely/not/here"})).await;
+    assert!(response.get("error").is_none());
+    assert_eq!(response["result"]["isError"], true);
+    assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("项目路径不存在"));
+}
+
+#[tokio::test]
+async fn test_memory_resources_and_subscriptions() {
+    use cunzhi_cli::mcp::tools::memory::resources::MemoryUri;
+    use cunzhi_cli::mcp::tools::memory::MemoryCategory;
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let project_dir = TempDir::new().unwrap();
+    let project = project_dir.path().canonicalize().unwrap().to_string_lossy().to_string();
+    let rules_uri = MemoryUri::Category { project: project.clone(), category: MemoryCategory::Rule }.to_string();
+    let project_uri = MemoryUri::Project { project: project.clone() }.to_string();
+    assert_eq!(MemoryUri::parse(&rules_uri), Some(MemoryUri::Category { project: project.clone(), category: MemoryCategory::Rule }));
+
+    let server = ZhiServer::new();
+    let (client, server_side) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_side);
+    let serve = tokio::spawn(async move { server.serve_lines(server_read, server_write, "test").await });
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_read).lines();
+
+    async fn send(writer: &mut tokio::io::WriteHalf<tokio::io::DuplexStream>, request: Value) {
+        writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+    }
+    // 读取消息直到满足条件
+    async fn next_matching(lines: &mut tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>, pred: impl Fn(&Value) -> bool) -> Value {
+        loop {
+            let line = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+                .await.expect("Expected message in time").unwrap().expect("Stream should stay open");
+            let message: Value = serde_json::from_str(&line).unwrap();
+            if pred(&message) {
+                return message;
+            }
+        }
+    }
+
+    send(&mut client_write, json!({"jsonrpc": "2.0", "id": 1, "method": "resources/subscribe", "params": {"uri": rules_uri}})).await;
+    next_matching(&mut lines, |m| m["id"] == 1).await;
+
+    send(&mut client_write, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {
+        "name": "ji", "arguments": {"action": "记忆", "project_path": project, "content": "使用中文注释", "category": "rule"}
+    }})).await;
+
+    // 通知和响应的先后顺序不确定
+    let (mut updated, mut result) = (None, None);
+    while updated.is_none() || result.is_none() {
+        let message = next_matching(&mut lines, |m| m["method"] == "notifications/resources/updated" || m["id"] == 2).await;
+        if message["id"] == 2 {
+            result = Some(message);
+        } else {
+            updated = Some(message);
+        }
+    }
+    assert_eq!(updated.unwrap()["params"]["uri"], rules_uri);
+    let result = result.unwrap();
+    assert!(result["result"]["isError"].is_null(), "ji should succeed: {}", result);
+
+    send(&mut client_write, json!({"jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": {"uri": project_uri}})).await;
+    let read = next_matching(&mut lines, |m| m["id"] == 3).await;
+    assert!(read["result"]["contents"][0]["text"].as_str().unwrap().contains("使用中文注释"));
+
+    send(&mut client_write, json!({"jsonrpc": "2.0", "id": 4, "method": "resources/list"})).await;
+    let list = next_matching(&mut lines, |m| m["id"] == 4).await;
+    let uris: Vec<&str> = list["result"]["resources"].as_array().unwrap()
+        .iter().map(|r| r["uri"].as_str().unwrap()).collect();
+    assert!(uris.contains(&project_uri.as_str()));
+    let entry_uri = uris.iter().find(|uri| uri.starts_with(&format!("{}/", rules_uri))).expect("Entry should be listed").to_string();
+
+    send(&mut client_write, json!({"jsonrpc": "2.0", "id": 5, "method": "resources/read", "params": {"uri": entry_uri}})).await;
+    let read = next_matching(&mut lines, |m| m["id"] == 5).await;
+    assert_eq!(read["result"]["contents"][0]["text"], "使用中文注释");
+
+    send(&mut client_write, json!({"jsonrpc": "2.0", "id": 6, "method": "resources/read", "params": {"uri": format!("{}/missing", rules_uri)}})).await;
+    let missing = next_matching(&mut lines, |m| m["id"] == 6).await;
+    assert_eq!(missing["error"]["code"], -32002);
+
+    send(&mut client_write, json!({"jsonrpc": "2.0", "id": 7, "method": "resources/templates/list"})).await;
+    let templates = next_matching(&mut lines, |m| m["id"] == 7).await;
+    assert_eq!(templates["result"]["resourceTemplates"].as_array().unwrap().len(), 3);
+
+    client_write.shutdown().await.unwrap();
+    serve.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_client_roots_default_project() {
+    use cunzhi_cli::mcp::{McpSession, RequestContext};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    let helper = TestHelper::new();
+    helper.set_config_env();
+    let root = helper.temp_dir.path().join("my app");
+    fs::create_dir_all(&root).unwrap();
+    let other = helper.temp_dir.path().join("other");
+    fs::create_dir_all(&other).unwrap();
+
+    let server = Arc::new(ZhiServer::new());
+    let session = Arc::new(McpSession::new("test"));
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Value>();
+    let ctx = RequestContext::new(session.clone(), Some(tx));
+
+    let initialize = json!({
+        "jsonrpc": "2.0", "id": 1, "method": "initialize",
+        "params": {"protocolVersion": "2025-06-18", "capabilities": {"roots": {"listChanged": true}}, "clientInfo": {"name": "test"}}
+    });
+    server.handle_jsonrpc_request(&initialize.to_string(), &ctx).await.unwrap();
+    assert!(session.supports_roots());
+
+    // 初始化完成后服务器向客户端请求根目录
+    let initialized = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+    assert!(server.handle_jsonrpc_request(&initialized.to_string(), &ctx).await.is_none());
+    /// 回答服务器的 roots/list 请求，并等待会话缓存更新
+    async fn answer_roots(server: &ZhiServer, ctx: &RequestContext, requests: &mut UnboundedReceiver<Value>, uris: &[String]) {
+        let request = tokio::time::timeout(Duration::from_secs(5), requests.recv()).await
+            .expect("Should request roots/list")
+            .unwrap();
+        assert_eq!(request["method"], "roots/list");
+        let roots: Vec<Value> = uris.iter().map(|uri| json!({"uri": uri, "name": "项目"})).collect();
+        let reply = json!({"jsonrpc": "2.0", "id": request["id"], "result": {"roots": roots}});
+        assert!(server.handle_jsonrpc_request(&reply.to_string(), ctx).await.is_none());
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while ctx.session().roots().is_none() {
+            assert!(tokio::time::Instant::now() < deadline, "Timed out waiting for roots");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    let root_uri = format!("file://{}", root.to_string_lossy().replace(' ', "%20"));
+    answer_roots(&server, &ctx, &mut rx, std::slice::from_ref(&root_uri)).await;
+    assert_eq!(session.roots().unwrap(), vec![root.clone()]);
+
+    // 只有一个根目录时可以省略 project_path
+    let call = json!({
+        "jsonrpc": "2.0", "id": 2, "method": "tools/call",
+        "params": {"name": "ji", "arguments": {"action": "记忆", "content": "根目录即项目", "category": "rule"}}
+    });
+    let response: Value = serde_json::from_str(&server.handle_jsonrpc_request(&call.to_string(), &ctx).await.unwrap()).unwrap();
+    assert_ne!(response["result"]["isError"], true, "ji should use the single root: {}", response);
+    assert!(root.join(".cunzhi-memory").is_dir());
+
+    // 根目录变化后缓存失效并重新获取
+    let changed = json!({"jsonrpc": "2.0", "method": "notifications/roots/list_changed"});
+    assert!(server.handle_jsonrpc_request(&changed.to_string(), &ctx).await.is_none());
+    assert!(session.roots().is_none());
+    let uris = [root_uri, format!("file://{}", other.display()), "https://example.com/repo".to_string()];
+    answer_roots(&server, &ctx, &mut rx, &uris).await;
+    assert_eq!(session.roots().unwrap(), vec![root.clone(), other.clone()]);
+
+    // 有多个根目录时必须指定项目
+    let call = json!({
+        "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+        "params": {"name": "ji", "arguments": {"action": "回忆"}}
+    });
+    let response: Value = serde_json::from_str(&server.handle_jsonrpc_request(&call.to_string(), &ctx).await.unwrap()).unwrap();
+    assert_eq!(response["result"]["isError"], true);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("多个根目录") && text.contains(&other.to_string_lossy().to_string()), "{}", text);
+
+    // 不支持 roots 的客户端不会收到请求
+    let plain = Arc::new(McpSession::new("test"));
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Value>();
+    let plain_ctx = RequestContext::new(plain, Some(tx));
+    server.handle_jsonrpc_request(&initialize.to_string().replace("\"roots\"", "\"sampling\""), &plain_ctx).await.unwrap();
+    server.handle_jsonrpc_request(&initialized.to_string(), &plain_ctx).await;
+    assert!(plain_ctx.roots().await.is_empty());
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_idle_http_sessions_expire() {
+    use cunzhi_cli::mcp::SessionManager;
+    use std::time::Duration;
+
+    let sessions = SessionManager::new();
+    let idle = sessions.create("http");
+    let streaming = sessions.create("http");
+    let (push, messages) = tokio::sync::mpsc::unbounded_channel();
+    streaming.attach_push(push);
+    let stdio = sessions.create("stdio");
+
+    // 最近访问过的会话不会过期
+    assert_eq!(sessions.expire_idle("http", Duration::from_secs(60)), 0);
+    assert_eq!(sessions.count(), 3);
 
-    // 测试停止未启动的服务器
-    let new_server = ZhiServer::new();
-    let _stop_result = new_server.stop().await;
-    // 停止未启动的服务器可能成功或失败，但不应该崩溃
+    // 打开推送流的会话和其它传输方式的会话保留
+    assert_eq!(sessions.expire_idle("http", Duration::ZERO), 1);
+    assert!(sessions.get(idle.id()).is_none());
+    assert!(sessions.get(streaming.id()).is_some());
+    assert!(sessions.get(stdio.id()).is_some());
 
-    // 测试获取状态
-    let status = new_server.status().await;
-    assert!(status.is_ok(), "Should always be able to get status");
+    // 客户端断开推送流后同样过期
+    drop(messages);
+    assert_eq!(sessions.expire_idle("http", Duration::ZERO), 1);
+    assert_eq!(sessions.count(), 1);
 }
 
 #[test]
@@ -142,21 +142,72 @@ fn test_server_lifecycle() {
     let init_output = helper.run_command(&["init", "--name", "test-project", "--yes"]);
     assert!(init_output.status.success(), "Init should succeed");
 
-    // 启动服务器
-    let start_output = helper.run_command(&["server", "start"]);
+    // 启动后台服务器
+    let start_output = helper.run_command(&["server", "start", "--daemon"]);
+    // 测试失败时也要停止后台进程
+    let _stop_on_exit = StopServerOnDrop(&helper);
     assert!(start_output.status.success(), "Server start should succeed");
 
     let start_stdout = String::from_utf8_lossy(&start_output.stdout);
     assert!(start_stdout.contains("启动成功") || start_stdout.contains("已启动"),
            "Start output should indicate success");
+    assert!(helper.temp_dir.path().join("server.pid").exists(), "PID file should exist while running");
 
     // 检查状态
     let status_output = helper.run_command(&["server", "status"]);
     assert!(status_output.status.success(), "Server status should succeed");
+    let status_stdout = String::from_utf8_lossy(&status_output.stdout);
+    assert!(status_stdout.contains("运行中"), "Status should report the running daemon");
+    assert!(status_stdout.contains("PID") && status_stdout.contains("端口"), "Status should show PID and port");
+    assert!(status_stdout.contains("活动会话: 0"), "Status should report active sessions");
+
+    // 重复启动不会再启动新进程
+    let again_output = helper.run_command(&["server", "start", "--daemon"]);
+    assert!(again_output.status.success(), "Starting twice should succeed");
+    assert!(String::from_utf8_lossy(&again_output.stdout).contains("已在运行"));
+
+    // 重启沿用原端口
+    let port_line = |stdout: &str| stdout.lines().find(|line| line.contains("端口:")).map(str::to_string);
+    let restart_output = helper.run_command(&["server", "restart"]);
+    assert!(restart_output.status.success(), "Server restart should succeed");
+    assert_eq!(port_line(&String::from_utf8_lossy(&restart_output.stdout)), port_line(&status_stdout));
 
     // 停止服务器
     let stop_output = helper.run_command(&["server", "stop"]);
     assert!(stop_output.status.success(), "Server stop should succeed");
+    assert!(String::from_utf8_lossy(&stop_output.stderr).contains("已停止"));
+    assert!(!helper.temp_dir.path().join("server.pid").exists(), "PID file should be removed after stop");
+
+    let status_output = helper.run_command(&["server", "status"]);
+    assert!(String::from_utf8_lossy(&status_output.stdout).contains("未运行"));
+
+    // 未运行时停止也应成功
+    let stop_output = helper.run_command(&["server", "stop"]);
+    assert!(stop_output.status.success(), "Stopping a stopped server should succeed");
+}
+
+/// 离开作用域时停止测试启动的后台服务器
+struct StopServerOnDrop<'a>(&'a TestHelper);
+
+impl Drop for StopServerOnDrop<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.run_command(&["server", "stop"]);
+    }
+}
+
+#[test]
+fn test_mcp_probe_command() {
+    let helper = TestHelper::new();
+    // 未指定目标时启动本机的 mcp-server；标准输入为空，列出能力后退出
+    let output = helper.run_command(&["mcp", "probe"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Probe should succeed: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("已连接 cunzhi-cli"), "Probe should report server info");
+    assert!(stdout.contains("zhi - "), "Probe should list tools");
+    assert!(stdout.contains("ji - "), "Probe should list tools");
+    assert!(stdout.contains("cunzhi-memory://{project}"), "Probe should list resource templates");
+    assert!(stdout.contains("review - "), "Probe should list prompts");
 }
 
 #[test]
@@ -258,7 +309,7 @@ fn test_command_help_subcommands() {
         assert!(output.status.success(), "Help for {} should succeed", cmd);
 
         let stdout = String::from_utf8_lossy(&output.stdout);
-        assert!(stdout.len() > 0, "Help for {} should produce output", cmd);
+        assert!(!stdout.is_empty(), "Help for {} should produce output", cmd);
     }
 }
 
@@ -292,3 +343,510 @@ fn test_concurrent_operations() {
         handle.join().expect("Thread should complete successfully");
     }
 }
+
+// HTTP 传输测试 - 在本地端口上启动真实的 MCP 服务器
+mod http_transport {
+    use cunzhi_cli::mcp::ZhiServer;
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Bytes;
+    use hyper::{Method, Request, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use serde_json::{json, Value};
+    use std::net::SocketAddr;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::oneshot;
+
+    /// 在随机端口上启动服务器，返回地址和关闭句柄
+    async fn spawn_server() -> (SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+        spawn_server_with(ZhiServer::new()).await
+    }
+
+    async fn spawn_server_with(server: ZhiServer) -> (SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Should bind local port");
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let handle = tokio::spawn(async move {
+            server
+                .serve_http(listener, async {
+                    let _ = shutdown_rx.await;
+                })
+                .await
+                .expect("HTTP server should shut down cleanly");
+        });
+
+        (addr, shutdown_tx, handle)
+    }
+
+    async fn send(addr: SocketAddr, method: Method, path: &str, body: Option<Value>) -> (StatusCode, String) {
+        let (status, _, body) = send_with_headers(addr, method, path, &[], body).await;
+        (status, body)
+    }
+
+    async fn send_with_headers(
+        addr: SocketAddr,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> (StatusCode, hyper::HeaderMap, String) {
+        let stream = TcpStream::connect(addr).await.expect("Should connect to server");
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .expect("HTTP handshake should succeed");
+        tokio::spawn(conn);
+
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(format!("http://{}{}", addr, path))
+            .header("Host", addr.to_string());
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let body = match body {
+            Some(value) => {
+                builder = builder.header("Content-Type", "application/json");
+                Full::new(Bytes::from(value.to_string()))
+            }
+            None => Full::new(Bytes::new()),
+        };
+
+        let response = sender.send_request(builder.body(body).unwrap()).await.expect("Request should succeed");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, headers, String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    /// 从 SSE 响应体中提取所有 data 负载
+    fn sse_messages(body: &str) -> Vec<Value> {
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str(data).expect("SSE data should be JSON"))
+            .collect()
+    }
+
+    fn initialize_request() -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {"protocolVersion": "2025-03-26", "capabilities": {}, "clientInfo": {"name": "test", "version": "0"}}
+        })
+    }
+
+    /// 发送 initialize 创建会话，返回会话 ID
+    async fn open_session(addr: SocketAddr) -> String {
+        let (status, headers, body) = send_with_headers(addr, Method::POST, "/mcp", &[], Some(initialize_request())).await;
+        assert_eq!(status, StatusCode::OK, "initialize should succeed: {}", body);
+        headers["mcp-session-id"].to_str().unwrap().to_string()
+    }
+
+    async fn post_jsonrpc(addr: SocketAddr, session_id: &str, request: Value) -> Value {
+        let (status, _, body) = send_with_headers(addr, Method::POST, "/mcp", &[("Mcp-Session-Id", session_id)], Some(request)).await;
+        assert_eq!(status, StatusCode::OK, "JSON-RPC request should succeed: {}", body);
+        serde_json::from_str(&body).expect("Response should be valid JSON")
+    }
+
+    #[tokio::test]
+    async fn test_http_initialize_and_list_tools() {
+        let (addr, shutdown, handle) = spawn_server().await;
+
+        let (status, headers, body) = send_with_headers(addr, Method::POST, "/mcp", &[], Some(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {"protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": {"name": "test", "version": "0"}}
+        }))).await;
+        assert_eq!(status, StatusCode::OK);
+        let session_id = headers["mcp-session-id"].to_str().unwrap().to_string();
+        let init: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(init["id"], 1);
+        assert_eq!(init["result"]["serverInfo"]["name"], "cunzhi-cli");
+        assert!(init["result"]["capabilities"]["completions"].is_object());
+
+        let tools = post_jsonrpc(addr, &session_id, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await;
+        let names: Vec<&str> = tools["result"]["tools"]
+            .as_array()
+            .expect("tools should be an array")
+            .iter()
+            .filter_map(|t| t["name"].as_str())
+            .collect();
+        assert!(names.contains(&"zhi"), "tools/list should include zhi: {:?}", names);
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_health_and_cors() {
+        let (addr, shutdown, handle) = spawn_server().await;
+
+        let (status, body) = send(addr, Method::GET, "/health", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let health: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(health["status"], "healthy");
+        assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
+
+        let (status, _) = send(addr, Method::OPTIONS, "/mcp", None).await;
+        assert_eq!(status, StatusCode::OK, "CORS preflight should succeed");
+
+        let (status, _) = send(addr, Method::GET, "/unknown", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_concurrent_requests() {
+        let (addr, shutdown, handle) = spawn_server().await;
+        let session_id = open_session(addr).await;
+
+        let requests = (0..8).map(|i| {
+            let session_id = session_id.clone();
+            tokio::spawn(async move {
+                post_jsonrpc(addr, &session_id, json!({"jsonrpc": "2.0", "id": i, "method": "tools/list"})).await
+            })
+        });
+
+        for (i, request) in requests.enumerate() {
+            let response = request.await.unwrap();
+            assert_eq!(response["id"], i, "Each response should echo its request id");
+        }
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_session_lifecycle() {
+        let (addr, shutdown, handle) = spawn_server().await;
+
+        // 初始化请求分配会话 ID
+        let (status, headers, body) = send_with_headers(
+            addr, Method::POST, "/mcp", &[("Accept", "application/json, text/event-stream")], Some(initialize_request()),
+        ).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["content-type"], "text/event-stream");
+        let session_id = headers
+            .get("mcp-session-id")
+            .expect("initialize should return Mcp-Session-Id")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let messages = sse_messages(&body);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["id"], 1);
+        assert_eq!(messages[0]["result"]["protocolVersion"], "2025-03-26");
+
+        // 通知不需要回复
+        let (status, _, _) = send_with_headers(
+            addr, Method::POST, "/mcp", &[("Mcp-Session-Id", &session_id)],
+            Some(json!({"jsonrpc": "2.0", "method": "notifications/initialized"})),
+        ).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        // 带会话的请求以 SSE 返回结果
+        let (status, _, body) = send_with_headers(
+            addr, Method::POST, "/mcp",
+            &[("Accept", "application/json, text/event-stream"), ("Mcp-Session-Id", &session_id)],
+            Some(json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})),
+        ).await;
+        assert_eq!(status, StatusCode::OK);
+        let messages = sse_messages(&body);
+        assert_eq!(messages.last().unwrap()["id"], 2);
+        assert!(messages.last().unwrap()["result"]["tools"].is_array());
+
+        // 结束会话后再使用该会话应返回 404
+        let (status, _, _) = send_with_headers(addr, Method::DELETE, "/mcp", &[("Mcp-Session-Id", &session_id)], None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = send_with_headers(
+            addr, Method::POST, "/mcp", &[("Mcp-Session-Id", &session_id)],
+            Some(json!({"jsonrpc": "2.0", "id": 3, "method": "tools/list"})),
+        ).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // 未携带会话 ID 的非初始化请求返回 400
+        let (status, _, _) = send_with_headers(
+            addr, Method::POST, "/mcp", &[], Some(json!({"jsonrpc": "2.0", "id": 4, "method": "tools/list"})),
+        ).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_over_http() {
+        use cunzhi_cli::mcp::client::{ClientTarget, McpClient, RpcError};
+
+        let (addr, shutdown, handle) = spawn_server().await;
+
+        let mut client = McpClient::connect(&ClientTarget::Http(format!("http://{}/mcp", addr))).await.unwrap();
+        let info = client.initialize().await.expect("Client should initialize");
+        assert_eq!(info["serverInfo"]["name"], "cunzhi-cli");
+
+        let tools = client.list_all("tools/list", "tools").await.unwrap();
+        assert!(tools.iter().any(|tool| tool["name"] == "zhi"));
+        assert_eq!(client.request("ping", json!({})).await.unwrap(), json!({}));
+
+        let error = client.request("no/such/method", json!({})).await.unwrap_err();
+        let error = error.downcast_ref::<RpcError>().expect("JSON-RPC errors should be reported as RpcError");
+        assert_eq!(error.code, -32601);
+
+        // 客户端关闭时结束会话
+        client.close().await.unwrap();
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_get_stream_requires_session() {
+        let (addr, shutdown, handle) = spawn_server().await;
+
+        let (status, _, _) = send_with_headers(addr, Method::GET, "/mcp", &[], None).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "GET without SSE accept should be rejected");
+
+        let (status, _, _) = send_with_headers(addr, Method::GET, "/mcp", &[("Accept", "text/event-stream")], None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "GET stream requires a session");
+
+        let (status, _, _) = send_with_headers(
+            addr, Method::GET, "/mcp", &[("Accept", "text/event-stream"), ("Mcp-Session-Id", "missing")], None,
+        ).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_open_push_streams() {
+        let (addr, shutdown, handle) = spawn_server().await;
+
+        let (_, headers, _) = send_with_headers(
+            addr, Method::POST, "/mcp", &[("Accept", "application/json")], Some(initialize_request()),
+        ).await;
+        let session_id = headers["mcp-session-id"].to_str().unwrap().to_string();
+
+        let stream = tokio::spawn(async move {
+            send_with_headers(
+                addr, Method::GET, "/mcp", &[("Accept", "text/event-stream"), ("Mcp-Session-Id", &session_id)], None,
+            ).await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        shutdown.send(()).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), handle)
+            .await
+            .expect("Server should not hang on open SSE streams")
+            .unwrap();
+        let (status, headers, _) = stream.await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["content-type"], "text/event-stream");
+    }
+
+    /// 启用令牌和来源白名单的服务器，配置写入临时目录
+    async fn spawn_secured_server(
+        temp_dir: &tempfile::TempDir,
+    ) -> (SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+        use cunzhi_cli::config::AppConfig;
+        use cunzhi_cli::mcp::LiveConfig;
+
+        let mut config = AppConfig::default();
+        config.http_config.allowed_origins = vec!["http://localhost:3000".to_string()];
+        config.http_config.max_body_bytes = 1024;
+        let config_path = temp_dir.path().join("config.json");
+        std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+        let server = ZhiServer::with_config(LiveConfig::from_path(config_path))
+            .with_http_token(Some("secret-token".to_string()));
+        spawn_server_with(server).await
+    }
+
+    #[tokio::test]
+    async fn test_http_bearer_token_required() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (addr, shutdown, handle) = spawn_secured_server(&temp_dir).await;
+        let ping = json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+
+        let (status, headers, _) = send_with_headers(addr, Method::POST, "/mcp", &[], Some(ping.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "Requests without a token should be rejected");
+        assert_eq!(headers["www-authenticate"], "Bearer");
+
+        let (status, _, _) = send_with_headers(
+            addr, Method::POST, "/mcp", &[("Authorization", "Bearer wrong-token")], Some(ping.clone()),
+        ).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "Requests with a wrong token should be rejected");
+
+        let (status, _, body) = send_with_headers(
+            addr, Method::POST, "/mcp", &[("Authorization", "Bearer secret-token")], Some(initialize_request()),
+        ).await;
+        assert_eq!(status, StatusCode::OK, "Requests with the token should succeed: {}", body);
+
+        // 健康检查不需要令牌
+        let (status, _) = send(addr, Method::GET, "/health", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_origin_allowlist() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (addr, shutdown, handle) = spawn_secured_server(&temp_dir).await;
+        let ping = json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+
+        let (status, _, _) = send_with_headers(
+            addr, Method::POST, "/mcp",
+            &[("Authorization", "Bearer secret-token"), ("Origin", "http://evil.example")],
+            Some(ping.clone()),
+        ).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "Unlisted origins should be rejected even with a token");
+
+        let (status, headers, _) = send_with_headers(
+            addr, Method::OPTIONS, "/mcp", &[("Origin", "http://localhost:3000")], None,
+        ).await;
+        assert_eq!(status, StatusCode::OK, "Preflight from an allowed origin should succeed");
+        assert_eq!(headers["access-control-allow-origin"], "http://localhost:3000");
+
+        let (status, headers, _) = send_with_headers(
+            addr, Method::POST, "/mcp",
+            &[("Authorization", "Bearer secret-token"), ("Origin", "http://localhost:3000")],
+            Some(initialize_request()),
+        ).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["access-control-allow-origin"], "http://localhost:3000");
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_body_size_limit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (addr, shutdown, handle) = spawn_secured_server(&temp_dir).await;
+
+        let oversized = json!({"jsonrpc": "2.0", "id": 1, "method": "ping", "params": {"padding": "x".repeat(2048)}});
+        let (status, _, _) = send_with_headers(
+            addr, Method::POST, "/mcp", &[("Authorization", "Bearer secret-token")], Some(oversized),
+        ).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_non_loopback_bind_requires_token() {
+        use cunzhi_cli::config::default_http_config;
+        use cunzhi_cli::mcp::server::bind_http;
+
+        let mut config = default_http_config();
+        config.bind_address = "0.0.0.0".to_string();
+        config.require_token = false;
+        let error = bind_http(&config, 0).await.expect_err("Binding all interfaces without a token should fail");
+        assert!(error.to_string().contains("require_token"), "unexpected error: {}", error);
+    }
+
+    #[tokio::test]
+    async fn test_http_metrics_endpoint() {
+        let (addr, shutdown, handle) = spawn_server().await;
+
+        let call = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": "ji", "arguments": {"project_path": "/tmp"}}
+        });
+        let session_id = open_session(addr).await;
+        post_jsonrpc(addr, &session_id, call).await;
+
+        let (status, headers, body) = send_with_headers(addr, Method::GET, "/metrics", &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers["content-type"].to_str().unwrap().starts_with("text/plain"));
+        assert!(body.contains("# TYPE cunzhi_tool_calls_total counter"));
+        assert!(body.contains(r#"cunzhi_tool_calls_total{tool="ji",outcome="invalid_arguments"}"#), "{}", body);
+        assert!(body.contains("# TYPE cunzhi_zhi_wait_seconds histogram"));
+        assert!(body.contains("cunzhi_active_sessions "));
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_gateway_proxies_downstream_server() {
+        use cunzhi_cli::config::{AppConfig, DownstreamServer};
+        use cunzhi_cli::mcp::LiveConfig;
+
+        let (downstream_addr, downstream_shutdown, downstream_handle) = spawn_server().await;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let downstream = |name: &str, url: String| DownstreamServer {
+            name: name.to_string(),
+            command: Vec::new(),
+            url: Some(url),
+            token: None,
+            enabled: true,
+        };
+        let mut config = AppConfig::default();
+        config.gateway_config.servers = vec![
+            downstream("docs", format!("http://{}/mcp", downstream_addr)),
+            // 无法连接的下游被跳过，不影响其它服务器
+            downstream("offline", "http://127.0.0.1:1/mcp".to_string()),
+        ];
+        config.mcp_config.tools.insert("docs__zhi".to_string(), false);
+        let config_path = temp_dir.path().join("config.json");
+        std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+        let server = ZhiServer::with_config(LiveConfig::from_path(config_path)).connect_gateway().await;
+        let (addr, shutdown, handle) = spawn_server_with(server).await;
+        let session_id = open_session(addr).await;
+
+        let tools = post_jsonrpc(addr, &session_id, json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"})).await;
+        let names: Vec<&str> = tools["result"]["tools"].as_array().unwrap().iter()
+            .filter_map(|tool| tool["name"].as_str())
+            .collect();
+        assert!(names.contains(&"zhi") && names.contains(&"ji"), "{:?}", names);
+        assert!(names.contains(&"docs__ji"), "Downstream tools should be prefixed: {:?}", names);
+        assert!(!names.contains(&"docs__zhi"), "Disabled proxied tool should be hidden: {:?}", names);
+        assert!(!names.iter().any(|name| name.starts_with("offline__")));
+
+        let project = temp_dir.path().to_string_lossy().to_string();
+        let call = post_jsonrpc(addr, &session_id, json!({
+            "jsonrpc": "2.0", "id": 2, "method": "tools/call",
+            "params": {"name": "docs__ji", "arguments": {"action": "记忆", "project_path": project, "content": "经由网关写入", "category": "rule"}}
+        })).await;
+        assert!(call["result"]["content"][0]["text"].as_str().unwrap().contains("经由网关写入"), "{}", call);
+        assert_ne!(call["result"]["isError"], true);
+
+        // 代理工具同样先按 inputSchema 校验参数
+        let invalid = post_jsonrpc(addr, &session_id, json!({
+            "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+            "params": {"name": "docs__ji", "arguments": {}}
+        })).await;
+        assert_eq!(invalid["result"]["isError"], true, "{}", invalid);
+
+        let disabled = post_jsonrpc(addr, &session_id, json!({
+            "jsonrpc": "2.0", "id": 4, "method": "tools/call",
+            "params": {"name": "docs__zhi", "arguments": {"message": "hi"}}
+        })).await;
+        assert_eq!(disabled["result"]["isError"], true, "{}", disabled);
+
+        let prompts = post_jsonrpc(addr, &session_id, json!({"jsonrpc": "2.0", "id": 5, "method": "prompts/list"})).await;
+        let prompt_names: Vec<&str> = prompts["result"]["prompts"].as_array().unwrap().iter()
+            .filter_map(|prompt| prompt["name"].as_str())
+            .collect();
+        assert!(prompt_names.contains(&"continue") && prompt_names.contains(&"docs__continue"), "{:?}", prompt_names);
+
+        let prompt = post_jsonrpc(addr, &session_id, json!({
+            "jsonrpc": "2.0", "id": 6, "method": "prompts/get", "params": {"name": "docs__continue"}
+        })).await;
+        assert!(prompt["result"]["messages"].is_array(), "{}", prompt);
+
+        shutdown.send(()).unwrap();
+        handle.await.unwrap();
+        downstream_shutdown.send(()).unwrap();
+        downstream_handle.await.unwrap();
+    }
+}
@@ -53,7 +53,7 @@ fn test_mcp_config() {
     tools.insert("zhi".to_string(), true);
     tools.insert("ji".to_string(), false);
     
-    let mcp_config = McpConfig { tools };
+    let mcp_config = McpConfig { tools, elicitation: true };
     
     assert_eq!(mcp_config.tools.get("zhi"), Some(&true));
     assert_eq!(mcp_config.tools.get("ji"), Some(&false));
@@ -227,7 +227,13 @@ fn test_config_validation() {
                 tools.insert("ji".to_string(), true);
                 tools
             },
+            elicitation: true,
         },
+        terminal_config: cunzhi_cli::config::default_terminal_config(),
+        prompt_config: cunzhi_cli::config::default_prompt_config(),
+        http_config: cunzhi_cli::config::default_http_config(),
+        gateway_config: cunzhi_cli::config::default_gateway_config(),
+        guard_config: cunzhi_cli::config::default_guard_config(),
     };
     
     // 配置应该能够序列化和反序列化
@@ -235,6 +241,191 @@ fn test_config_validation() {
     let _: AppConfig = serde_json::from_str(&json).expect("Should deserialize");
 }
 
+#[test]
+fn test_http_config_defaults() {
+    let mut config = cunzhi_cli::config::default_http_config();
+    assert_eq!(config.bind_address, "127.0.0.1");
+    assert!(config.require_token, "Token authentication should be enabled by default");
+    assert!(config.allowed_origins.is_empty());
+    assert!(config.bind_ip().unwrap().is_loopback());
+
+    // 旧配置文件缺少 http_config 时使用默认值
+    let legacy: AppConfig = serde_json::from_str(r#"{"version": "0.2.12"}"#).expect("Should deserialize");
+    assert_eq!(legacy.http_config.bind_address, "127.0.0.1");
+
+    config.bind_address = "not-an-ip".to_string();
+    assert!(config.bind_ip().is_err());
+}
+
+#[test]
+fn test_downstream_server_validation() {
+    use cunzhi_cli::config::DownstreamServer;
+
+    let server: DownstreamServer = serde_json::from_str(r#"{"name": "docs", "command": ["docs-mcp", "--stdio"]}"#)
+        .expect("Should deserialize");
+    assert!(server.enabled, "Downstream servers should be enabled by default");
+    assert!(server.validate().is_ok());
+
+    let mut invalid = server.clone();
+    invalid.name = "docs server".to_string();
+    assert!(invalid.validate().is_err(), "Names with spaces should be rejected");
+
+    let mut both = server.clone();
+    both.url = Some("http://127.0.0.1:8080/mcp".to_string());
+    assert!(both.validate().is_err(), "command and url are mutually exclusive");
+
+    let mut neither = server.clone();
+    neither.command.clear();
+    assert!(neither.validate().is_err(), "command or url is required");
+
+    // 名称重复的配置无效
+    let mut config = AppConfig::default();
+    config.gateway_config.servers = vec![server.clone(), server];
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_guard_rule_matching() {
+    use cunzhi_cli::config::{GuardConfig, GuardRule};
+    use cunzhi_cli::mcp::guard::{wildcard_match, Guard};
+    use serde_json::json;
+
+    assert!(wildcard_match("*delete*", "fs_delete_file"));
+    assert!(wildcard_match("shell.exec", "shell.exec"));
+    assert!(!wildcard_match("shell.exec", "shell_exec"), "`.` should match literally");
+    assert!(wildcard_match("read_?", "read_a"));
+    assert!(!wildcard_match("read_?", "read_ab"));
+
+    let rule = |json: serde_json::Value| -> GuardRule { serde_json::from_value(json).unwrap() };
+    let config = GuardConfig {
+        rules: vec![
+            rule(json!({"tool": "*delete*"})),
+            rule(json!({"tool": "shell.exec", "argument": "command", "pattern": "*rm -rf*"})),
+            rule(json!({"argument": "path", "outside_project": true, "reason": "访问项目外的文件"})),
+        ],
+        audit_log: None,
+        approval_timeout_seconds: 300,
+    };
+    let temp_dir = TempDir::new().unwrap();
+    let project = temp_dir.path().join("repo");
+    std::fs::create_dir(&project).unwrap();
+    let guard = Guard::new(&config, &project, &temp_dir.path().join("audit.jsonl"), "test".to_string()).unwrap();
+
+    assert!(guard.matching_rule("delete_branch", &json!({})).is_some());
+    assert!(guard.matching_rule("shell.exec", &json!({"command": "sudo rm -rf /"})).is_some());
+    assert!(guard.matching_rule("shell.exec", &json!({"command": "ls"})).is_none());
+
+    let outside = guard.matching_rule("read_file", &json!({"path": "../secrets.txt"})).expect("Paths outside the repo should match");
+    assert_eq!(outside.reason.as_deref(), Some("访问项目外的文件"));
+    assert!(guard.matching_rule("read_file", &json!({"path": "/etc/passwd"})).is_some());
+    assert!(guard.matching_rule("read_file", &json!({"path": "src/../README.md"})).is_none());
+    let inside = project.canonicalize().unwrap().join("src/main.rs").to_string_lossy().to_string();
+    assert!(guard.matching_rule("read_file", &json!({"path": inside})).is_none());
+    // 只检查 rule.argument 指定的参数
+    assert!(guard.matching_rule("read_file", &json!({"query": "/etc/passwd"})).is_none());
+}
+
+#[test]
+fn test_guard_never_forwards_guarded_calls_without_id() {
+    use cunzhi_cli::config::GuardConfig;
+    use cunzhi_cli::mcp::guard::{Guard, Intercept};
+    use serde_json::json;
+
+    let config = GuardConfig {
+        rules: vec![serde_json::from_value(json!({"tool": "*delete*"})).unwrap()],
+        audit_log: None,
+        approval_timeout_seconds: 300,
+    };
+    let temp_dir = TempDir::new().unwrap();
+    let guard = Guard::new(&config, temp_dir.path(), &temp_dir.path().join("audit.jsonl"), "test".to_string()).unwrap();
+    let call = |id: Option<u64>, name: &str| {
+        let mut message = json!({"jsonrpc": "2.0", "method": "tools/call", "params": {"name": name, "arguments": {}}});
+        if let Some(id) = id {
+            message["id"] = json!(id);
+        }
+        message
+    };
+
+    // 单条调用：有 id 等待批准，没有 id 直接丢弃
+    assert!(matches!(guard.intercept(&call(Some(1), "delete_branch").to_string()), Intercept::Review { .. }));
+    assert!(matches!(guard.intercept(&call(None, "delete_branch").to_string()), Intercept::Drop));
+    assert!(matches!(guard.intercept(&call(None, "list_branches").to_string()), Intercept::Forward));
+
+    // 批量请求：只要包含需要批准的调用就不转发
+    let batch = json!([call(None, "delete_branch"), call(None, "list_branches")]);
+    assert!(matches!(guard.intercept(&batch.to_string()), Intercept::Drop));
+    let batch = json!([call(None, "delete_branch"), call(Some(2), "list_branches")]);
+    match guard.intercept(&batch.to_string()) {
+        Intercept::Reply(serde_json::Value::Array(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0]["id"], 2);
+            assert!(errors[0]["error"].is_object());
+        }
+        _ => panic!("Batches containing guarded calls must not be forwarded"),
+    }
+    let batch = json!([call(None, "list_branches"), call(Some(3), "list_branches")]);
+    assert!(matches!(guard.intercept(&batch.to_string()), Intercept::Forward));
+}
+
+#[test]
+fn test_guard_decision_from_interactive_replies() {
+    use cunzhi_cli::mcp::guard::{decision_from_response, GuardDecision};
+    use cunzhi_cli::mcp::tools::interaction::reply::reply_to_response;
+    use serde_json::json;
+
+    let arguments = json!({"path": "../secrets.txt"});
+    // 终端窗口和 `cunzhi prompt` 的回复文本
+    let decide = |reply: &str| decision_from_response(&reply_to_response(reply, "req", "cli", "继续"), &arguments);
+
+    assert_eq!(decide("用户选择: 批准"), GuardDecision::Approve { arguments: arguments.clone(), edited: false });
+    assert!(matches!(decide("用户选择: 拒绝"), GuardDecision::Deny { .. }));
+    // 自定义输入的 JSON 对象作为新参数
+    assert_eq!(
+        decide(r#"用户输入: {"path": "src/main.rs"}"#),
+        GuardDecision::Approve { arguments: json!({"path": "src/main.rs"}), edited: true }
+    );
+    // 非 JSON 对象的输入和直接回车都不会放行
+    assert!(matches!(decide("用户输入: 改成 src 下的文件"), GuardDecision::Deny { .. }));
+    assert!(matches!(decide(r#"用户输入: ["src/main.rs"]"#), GuardDecision::Deny { .. }));
+    assert!(matches!(decide("用户输入: "), GuardDecision::Deny { .. }));
+}
+
+#[test]
+fn test_metrics_snapshot_render_and_merge() {
+    use cunzhi_cli::mcp::metrics::{Histogram, MetricsSnapshot};
+
+    let mut first = MetricsSnapshot::default();
+    first.tool_calls.entry("zhi".to_string()).or_default().insert("success".to_string(), 2);
+    // 桶：≤1s 1 次，≤15s 1 次
+    let mut buckets = vec![0; 11];
+    buckets[0] = 1;
+    buckets[2] = 1;
+    first.zhi_waits.insert("ui_process".to_string(), Histogram { buckets, sum: 10.5, count: 2 });
+    first.active_sessions = 1;
+
+    let mut second = first.clone();
+    second.memory_writes.insert("rule".to_string(), 3);
+    first.merge(&second);
+
+    assert_eq!(first.tool_call_total("zhi"), 4);
+    assert_eq!(first.memory_writes["rule"], 3);
+    assert_eq!(first.active_sessions, 2);
+    let waits = &first.zhi_waits["ui_process"];
+    assert_eq!(waits.count, 4);
+    assert_eq!(waits.mean(), Some(5.25));
+    assert_eq!(waits.quantile_upper_bound(0.5), Some(1.0));
+    assert_eq!(waits.quantile_upper_bound(0.9), Some(15.0));
+
+    let text = first.render_prometheus();
+    assert!(text.contains(r#"cunzhi_tool_calls_total{tool="zhi",outcome="success"} 4"#));
+    // 直方图的桶是累积的
+    assert!(text.contains(r#"cunzhi_zhi_wait_seconds_bucket{backend="ui_process",le="5"} 2"#));
+    assert!(text.contains(r#"cunzhi_zhi_wait_seconds_bucket{backend="ui_process",le="15"} 4"#));
+    assert!(text.contains(r#"cunzhi_zhi_wait_seconds_bucket{backend="ui_process",le="+Inf"} 4"#));
+    assert!(text.contains(r#"cunzhi_zhi_wait_seconds_sum{backend="ui_process"} 21"#));
+    assert!(text.contains("cunzhi_active_sessions 2"));
+}
+
 #[test]
 fn test_mcp_tools_configuration() {
     let mut tools = HashMap::new();
@@ -242,7 +433,7 @@ fn test_mcp_tools_configuration() {
     tools.insert("ji".to_string(), false);
     tools.insert("custom_tool".to_string(), true);
     
-    let mcp_config = McpConfig { tools };
+    let mcp_config = McpConfig { tools, elicitation: true };
     
     // 测试工具状态查询
     assert!(mcp_config.tools.get("zhi").copied().unwrap_or(false));
@@ -299,3 +490,251 @@ fn test_concurrent_config_access() {
         handle.join().expect("Thread should complete");
     }
 }
+
+#[test]
+fn test_validate_arguments() {
+    use cunzhi_cli::mcp::validate_arguments;
+    use serde_json::json;
+
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "name": {"type": "string", "minLength": 1},
+            "tags": {"type": "array", "items": {"type": "string"}},
+            "mode": {"type": ["string", "null"], "enum": ["a", "b", null]},
+            "nested": {
+                "type": "object",
+                "properties": {"count": {"type": "integer", "minimum": 0}},
+                "additionalProperties": false
+            }
+        },
+        "required": ["name"]
+    });
+
+    assert!(validate_arguments(&schema, &json!({"name": "x"})).is_ok());
+    assert!(validate_arguments(&schema, &json!({"name": "x", "mode": null, "tags": []})).is_ok());
+
+    let err = validate_arguments(&schema, &json!({})).unwrap_err();
+    assert_eq!(err.path, "name");
+
+    let err = validate_arguments(&schema, &json!("oops")).unwrap_err();
+    assert_eq!(err.path, "arguments");
+
+    let err = validate_arguments(&schema, &json!({"name": ""})).unwrap_err();
+    assert_eq!(err.path, "name");
+
+    let err = validate_arguments(&schema, &json!({"name": "x", "tags": ["a", 1]})).unwrap_err();
+    assert_eq!(err.path, "tags[1]");
+
+    let err = validate_arguments(&schema, &json!({"name": "x", "mode": "c"})).unwrap_err();
+    assert_eq!(err.path, "mode");
+
+    let err = validate_arguments(&schema, &json!({"name": "x", "nested": {"count": -1}})).unwrap_err();
+    assert_eq!(err.path, "nested.count");
+
+    let err = validate_arguments(&schema, &json!({"name": "x", "nested": {"extra": 1}})).unwrap_err();
+    assert_eq!(err.path, "nested.extra");
+    assert!(err.to_string().contains("nested.extra"));
+}
+
+#[test]
+fn test_prompt_templates() {
+    use cunzhi_cli::config::{PromptArgument, PromptTemplate};
+    use cunzhi_cli::mcp::prompts::{get_prompt, list_prompts};
+    use serde_json::json;
+
+    let mut config = AppConfig::default();
+    config.reply_config.continue_prompt = "继续写".to_string();
+
+    let listed = list_prompts(&config);
+    let names: Vec<&str> = listed["prompts"].as_array().unwrap()
+        .iter().map(|p| p["name"].as_str().unwrap()).collect();
+    assert_eq!(names[0], "continue");
+    assert!(names.contains(&"best-practice"));
+
+    let prompt = get_prompt(&config, "continue", &json!(null)).unwrap();
+    assert_eq!(prompt["messages"][0]["content"]["text"], "继续写");
+
+    // 提供的参数替换占位符，缺省的参数使用默认值
+    let prompt = get_prompt(&config, "best-practice", &json!({"project_path": "/srv/app"})).unwrap();
+    let text = prompt["messages"][0]["content"]["text"].as_str().unwrap();
+    assert!(text.contains("/srv/app") && text.contains("中文") && !text.contains("{{"));
+
+    config.prompt_config.templates.push(PromptTemplate {
+        name: "translate".to_string(),
+        description: "翻译".to_string(),
+        template: "把 {{file}} 翻译成{{language}}".to_string(),
+        arguments: vec![
+            PromptArgument { name: "file".to_string(), description: String::new(), required: true, default: None },
+            PromptArgument { name: "language".to_string(), description: String::new(), required: false, default: None },
+        ],
+    });
+    let err = get_prompt(&config, "translate", &json!({})).unwrap_err();
+    assert_eq!(err.code(), -32602);
+    let prompt = get_prompt(&config, "translate", &json!({"file": "a.md", "language": "英文"})).unwrap();
+    assert_eq!(prompt["messages"][0]["content"]["text"], "把 a.md 翻译成英文");
+
+    assert_eq!(get_prompt(&config, "missing", &json!({})).unwrap_err().code(), -32602);
+
+    // 重名模板无法通过校验
+    let duplicate = config.prompt_config.templates[0].clone();
+    config.prompt_config.templates.push(duplicate);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_elicitation_mapping() {
+    use cunzhi_cli::mcp::tools::interaction::elicitation::{elicitation_params, elicitation_response};
+    use cunzhi_cli::mcp::ZhiRequest;
+    use serde_json::json;
+
+    let request = ZhiRequest {
+        message: "选择下一步".to_string(),
+        predefined_options: vec!["重构".to_string(), "写测试".to_string()],
+        is_markdown: true,
+        terminal_mode: None,
+    };
+
+    let params = elicitation_params(&request);
+    assert_eq!(params["message"], "选择下一步");
+    let properties = &params["requestedSchema"]["properties"];
+    assert_eq!(properties["option_1"]["title"], "重构");
+    assert_eq!(properties["option_2"]["type"], "boolean");
+    assert_eq!(properties["user_input"]["type"], "string");
+
+    let accepted = json!({"action": "accept", "content": {"option_2": true, "user_input": "  顺便更新文档 "}});
+    let response = elicitation_response(&request, &accepted, "req-1", "继续").unwrap();
+    assert_eq!(response["selected_options"], json!(["写测试"]));
+    assert_eq!(response["user_input"], "顺便更新文档");
+    assert_eq!(response["metadata"]["request_id"], "req-1");
+    assert_eq!(response["metadata"]["source"], "elicitation");
+
+    // 什么都没填时使用继续提示词
+    let empty = json!({"action": "accept", "content": {}});
+    assert_eq!(elicitation_response(&request, &empty, "req-2", "继续").unwrap()["user_input"], "继续");
+
+    let declined = elicitation_response(&request, &json!({"action": "decline"}), "req-3", "继续").unwrap();
+    assert_eq!(declined["user_input"], "用户拒绝回复");
+    let cancelled = elicitation_response(&request, &json!({"action": "cancel"}), "req-4", "继续").unwrap();
+    assert_eq!(cancelled["user_input"], "用户取消了操作");
+    assert_eq!(cancelled["selected_options"], json!([]));
+
+    assert!(elicitation_response(&request, &json!({"action": "maybe"}), "req-5", "继续").is_err());
+}
+
+#[test]
+fn test_control_reply_mapping() {
+    use cunzhi_cli::mcp::tools::interaction::pending::{control_response, ControlReply};
+    use serde_json::json;
+
+    let answered = control_response(
+        ControlReply::Answer { text: Some(" 先跑测试 ".to_string()), options: vec!["是".to_string()] },
+        "req-1",
+        "继续",
+    );
+    assert_eq!(answered["user_input"], "先跑测试");
+    assert_eq!(answered["selected_options"], json!(["是"]));
+    assert_eq!(answered["metadata"]["request_id"], "req-1");
+    assert_eq!(answered["metadata"]["source"], "control");
+
+    // 只给选项时没有输入文本，什么都不给时等同于继续
+    let option_only = control_response(ControlReply::Answer { text: None, options: vec!["否".to_string()] }, "req-2", "继续");
+    assert!(option_only["user_input"].is_null());
+    let empty = control_response(ControlReply::Answer { text: Some("  ".to_string()), options: vec![] }, "req-3", "继续");
+    assert_eq!(empty["user_input"], "继续");
+
+    let cancelled = control_response(ControlReply::Cancel, "req-4", "继续");
+    assert_eq!(cancelled["user_input"], "用户取消了操作");
+}
+
+#[test]
+fn test_interaction_replies_are_structured() {
+    use cunzhi_cli::mcp::tools::interaction::reply::{popup_output_to_response, reply_to_response};
+    use serde_json::json;
+
+    let selected = reply_to_response("用户选择: 重构", "req-1", "cli", "继续");
+    assert_eq!(selected["selected_options"], json!(["重构"]));
+    assert_eq!(selected["user_input"], json!(null));
+    assert_eq!(selected["metadata"]["source"], "cli");
+    assert_eq!(selected["metadata"]["request_id"], "req-1");
+
+    let typed = reply_to_response("用户输入: 加上日志\n", "req-2", "terminal", "继续");
+    assert_eq!(typed["user_input"], "加上日志");
+    assert_eq!(typed["selected_options"], json!([]));
+
+    // 确认继续和空输入都使用继续提示词
+    assert_eq!(reply_to_response("用户确认继续", "req-3", "cli", "请继续")["user_input"], "请继续");
+    assert_eq!(reply_to_response("用户输入: ", "req-4", "terminal", "请继续")["user_input"], "请继续");
+    assert_eq!(reply_to_response("用户取消了操作", "req-5", "cli", "请继续")["user_input"], "用户取消了操作");
+
+    // UI 进程输出的最后一行是响应 JSON
+    let output = format!("🤖 寸止 AI 助手\n消息\n{}\n", reply_to_response("用户选择: 是", "req-6", "popup", "继续"));
+    let response = popup_output_to_response(&output, "req-6", "继续");
+    assert_eq!(response["selected_options"], json!(["是"]));
+    assert_eq!(response["metadata"]["source"], "popup");
+
+    // 旧版本 UI 进程只输出文本
+    let legacy = popup_output_to_response("标题\n用户输入: 好的", "req-7", "继续");
+    assert_eq!(legacy["user_input"], "好的");
+    assert_eq!(legacy["metadata"]["source"], "popup");
+}
+
+#[test]
+fn test_parse_mcp_response_images() {
+    use cunzhi_cli::mcp::handlers::response::parse_mcp_response;
+    use cunzhi_cli::mcp::tools::interaction::reply::popup_output_to_response;
+    use cunzhi_cli::mcp::Content;
+    use serde_json::json;
+
+    // 新格式：图片在前，文字摘要在后
+    let response = json!({
+        "user_input": "看看这个报错",
+        "selected_options": ["修复"],
+        "images": [{"data": "iVBORw0KGgo=", "media_type": "image/png", "filename": "error.png"}],
+        "metadata": {"timestamp": null, "request_id": "req-1", "source": "popup"}
+    });
+    let contents = parse_mcp_response(&response.to_string()).unwrap();
+    assert_eq!(contents[0], Content::image("iVBORw0KGgo=".to_string(), "image/png".to_string()));
+    let text = contents[1].as_text().unwrap();
+    assert!(text.starts_with("选择的选项: 修复\n\n看看这个报错"));
+    assert!(text.contains("文件名: error.png"));
+    assert_eq!(
+        serde_json::to_value(&contents[0]).unwrap(),
+        json!({"type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png"})
+    );
+
+    // 旧格式的内容数组
+    let legacy = json!([
+        {"type": "text", "text": "截图如下"},
+        {"type": "image", "source": {"type": "base64", "media_type": "image/jpeg", "data": "/9j/4AAQ"}}
+    ]).to_string();
+    let contents = parse_mcp_response(&legacy).unwrap();
+    assert_eq!(contents.len(), 2);
+    assert!(matches!(&contents[0], Content::Image { mime_type, .. } if mime_type == "image/jpeg"));
+    assert!(contents[1].as_text().unwrap().starts_with("截图如下"));
+
+    // 旧版 UI 输出的内容数组转换为新格式
+    let response = popup_output_to_response(&legacy, "req-2", "继续");
+    assert_eq!(response["user_input"], "截图如下");
+    assert_eq!(response["images"][0]["media_type"], "image/jpeg");
+    assert_eq!(response["metadata"]["request_id"], "req-2");
+
+    assert_eq!(parse_mcp_response("用户取消了操作").unwrap(), vec![Content::text("用户取消了操作".to_string())]);
+    assert_eq!(parse_mcp_response("纯文本").unwrap()[0].as_text(), Some("纯文本"));
+}
+
+#[test]
+fn test_root_uri_to_path() {
+    use cunzhi_cli::mcp::root_path;
+    use std::path::PathBuf;
+
+    assert_eq!(root_path("file:///home/dev/app"), Some(PathBuf::from("/home/dev/app")));
+    assert_eq!(root_path("file://localhost/home/dev/app"), Some(PathBuf::from("/home/dev/app")));
+    assert_eq!(root_path("file:///home/dev/my%20app"), Some(PathBuf::from("/home/dev/my app")));
+    assert_eq!(root_path("file:///C:/work/app"), Some(PathBuf::from("C:/work/app")));
+
+    // 只支持本地文件根目录
+    assert_eq!(root_path("https://example.com/app"), None);
+    assert_eq!(root_path("file://host-only"), None);
+    assert_eq!(root_path("file:///bad%zzpath"), None);
+}